@@ -16,12 +16,9 @@ fn start(mut commands: Commands, mut typst_server: ResMut<TypstTextureServer>) {
     commands.spawn(Sprite {
         image: typst_server.add_job(
             StructuredInMemoryTemplate {
-                loaded_toml: Default::default(),
-                loaded_fonts: Default::default(),
                 loaded_main: MAIN_DOT_TYP.to_string(),
                 path_given: "manually_defined".into(),
-                file_resolver: Default::default(),
-                source_resolver: Default::default(),
+                ..default()
             },
             TypstJobOptions::default(),
         ),
@@ -0,0 +1,72 @@
+use bevy_ecs::resource::Resource;
+use typst::foundations::{Dict, IntoValue};
+
+/// The key ambient inputs are placed under in every job's input dict, i.e. `sys.inputs.ambient.locale`.
+pub const AMBIENT_INPUT_KEY: &str = "ambient";
+
+/// Inputs shared by every job, such as the current locale or UI theme, injected under
+/// [`AMBIENT_INPUT_KEY`] when a job is compiled. Individual jobs can shadow these through
+/// [`crate::TypstJobOptions::ambient_overrides`] without changing this resource. Jobs whose own input already
+/// has an [`AMBIENT_INPUT_KEY`] key keep it, and jobs with no ambient inputs set don't get the key at all.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct TypstAmbientInputs {
+    /// A locale identifier, i.e. `"en-GB"`.
+    pub locale: Option<String>,
+    /// The name of the current theme, i.e. `"dark"`.
+    pub theme: Option<String>,
+    /// A time in seconds, such as the elapsed game time.
+    pub time: Option<f64>,
+    /// Any other ambient values.
+    pub extra: Dict,
+}
+
+impl TypstAmbientInputs {
+    /// These inputs with any values set in `overrides` taking priority.
+    pub fn shadowed_by(&self, overrides: &TypstAmbientInputs) -> TypstAmbientInputs {
+        let mut extra = self.extra.clone();
+        for (key, value) in overrides.extra.clone() {
+            extra.insert(key, value);
+        }
+        TypstAmbientInputs {
+            locale: overrides.locale.clone().or_else(|| self.locale.clone()),
+            theme: overrides.theme.clone().or_else(|| self.theme.clone()),
+            time: overrides.time.or(self.time),
+            extra,
+        }
+    }
+
+    pub fn to_dict(&self) -> Dict {
+        let mut dict = self.extra.clone();
+        if let Some(locale) = &self.locale {
+            dict.insert("locale".into(), locale.as_str().into_value());
+        }
+        if let Some(theme) = &self.theme {
+            dict.insert("theme".into(), theme.as_str().into_value());
+        }
+        if let Some(time) = self.time {
+            dict.insert("time".into(), time.into_value());
+        }
+        dict
+    }
+
+    /// Put these inputs in a job's input under [`AMBIENT_INPUT_KEY`], unless it already has the key or there
+    /// are none.
+    pub(crate) fn insert_into(&self, input: &mut Dict) {
+        let ambient = self.to_dict();
+        if !ambient.is_empty() && !input.contains(AMBIENT_INPUT_KEY) {
+            input.insert(AMBIENT_INPUT_KEY.into(), ambient.into_value());
+        }
+    }
+
+    /// If the inputs of a job with `overrides` differ from `last`, which is set to them.
+    pub(crate) fn resolved_changed(
+        &self,
+        overrides: &TypstAmbientInputs,
+        last: &mut Option<Dict>,
+    ) -> bool {
+        let resolved = self.shadowed_by(overrides).to_dict();
+        let changed = last.as_ref() != Some(&resolved);
+        *last = Some(resolved);
+        changed
+    }
+}
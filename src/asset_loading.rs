@@ -1,22 +1,196 @@
-use std::io::Cursor;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
 
 use bevy_app::{App, Plugin};
-use bevy_asset::{Asset, AssetApp, AssetLoader, AsyncReadExt};
+use bevy_asset::{Asset, AssetApp, AssetLoader, AssetPath, AssetServer};
+use bevy_ecs::resource::Resource;
 use bevy_reflect::TypePath;
+use serde::{Deserialize, Serialize};
 
-use crate::file_resolver::{FilePreloaderError, StructuredInMemoryTemplate};
+use crate::{
+    directory::TypstDirectoryLoader,
+    file_resolver::{
+        FilePreloaderError, StructuredInMemoryTemplate, TemplateLoadDiagnostic,
+        TypstTemplateDiagnostics, decode_text,
+    },
+    font_library::TypstEngineFonts,
+    logging::typst_log,
+    processed::{TypstProcessedLoader, TypstProcessedSaver, TypstTemplateProcessor},
+    progress::{TypstLoadProgress, TypstLoadStage},
+    shared_files::{SharedFilesLoad, system_prune_shared_files},
+    static_document::TypstStaticLoader,
+};
 
-pub struct AssetPluginForTypstTextures;
+#[derive(Debug, Default)]
+pub struct AssetPluginForTypstTextures {
+    pub archive_limits: TypstArchiveLimits,
+}
 
 impl Plugin for AssetPluginForTypstTextures {
     fn build(&self, app: &mut App) {
         app.init_asset::<TypstTemplate>();
-        app.init_asset_loader::<TypstZipLoader>();
+        let passwords = TypstArchivePasswords::default();
+        app.insert_resource(passwords.clone());
+        let progress = TypstLoadProgress::default();
+        app.insert_resource(progress.clone());
+        let engine_fonts = TypstEngineFonts::default();
+        app.insert_resource(engine_fonts.clone());
+        // Registered before the zip loader so that stays the loader for templates of other extensions.
+        app.register_asset_loader(TypstDirectoryLoader {
+            archive_limits: self.archive_limits.clone(),
+            asset_server: app.world().get_resource::<AssetServer>().cloned(),
+        });
+        app.register_asset_loader(TypstZipLoader {
+            archive_limits: self.archive_limits.clone(),
+            passwords: passwords.clone(),
+            progress: progress.clone(),
+        });
+        app.register_asset_loader(TypstProcessedLoader);
+        app.register_asset_processor::<TypstTemplateProcessor>(TypstProcessedSaver.into());
+        app.set_default_asset_processor::<TypstTemplateProcessor>("typdir");
+        app.register_asset_loader(TypstStaticLoader {
+            archive_limits: self.archive_limits.clone(),
+            passwords,
+            progress,
+            engine_fonts,
+        });
+        app.add_systems(bevy_app::Last, system_prune_shared_files);
     }
 }
 
 #[derive(Debug, Default, TypePath)]
-pub struct TypstZipLoader;
+pub struct TypstZipLoader {
+    pub archive_limits: TypstArchiveLimits,
+    pub passwords: TypstArchivePasswords,
+    /// Updated as archives are read and extracted, see [`TypstLoadProgress`].
+    pub progress: TypstLoadProgress,
+}
+
+/// Passwords for encrypted zip archives by asset path, shared between the template loaders and the
+/// [`crate::TypstTextureServer`], for keeping passwords out of `.meta` files. See
+/// [`crate::TypstTextureServer::set_archive_password`]. Passwords have to be set before their archive loads, and
+/// are used over none being set in [`TypstTemplateSettings::password`].
+#[derive(Clone, Default, Resource)]
+pub struct TypstArchivePasswords(Arc<RwLock<HashMap<PathBuf, String>>>);
+
+impl TypstArchivePasswords {
+    /// Set the password of the archive at an asset path.
+    pub fn insert(&self, path: impl Into<PathBuf>, password: impl Into<String>) {
+        self.0.write().unwrap().insert(path.into(), password.into());
+    }
+
+    /// Forget the password of the archive at an asset path.
+    pub fn remove(&self, path: &Path) {
+        self.0.write().unwrap().remove(path);
+    }
+
+    pub fn get(&self, path: &Path) -> Option<String> {
+        self.0.read().unwrap().get(path).cloned()
+    }
+}
+
+impl std::fmt::Debug for TypstArchivePasswords {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "TypstArchivePasswords({} archives)",
+            self.0.read().unwrap().len()
+        )
+    }
+}
+
+/// Limits on how much a zipped template may decompress to, protecting against zip bombs and accidentally
+/// huge archives. Sizes are checked against the bytes actually decompressed, not the sizes archives declare.
+#[derive(Debug, Clone)]
+pub struct TypstArchiveLimits {
+    /// Maximum number of entries in the archive, including folders. Defaults to 10,000.
+    pub max_entries: Option<usize>,
+    /// Maximum decompressed size of any single file. Defaults to 128 MiB.
+    pub max_entry_bytes: Option<u64>,
+    /// Maximum decompressed size of all files together. Defaults to 512 MiB.
+    pub max_total_bytes: Option<u64>,
+}
+
+impl Default for TypstArchiveLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: Some(10_000),
+            max_entry_bytes: Some(128 * 1024 * 1024),
+            max_total_bytes: Some(512 * 1024 * 1024),
+        }
+    }
+}
+
+impl TypstArchiveLimits {
+    pub fn unlimited() -> Self {
+        Self {
+            max_entries: None,
+            max_entry_bytes: None,
+            max_total_bytes: None,
+        }
+    }
+}
+
+/// How a template is found within its archive or directory. These are the loader settings of
+/// [`TypstZipLoader`] and [`TypstDirectoryLoader`], so they can be set in a `.meta` file or with
+/// `AssetServer::load_with_settings`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TypstTemplateSettings {
+    /// The template's main file, relative to its root. Takes priority over an `entrypoint` in `package.toml` or
+    /// `typst.toml`. When none of these are set, the first of [`crate::file_resolver::DEFAULT_ENTRY_POINTS`]
+    /// found is used.
+    pub main: Option<PathBuf>,
+    /// The folder within the archive that holds the template, with anything outside of it left out. When not
+    /// set, a folder that every file of an archive is inside of is used as its root, if there is one.
+    pub root: Option<PathBuf>,
+    /// Glob patterns of files to leave out, matched against their paths relative to the root, i.e.
+    /// `"drafts/**"` or `"*.psd"`.
+    pub ignore: Vec<String>,
+    /// Decompress images, PDFs and other binary files of a zip archive when a job first uses them, rather than
+    /// while loading, to avoid a spike in memory for art-heavy templates. The compressed archive is kept in
+    /// memory for as long as the template is. Members still count towards the [`TypstArchiveLimits`] as they're
+    /// decompressed, and one that goes over them is logged and left unresolved. Defaults to `false`.
+    pub lazy_members: bool,
+    /// Only read the metadata of a template's fonts while loading, parsing them when the template's first job
+    /// builds an engine, so loading doesn't stall on parsing fonts. Defaults to `false`.
+    pub lazy_fonts: bool,
+    /// The password of an encrypted zip archive, for ZipCrypto or AES encryption. This is only light protection, as
+    /// the password ships with the game, and in plain text if set in a `.meta` file; see
+    /// [`TypstArchivePasswords`] for setting it from code instead.
+    pub password: Option<String>,
+    /// The templates that led to this one being loaded as one of their [`crate::file_resolver::BevyTypstDotToml::dependencies`], from
+    /// the outermost in. Set by the loader to catch dependency cycles.
+    #[serde(skip)]
+    pub dependency_chain: Vec<PathBuf>,
+}
+
+/// Which of the [`TypstArchiveLimits`] an archive exceeded.
+#[derive(Debug, Clone)]
+pub enum TypstArchiveLimitExceeded {
+    Entries { count: usize, limit: usize },
+    EntryBytes { entry: String, limit: u64 },
+    TotalBytes { limit: u64 },
+}
+
+impl std::fmt::Display for TypstArchiveLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypstArchiveLimitExceeded::Entries { count, limit } => {
+                write!(f, "archive has {count} entries, over the limit of {limit}")
+            }
+            TypstArchiveLimitExceeded::EntryBytes { entry, limit } => {
+                write!(f, "'{entry}' decompresses to over {limit} bytes")
+            }
+            TypstArchiveLimitExceeded::TotalBytes { limit } => {
+                write!(f, "archive decompresses to over {limit} bytes")
+            }
+        }
+    }
+}
 
 #[non_exhaustive]
 #[derive(Debug)]
@@ -25,6 +199,27 @@ pub enum TypstAssetError {
     Zip(zip::result::ZipError),
     Preloader(FilePreloaderError),
     UnsupportedFormat,
+    LimitExceeded(TypstArchiveLimitExceeded),
+    /// A static document failed to compile while loading.
+    Compile(typst_as_lib::TypstAsLibError),
+    /// A text file, named by its path within the archive, couldn't be decoded.
+    InvalidText {
+        entry: String,
+        reason: String,
+    },
+    /// A file or folder of a template directory couldn't be read from its asset source.
+    AssetRead {
+        path: PathBuf,
+        reason: String,
+    },
+    /// The [`TypstTemplateSettings`] the template was loaded with are invalid.
+    InvalidSettings(String),
+    /// A processed template, see [`crate::processed::TypstProcessedLoader`], is malformed or from another version.
+    Processed(String),
+    /// A static document, see [`crate::static_document::TypstStaticLoader`], compiled to no pages.
+    EmptyDocument,
+    /// A template depends on itself through its [`crate::file_resolver::BevyTypstDotToml::dependencies`], by way of the templates given.
+    DependencyCycle(Vec<PathBuf>),
 }
 
 impl std::fmt::Display for TypstAssetError {
@@ -39,6 +234,43 @@ impl std::fmt::Display for TypstAssetError {
                 f,
                 "TypstAssetError::UnsupportedFormat: Neither a .zip archive or a standalone .typ file"
             ),
+            TypstAssetError::LimitExceeded(exceeded) => {
+                write!(f, "TypstAssetError::LimitExceeded: {exceeded}")
+            }
+            TypstAssetError::Compile(error) => write!(f, "TypstAssetError::Compile: {error}"),
+            TypstAssetError::InvalidText { entry, reason } => {
+                write!(f, "TypstAssetError::InvalidText: '{entry}' {reason}")
+            }
+            TypstAssetError::AssetRead { path, reason } => {
+                write!(
+                    f,
+                    "TypstAssetError::AssetRead: '{}' {reason}",
+                    path.display()
+                )
+            }
+            TypstAssetError::InvalidSettings(reason) => {
+                write!(f, "TypstAssetError::InvalidSettings: {reason}")
+            }
+            TypstAssetError::Processed(reason) => {
+                write!(f, "TypstAssetError::Processed: {reason}")
+            }
+            TypstAssetError::EmptyDocument => {
+                write!(
+                    f,
+                    "TypstAssetError::EmptyDocument: The document has no pages"
+                )
+            }
+            TypstAssetError::DependencyCycle(chain) => {
+                let chain: Vec<String> = chain
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                write!(
+                    f,
+                    "TypstAssetError::DependencyCycle: {}",
+                    chain.join(" -> ")
+                )
+            }
         }
     }
 }
@@ -48,58 +280,223 @@ impl std::error::Error for TypstAssetError {}
 #[derive(Debug, Asset, TypePath)]
 pub struct TypstTemplate(pub StructuredInMemoryTemplate);
 
+impl TypstTemplate {
+    /// The recoverable problems found while loading this template.
+    pub fn diagnostics(&self) -> &TypstTemplateDiagnostics {
+        &self.0.load_diagnostics
+    }
+}
+
 impl AssetLoader for TypstZipLoader {
     type Asset = TypstTemplate;
 
-    type Settings = ();
+    type Settings = TypstTemplateSettings;
 
     type Error = TypstAssetError;
 
     async fn load(
         &self,
         reader: &mut dyn bevy_asset::io::Reader,
-        _settings: &Self::Settings,
+        settings: &Self::Settings,
         load_context: &mut bevy_asset::LoadContext<'_>,
     ) -> std::result::Result<Self::Asset, Self::Error> {
-        if load_context
-            .path()
-            .get_full_extension()
-            .is_some_and(|ext| ext == "zip")
+        read_template(
+            reader,
+            load_context,
+            settings,
+            &self.archive_limits,
+            &self.passwords,
+            &self.progress,
+        )
+        .await
+        .map(TypstTemplate)
+    }
+}
+
+/// Read a zipped template or standalone `.typ` file, as per the path's extension, along with the files its
+/// `package.toml` requests from the game's assets. Archives with a password in `passwords` are decrypted with it
+/// when the settings don't have one.
+pub(crate) async fn read_template(
+    reader: &mut dyn bevy_asset::io::Reader,
+    load_context: &mut bevy_asset::LoadContext<'_>,
+    settings: &TypstTemplateSettings,
+    archive_limits: &TypstArchiveLimits,
+    passwords: &TypstArchivePasswords,
+    progress: &TypstLoadProgress,
+) -> Result<StructuredInMemoryTemplate, TypstAssetError> {
+    let _sharing = SharedFilesLoad::start();
+    let registered = settings
+        .password
+        .is_none()
+        .then(|| passwords.get(load_context.path().path()))
+        .flatten()
+        .map(|password| TypstTemplateSettings {
+            password: Some(password),
+            ..settings.clone()
+        });
+    let settings = registered.as_ref().unwrap_or(settings);
+    let path = load_context.path().clone_owned();
+    let template =
+        read_template_files(reader, load_context, settings, archive_limits, progress).await;
+    progress.finish(&path);
+    let mut template = template?;
+    read_asset_requests(&mut template, load_context, settings).await?;
+    Ok(template)
+}
+
+/// Read the files a template's `package.toml` requests from the game's assets into it, and mount the templates
+/// it depends on. Errors if a dependency is the template itself or one of the templates that depend on it.
+pub(crate) async fn read_asset_requests(
+    template: &mut StructuredInMemoryTemplate,
+    load_context: &mut bevy_asset::LoadContext<'_>,
+    settings: &TypstTemplateSettings,
+) -> Result<(), TypstAssetError> {
+    let mut chain = settings.dependency_chain.clone();
+    chain.push(load_context.path().path().to_path_buf());
+    let dependencies = template.loaded_toml.dependencies.clone();
+    for (folder, path) in dependencies {
+        if chain.contains(&path) {
+            chain.push(path);
+            return Err(TypstAssetError::DependencyCycle(chain));
+        }
+        let dependency_chain = chain.clone();
+        match load_context
+            .loader()
+            .with_settings(move |settings: &mut TypstTemplateSettings| {
+                settings.dependency_chain = dependency_chain.clone();
+            })
+            .immediate()
+            .load::<TypstTemplate>(AssetPath::from(path.clone()))
+            .await
         {
-            let mut buffer: Vec<u8> = vec![];
-            reader
-                .read_to_end(&mut buffer)
-                .await
-                .map_err(TypstAssetError::Io)?;
-            let cursor = Cursor::new(buffer);
-            let zip = zip::ZipArchive::new(cursor).map_err(TypstAssetError::Zip)?;
-            let resolver = StructuredInMemoryTemplate::from_zip(zip)?;
-            Ok(TypstTemplate(resolver))
-        } else if load_context
-            .path()
-            .get_full_extension()
-            .is_some_and(|ext| ext == "typ")
+            Ok(dependency) => template.mount(&folder, &dependency.get().0),
+            Err(error) => {
+                template
+                    .load_diagnostics
+                    .push(TemplateLoadDiagnostic::DependencyFailed {
+                        path: path.clone(),
+                        reason: error.to_string(),
+                    })
+            }
+        }
+    }
+    let requests = template.loaded_toml.asset_requests.clone();
+    for (path, hint) in &requests {
+        match load_context
+            .read_asset_bytes(AssetPath::from(path.clone()))
+            .await
         {
-            // Standalone file.
-            if cfg!(not(any(
-                feature = "typst-asset-fonts",
-                feature = "typst-search-system-fonts"
-            ))) {
-                bevy_log::warn!(
-                    "[TYPST WARNING] Standalone typst file being loaded without either of the 'typst-asset-fonts' or 'typst-search-system-fonts' features enabled. Compilation may fail if text is output is displayed."
-                );
+            Ok(bytes) => template.add_requested_asset(path, hint.as_ref(), bytes),
+            Err(error) => {
+                template
+                    .load_diagnostics
+                    .push(TemplateLoadDiagnostic::AssetRequestFailed {
+                        path: path.clone(),
+                        reason: error.to_string(),
+                    })
             }
-            let mut buffer = String::new();
-            reader
-                .read_to_string(&mut buffer)
-                .await
-                .map_err(TypstAssetError::Io)?;
-            Ok(TypstTemplate(StructuredInMemoryTemplate {
-                loaded_main: buffer,
-                ..Default::default()
-            }))
-        } else {
-            Err(TypstAssetError::UnsupportedFormat)
         }
     }
+    Ok(())
+}
+
+/// If a path's full extension, such as `static.zip`, ends with `extension`.
+fn has_extension(full_extension: &str, extension: &str) -> bool {
+    full_extension == extension
+        || full_extension
+            .strip_suffix(extension)
+            .is_some_and(|rest| rest.ends_with('.'))
+}
+
+async fn read_template_files(
+    reader: &mut dyn bevy_asset::io::Reader,
+    load_context: &bevy_asset::LoadContext<'_>,
+    settings: &TypstTemplateSettings,
+    archive_limits: &TypstArchiveLimits,
+    progress: &TypstLoadProgress,
+) -> Result<StructuredInMemoryTemplate, TypstAssetError> {
+    let path = load_context.path().clone_owned();
+    #[cfg(feature = "tar-archives")]
+    if let Some(compression) = load_context.path().get_full_extension().and_then(|ext| {
+        ["tar.gz", "tgz", "tar"]
+            .into_iter()
+            .find(|tar| has_extension(&ext, tar))
+            .and_then(crate::tar_archive::TarCompression::from_extension)
+    }) {
+        let buffer = progress.read_to_end(reader, &path).await?;
+        progress.update(&path, |progress| {
+            progress.stage = TypstLoadStage::Extracting
+        });
+        let _span =
+            bevy_log::info_span!("typst_template_load", path = %load_context.path()).entered();
+        return StructuredInMemoryTemplate::from_tar_with_settings(
+            std::io::Cursor::new(buffer),
+            compression,
+            settings,
+            archive_limits,
+        );
+    }
+    if load_context
+        .path()
+        .get_full_extension()
+        .is_some_and(|ext| has_extension(&ext, "zip"))
+    {
+        let buffer = progress.read_to_end(reader, &path).await?;
+        let _span =
+            bevy_log::info_span!("typst_template_load", path = %load_context.path()).entered();
+        StructuredInMemoryTemplate::from_zip_bytes_with_progress(
+            buffer.into(),
+            settings,
+            archive_limits,
+            &mut |entries_read, entry_count| {
+                progress.update(&path, |progress| {
+                    progress.stage = TypstLoadStage::Extracting;
+                    progress.entries_read = entries_read;
+                    progress.entry_count = entry_count;
+                })
+            },
+        )
+    } else if load_context
+        .path()
+        .get_full_extension()
+        .is_some_and(|ext| has_extension(&ext, "typ"))
+    {
+        // Standalone file.
+        if cfg!(not(any(
+            feature = "typst-asset-fonts",
+            feature = "typst-search-system-fonts"
+        ))) {
+            typst_log!(
+                Setup,
+                Warn,
+                "[TYPST WARNING] Standalone typst file being loaded without either of the 'typst-asset-fonts' or 'typst-search-system-fonts' features enabled. Compilation may fail if text is output is displayed."
+            );
+        }
+        let mut buffer = Vec::new();
+        reader
+            .read_to_end(&mut buffer)
+            .await
+            .map_err(TypstAssetError::Io)?;
+        let _span =
+            bevy_log::info_span!("typst_template_load", path = %load_context.path()).entered();
+        let entry = load_context.path().to_string();
+        let (loaded_main, encoding) =
+            decode_text(buffer, true).map_err(|reason| TypstAssetError::InvalidText {
+                entry: entry.clone(),
+                reason,
+            })?;
+        let load_diagnostics = if encoding.is_reencoded() {
+            vec![TemplateLoadDiagnostic::TextReencoded { entry, encoding }].into()
+        } else {
+            TypstTemplateDiagnostics::default()
+        };
+        Ok(StructuredInMemoryTemplate {
+            loaded_main,
+            main_path: Some(load_context.path().path().to_path_buf()),
+            load_diagnostics,
+            ..Default::default()
+        })
+    } else {
+        Err(TypstAssetError::UnsupportedFormat)
+    }
 }
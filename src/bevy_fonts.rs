@@ -0,0 +1,49 @@
+use bevy_asset::{AssetServer, Assets, Handle, LoadState};
+use bevy_ecs::system::{Res, ResMut};
+use bevy_text::Font;
+
+use crate::{TypstTextureServer, font_library::TypstFontLibrary, logging::typst_log};
+
+impl TypstTextureServer {
+    /// Make a bevy font asset, such as one already used for the game's UI, available to every template through the
+    /// [`TypstFontLibrary`] once it has loaded, so it doesn't need to be shipped again inside typst archives.
+    pub fn register_bevy_font(&mut self, font: Handle<Font>) {
+        self.pending_bevy_fonts.push(font);
+    }
+}
+
+/// Runs in `Update`. Adds fonts registered with [`TypstTextureServer::register_bevy_font`] to the
+/// [`TypstFontLibrary`] as they finish loading.
+pub fn system_register_bevy_fonts(
+    mut template_server: ResMut<TypstTextureServer>,
+    mut library: ResMut<TypstFontLibrary>,
+    fonts: Res<Assets<Font>>,
+    asset_server: Res<AssetServer>,
+) {
+    if template_server.pending_bevy_fonts.is_empty() {
+        return;
+    }
+    template_server.pending_bevy_fonts.retain(|handle| {
+        let Some(font) = fonts.get(handle) else {
+            if let Some(LoadState::Failed(error)) = asset_server.get_load_state(handle) {
+                typst_log!(
+                    Setup,
+                    Error,
+                    "[TYPST FONT ERROR] Bevy font {:?} failed to load: {error}",
+                    handle.path()
+                );
+                return false;
+            }
+            return true;
+        };
+        if let Err(error) = library.add_bytes(font.data.to_vec()) {
+            typst_log!(
+                Setup,
+                Error,
+                "[TYPST FONT ERROR] Bevy font {:?} can't be used by typst: {error}",
+                handle.path()
+            );
+        }
+        false
+    });
+}
@@ -0,0 +1,359 @@
+use std::{marker::PhantomData, path::PathBuf, time::Duration};
+
+use bevy_app::{Plugin, Update};
+use bevy_asset::Handle;
+use bevy_ecs::{
+    change_detection::{DetectChanges, DetectChangesMut, Ref},
+    component::Component,
+    resource::Resource,
+    system::{Query, Res, ResMut},
+};
+use bevy_image::Image;
+use bevy_time::{Real, Time};
+use serde::Serialize;
+use typst::foundations::{Dict, IntoValue, Value};
+
+use crate::{
+    PathBufOrTemplate, TypstJobId, TypstJobOptions, TypstRenderInfo, TypstTextureServer,
+    ambient::TypstAmbientInputs, logging::typst_log, serde_value,
+};
+
+/// Keeps an image rendered from `T`, serialized as the job's input, re-rendering it into the same image whenever
+/// `T` or its ambient inputs change. Insert it as a resource to bind a resource `T` (with [`TypstResourceBindingPlugin`]), or as a
+/// component alongside a component `T` (with [`TypstComponentBindingPlugin`]).
+#[derive(Debug, Resource, Component)]
+pub struct TypstBinding<T> {
+    pub template: PathBuf,
+    pub options: TypstJobOptions,
+    /// When set, `T` is serialized under this key of the input rather than being the whole input.
+    /// `T` must serialize to a map or struct when this isn't set.
+    pub input_key: Option<String>,
+    /// Wait until `T` has stopped changing for this long before re-rendering. Ignored without a `Time<Real>` resource.
+    pub debounce: Option<Duration>,
+    image: Handle<Image>,
+    info: Handle<TypstRenderInfo>,
+    /// When `T` last changed without being rendered yet.
+    dirty_since: Option<Duration>,
+    /// If `T` has changed since it was last serialized.
+    value_changed: bool,
+    /// The input of the latest render, which patches are applied to.
+    last_input: Option<Dict>,
+    /// Changes to the input to apply on the next render, from [`TypstBinding::patch`].
+    patch: Option<TypstInputPatch>,
+    job: Option<TypstJobId>,
+    /// The binding's ambient inputs as of the last change to [`TypstAmbientInputs`].
+    ambient: Option<Dict>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize> TypstBinding<T> {
+    /// A binding rendering into a new image, which shows the server's fallback image until the first render.
+    pub fn new(
+        template_server: &TypstTextureServer,
+        template: impl Into<PathBuf>,
+        options: TypstJobOptions,
+    ) -> Self {
+        Self {
+            template: template.into(),
+            options,
+            input_key: None,
+            debounce: None,
+            image: template_server
+                .asset_server
+                .add(template_server.fallback.clone()),
+            info: template_server.asset_server.add(TypstRenderInfo::default()),
+            dirty_since: None,
+            value_changed: false,
+            last_input: None,
+            patch: None,
+            job: None,
+            ambient: None,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn with_input_key(mut self, key: impl Into<String>) -> Self {
+        self.input_key = Some(key.into());
+        self
+    }
+
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = Some(debounce);
+        self
+    }
+
+    /// Re-render with changes to the last input, without serializing `T` again. Keys are those of the whole input,
+    /// so values under an `input_key` are patched with [`TypstInputPatch::set_path`]. Patches accumulate until the
+    /// next render, and any change to `T` itself replaces the input, discarding patches not yet rendered.
+    ///
+    /// This is for large values where only a field or two changes often: change `T` without triggering change
+    /// detection (such as through `bypass_change_detection`) and patch the binding with those fields instead.
+    pub fn patch(&mut self, patch: TypstInputPatch) {
+        match &mut self.patch {
+            Some(pending) => pending.ops.extend(patch.ops),
+            None => self.patch = Some(patch),
+        }
+    }
+
+    /// The image this binding renders into.
+    pub fn image(&self) -> Handle<Image> {
+        self.image.clone()
+    }
+
+    /// The render info of this binding's latest render.
+    pub fn info(&self) -> Handle<TypstRenderInfo> {
+        self.info.clone()
+    }
+
+    /// Re-render with the last input if the ambient inputs resolved for this binding have changed.
+    fn update_ambient(&mut self, ambient_inputs: &TypstAmbientInputs, now: Option<Duration>) {
+        if ambient_inputs.resolved_changed(&self.options.ambient_overrides, &mut self.ambient)
+            && self.dirty_since.is_none()
+        {
+            self.dirty_since = Some(now.unwrap_or_default());
+        }
+    }
+
+    fn update(
+        &mut self,
+        value: &T,
+        changed: bool,
+        now: Option<Duration>,
+        template_server: &mut TypstTextureServer,
+    ) {
+        if changed {
+            self.dirty_since = Some(now.unwrap_or_default());
+            self.value_changed = true;
+            self.patch = None;
+        } else if self.patch.is_some() && self.dirty_since.is_none() {
+            self.dirty_since = Some(now.unwrap_or_default());
+        }
+        let Some(dirty_since) = self.dirty_since else {
+            return;
+        };
+        if let (Some(debounce), Some(now)) = (self.debounce, now)
+            && now.saturating_sub(dirty_since) < debounce
+        {
+            return;
+        }
+        self.dirty_since = None;
+        let patch = self.patch.take();
+        let mut input = match &self.last_input {
+            Some(last_input) if !self.value_changed => last_input.clone(),
+            _ => match self.serialize(value) {
+                Some(input) => input,
+                None => return,
+            },
+        };
+        self.value_changed = false;
+        if let Some(patch) = patch {
+            patch.apply(&mut input);
+        }
+        self.last_input = Some(input.clone());
+        if let Some(job) = self.job.take() {
+            template_server.cancel(job);
+        }
+        let template =
+            template_server.resolve_template(PathBufOrTemplate::PathBuf(self.template.clone()));
+        self.job = Some(template_server.enqueue_into(
+            template,
+            self.image.clone(),
+            self.info.id(),
+            input,
+            self.options.clone(),
+        ));
+    }
+
+    /// `T` as this binding's input, logging why if it can't be.
+    fn serialize(&self, value: &T) -> Option<Dict> {
+        Some(match (serde_value::to_value(value), &self.input_key) {
+            (Ok(value), Some(key)) => {
+                let mut input = Dict::new();
+                input.insert(key.as_str().into(), value);
+                input
+            }
+            (Ok(Value::Dict(input)), None) => input,
+            (Ok(value), None) => {
+                typst_log!(
+                    Input,
+                    Error,
+                    "[TYPST INPUT ERROR] Bound value serialized to {} rather than a dictionary, set an input key.",
+                    value.ty()
+                );
+                return None;
+            }
+            (Err(error), _) => {
+                typst_log!(
+                    Input,
+                    Error,
+                    "[TYPST INPUT ERROR] Could not transform bound value: {error}"
+                );
+                return None;
+            }
+        })
+    }
+}
+
+/// Changes to the input of a [`TypstBinding`], applied in order by [`TypstBinding::patch`].
+///
+/// ```rust,ignore
+/// binding.patch(TypstInputPatch::new().set_path(["player", "health"], 42).remove("banner"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TypstInputPatch {
+    ops: Vec<PatchOp>,
+}
+
+#[derive(Debug, Clone)]
+enum PatchOp {
+    Set(Vec<String>, Value),
+    Remove(Vec<String>),
+}
+
+impl TypstInputPatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a top-level key of the input.
+    pub fn set(self, key: impl Into<String>, value: impl IntoValue) -> Self {
+        self.set_path([key.into()], value)
+    }
+
+    /// Set a key nested in dictionaries, such as `["stats", "health"]` for `stats.health`, creating any missing
+    /// dictionaries along the way and replacing any non-dictionary values in the way.
+    pub fn set_path(
+        mut self,
+        path: impl IntoIterator<Item = impl Into<String>>,
+        value: impl IntoValue,
+    ) -> Self {
+        let path = path.into_iter().map(Into::into).collect();
+        self.ops.push(PatchOp::Set(path, value.into_value()));
+        self
+    }
+
+    /// Remove a top-level key of the input.
+    pub fn remove(self, key: impl Into<String>) -> Self {
+        self.remove_path([key.into()])
+    }
+
+    /// Remove a key nested in dictionaries. Does nothing if the key isn't there.
+    pub fn remove_path(mut self, path: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.ops
+            .push(PatchOp::Remove(path.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Apply each change to `input` in order.
+    pub fn apply(&self, input: &mut Dict) {
+        for op in &self.ops {
+            match op {
+                PatchOp::Set(path, value) => set_path(input, path, value.clone()),
+                PatchOp::Remove(path) => remove_path(input, path),
+            }
+        }
+    }
+}
+
+fn set_path(dict: &mut Dict, path: &[String], value: Value) {
+    let Some((key, rest)) = path.split_first() else {
+        return;
+    };
+    if rest.is_empty() {
+        dict.insert(key.as_str().into(), value);
+        return;
+    }
+    let mut child = match dict.remove(key.as_str().into(), None) {
+        Ok(Value::Dict(child)) => child,
+        _ => Dict::new(),
+    };
+    set_path(&mut child, rest, value);
+    dict.insert(key.as_str().into(), Value::Dict(child));
+}
+
+fn remove_path(dict: &mut Dict, path: &[String]) {
+    let Some((key, rest)) = path.split_first() else {
+        return;
+    };
+    if rest.is_empty() {
+        let _ = dict.remove(key.as_str().into(), None);
+        return;
+    }
+    if let Ok(Value::Dict(mut child)) = dict.remove(key.as_str().into(), None) {
+        remove_path(&mut child, rest);
+        dict.insert(key.as_str().into(), Value::Dict(child));
+    }
+}
+
+/// Re-renders a [`TypstBinding<T>`] resource whenever the resource `T` changes.
+pub struct TypstResourceBindingPlugin<T>(PhantomData<fn() -> T>);
+
+impl<T> Default for TypstResourceBindingPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: Resource + Serialize> Plugin for TypstResourceBindingPlugin<T> {
+    fn build(&self, app: &mut bevy_app::App) {
+        app.add_systems(Update, system_bind_resource::<T>);
+    }
+}
+
+/// Re-renders [`TypstBinding<T>`] components whenever the component `T` on the same entity changes.
+pub struct TypstComponentBindingPlugin<T>(PhantomData<fn() -> T>);
+
+impl<T> Default for TypstComponentBindingPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: Component + Serialize> Plugin for TypstComponentBindingPlugin<T> {
+    fn build(&self, app: &mut bevy_app::App) {
+        app.add_systems(Update, system_bind_component::<T>);
+    }
+}
+
+fn system_bind_resource<T: Resource + Serialize>(
+    value: Option<Res<T>>,
+    binding: Option<ResMut<TypstBinding<T>>>,
+    mut template_server: ResMut<TypstTextureServer>,
+    time: Option<Res<Time<Real>>>,
+    ambient_inputs: Option<Res<TypstAmbientInputs>>,
+) {
+    let (Some(value), Some(mut binding)) = (value, binding) else {
+        return;
+    };
+    let now = time.map(|time| time.elapsed());
+    let changed = value.is_changed() || binding.is_added();
+    let binding = binding.bypass_change_detection();
+    if let Some(ambient_inputs) =
+        ambient_inputs.filter(|ambient_inputs| ambient_inputs.is_changed())
+    {
+        binding.update_ambient(&ambient_inputs, now);
+    }
+    binding.update(&value, changed, now, &mut template_server);
+}
+
+fn system_bind_component<T: Component + Serialize>(
+    mut bindings: Query<(Ref<T>, &mut TypstBinding<T>)>,
+    mut template_server: ResMut<TypstTextureServer>,
+    time: Option<Res<Time<Real>>>,
+    ambient_inputs: Option<Res<TypstAmbientInputs>>,
+) {
+    let now = time.map(|time| time.elapsed());
+    let ambient_inputs = ambient_inputs.filter(|ambient_inputs| ambient_inputs.is_changed());
+    for (value, mut binding) in &mut bindings {
+        let changed = value.is_changed() || binding.is_added();
+        let binding = binding.bypass_change_detection();
+        if let Some(ambient_inputs) = &ambient_inputs {
+            binding.update_ambient(ambient_inputs, now);
+        }
+        binding.update(&value, changed, now, &mut template_server);
+    }
+}
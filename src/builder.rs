@@ -0,0 +1,163 @@
+use bevy_ecs::entity::Entity;
+use serde::Serialize;
+use typst::foundations::{Dict, IntoValue};
+
+use crate::{
+    InputUnifyMode, PathBufOrTemplate, TypstJobOptions, TypstJobPriority, TypstRenderInfo,
+    TypstRenderTarget, TypstTextureServer, TypstWarningPolicy, callbacks::TypstJobCallback,
+    data_files::TypstDataFile, font_library::TypstJobFont, job_error::TypstJobError,
+    state_scope::TypstStateScope, unify_inputs,
+};
+
+/// A fluent builder for a typst job, from [`TypstTextureServer::job`].
+///
+/// ```rust,ignore
+/// let target = typst_server
+///     .job("example.zip")
+///     .input_serde(data)
+///     .page(2)
+///     .pixels_per_pt(2.0)
+///     .priority(TypstJobPriority::High)
+///     .spawn();
+/// ```
+pub struct TypstJobBuilder<'a> {
+    server: &'a mut TypstTextureServer,
+    template: PathBufOrTemplate,
+    input: Dict,
+    options: TypstJobOptions,
+    callback: Option<TypstJobCallback>,
+}
+
+impl<'a> TypstJobBuilder<'a> {
+    pub(crate) fn new(server: &'a mut TypstTextureServer, template: PathBufOrTemplate) -> Self {
+        let options = server.default_options();
+        Self {
+            server,
+            template,
+            input: Dict::new(),
+            options,
+            callback: None,
+        }
+    }
+
+    /// Merge a dict into the job's input, overriding any existing keys.
+    pub fn input_dict(mut self, input: impl Into<Dict>) -> Self {
+        for (key, value) in input.into() {
+            self.input.insert(key, value);
+        }
+        self
+    }
+
+    /// Merge a serializable value into the job's input, according to the options' [`InputUnifyMode`].
+    pub fn input_serde(mut self, input: impl Serialize) -> Self {
+        self.input = unify_inputs(
+            input,
+            std::mem::take(&mut self.input),
+            &self.options.input_unify_mode,
+        );
+        self
+    }
+
+    /// Set a single key of the job's input.
+    pub fn input(mut self, key: &str, value: impl IntoValue) -> Self {
+        self.input.insert(key.into(), value.into_value());
+        self
+    }
+
+    /// Replace all of the job's options. Call this before any other option setters.
+    pub fn options(mut self, options: TypstJobOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Run the job in a named queue, replacing all of the job's options with that queue's defaults.
+    /// Call this before any other option setters.
+    pub fn queue(mut self, name: &str) -> Self {
+        self.options = self.server.queue_options(name);
+        self
+    }
+
+    pub fn input_unify_mode(mut self, input_unify_mode: InputUnifyMode) -> Self {
+        self.options.input_unify_mode = input_unify_mode;
+        self
+    }
+
+    pub fn page(mut self, page: usize) -> Self {
+        self.options.specific_page = Some(page);
+        self
+    }
+
+    pub fn page_label(mut self, label: impl Into<String>) -> Self {
+        self.options.page_label = Some(label.into());
+        self
+    }
+
+    pub fn pixels_per_pt(mut self, pixels_per_pt: f32) -> Self {
+        self.options.pixels_per_pt = pixels_per_pt;
+        self
+    }
+
+    pub fn priority(mut self, priority: TypstJobPriority) -> Self {
+        self.options.priority = priority;
+        self
+    }
+
+    pub fn data_file(mut self, data_file: TypstDataFile) -> Self {
+        self.options.data_files.push(data_file);
+        self
+    }
+
+    /// Compile this typst file of the template instead of its main file, see [`TypstJobOptions::entry`].
+    pub fn entry(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.options.entry = Some(path.into());
+        self
+    }
+
+    /// Give the job an extra font, see [`TypstJobOptions::fonts`].
+    pub fn font(mut self, font: TypstJobFont) -> Self {
+        self.options.fonts.push(font);
+        self
+    }
+
+    pub fn state_scope(mut self, state_scope: TypstStateScope) -> Self {
+        self.options.state_scope = Some(state_scope);
+        self
+    }
+
+    /// Drop the job if `entity` despawns before it's compiled, see [`TypstJobOptions::owner`].
+    pub fn owner(mut self, entity: Entity) -> Self {
+        self.options.owner = Some(entity);
+        self
+    }
+
+    /// Render the job's errors into its image if it fails, see [`TypstJobOptions::error_texture`].
+    pub fn error_texture(mut self) -> Self {
+        self.options.error_texture = true;
+        self
+    }
+
+    pub fn warning_policy(mut self, policy: TypstWarningPolicy) -> Self {
+        self.options.warning_policy = policy;
+        self
+    }
+
+    /// Run `callback` once the job resolves, see [`TypstTextureServer::on_resolved`].
+    pub fn on_resolved(
+        mut self,
+        callback: impl FnOnce(Result<TypstRenderInfo, TypstJobError>) + Send + 'static,
+    ) -> Self {
+        self.callback = Some(TypstJobCallback::new(callback));
+        self
+    }
+
+    /// Queue the job.
+    pub fn spawn(self) -> TypstRenderTarget {
+        let target = self
+            .server
+            .submit_with_dict_input(self.template, self.input, self.options);
+        if let Some(callback) = self.callback {
+            self.server.callbacks.insert(target.job, callback);
+        }
+        target
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::Mutex;
+
+use bevy_ecs::{
+    message::MessageReader,
+    system::{Commands, ResMut},
+    world::World,
+};
+
+use crate::{
+    TypstJobId, TypstRenderInfo, TypstTextureServer,
+    events::{TypstJobCompleted, TypstJobFailed},
+    job_error::TypstJobError,
+};
+
+type JobCallbackFn = Box<dyn FnOnce(Result<TypstRenderInfo, TypstJobError>) + Send>;
+
+/// A callback run with the outcome of a job, from [`TypstTextureServer::on_resolved`].
+pub struct TypstJobCallback(Mutex<JobCallbackFn>);
+
+impl TypstJobCallback {
+    pub fn new(
+        callback: impl FnOnce(Result<TypstRenderInfo, TypstJobError>) + Send + 'static,
+    ) -> Self {
+        Self(Mutex::new(Box::new(callback)))
+    }
+
+    /// Queue the callback to run with `result` when `commands` are applied.
+    fn queue(self, commands: &mut Commands, result: Result<TypstRenderInfo, TypstJobError>) {
+        commands.queue(move |_: &mut World| {
+            if let Ok(callback) = self.0.into_inner() {
+                callback(result);
+            }
+        });
+    }
+}
+
+impl std::fmt::Debug for TypstJobCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TypstJobCallback").finish_non_exhaustive()
+    }
+}
+
+impl TypstTextureServer {
+    /// Run `callback` on the main thread once `job` resolves: with its render info when its image reaches the
+    /// asset server, or with the error it failed with, including [`TypstJobError::Cancelled`] if it's cancelled
+    /// through [`TypstTextureServer::cancel`]. This is an alternative to reading [`TypstJobCompleted`] and
+    /// [`TypstJobFailed`] messages. Attaching another callback to the same job replaces the previous one.
+    ///
+    /// Jobs dropped from the queue without running, i.e. because their owner despawned, call back with
+    /// [`TypstJobError::Cancelled`] too. Jobs deduplicated by [`crate::TypstJobOptions::content_key`] onto an
+    /// earlier job that has already completed call back with its render info on the next run of the callbacks.
+    pub fn on_resolved(
+        &mut self,
+        job: TypstJobId,
+        callback: impl FnOnce(Result<TypstRenderInfo, TypstJobError>) + Send + 'static,
+    ) {
+        let callback = TypstJobCallback::new(callback);
+        let resolved = self
+            .content_hashes
+            .values()
+            .find(|last| last.target.job == job)
+            .and_then(|last| last.info.clone());
+        match resolved {
+            Some(info) => self.resolved_callbacks.push((callback, Ok(info))),
+            None => {
+                self.callbacks.insert(job, callback);
+            }
+        }
+    }
+}
+
+pub(crate) fn system_run_job_callbacks(
+    mut commands: Commands,
+    mut template_server: ResMut<TypstTextureServer>,
+    mut completed: MessageReader<TypstJobCompleted>,
+    mut failed: MessageReader<TypstJobFailed>,
+) {
+    for completed in completed.read() {
+        template_server.record_content_key_info(completed.job, &completed.info);
+        if let Some(callback) = template_server.callbacks.remove(&completed.job) {
+            callback.queue(&mut commands, Ok(completed.info.clone()));
+        }
+    }
+    for failed in failed.read() {
+        template_server.forget_job_content_key(failed.job);
+        if let Some(callback) = template_server.callbacks.remove(&failed.job) {
+            callback.queue(&mut commands, Err(failed.error.clone()));
+        }
+    }
+    for (callback, result) in template_server.resolved_callbacks.drain(..) {
+        callback.queue(&mut commands, result);
+    }
+}
@@ -0,0 +1,133 @@
+use bevy_ecs::{
+    bundle::Bundle,
+    system::{Commands, EntityCommands},
+    world::{EntityWorldMut, World},
+};
+use typst::foundations::Dict;
+
+use crate::{
+    PathBufOrTemplate, TypstJobOptions, TypstRenderTarget, TypstTextureServer, logging::typst_log,
+};
+
+/// Queue typst jobs through [`Commands`], for observers and command-driven spawning where a
+/// `ResMut<TypstTextureServer>` isn't at hand. Jobs are submitted when the commands are applied.
+pub trait TypstCommandsExt {
+    /// Submit a job as per [`TypstTextureServer::submit_with_dict_input`], passing its render target to `then`.
+    fn typst_job(
+        &mut self,
+        template: impl Into<PathBufOrTemplate>,
+        input: impl Into<Dict>,
+        options: TypstJobOptions,
+        then: impl FnOnce(&mut World, TypstRenderTarget) + Send + 'static,
+    );
+
+    /// Spawn an entity with a [`crate::sprite::TypstSprite`], which queues its job and switches its sprite over
+    /// to the render once it's loaded, with [`crate::sprite::TypstSpriteRender`] tracking the job.
+    #[cfg(feature = "sprite")]
+    fn spawn_typst_sprite(
+        &mut self,
+        template: impl Into<std::path::PathBuf>,
+        input: impl Into<Dict>,
+        options: TypstJobOptions,
+    ) -> EntityCommands<'_>;
+
+    /// Spawn an entity with a [`crate::ui::TypstUiNode`], which renders at the node's on-screen resolution,
+    /// with [`crate::ui::TypstUiNodeRender`] tracking its jobs. Remember to size its `Node`.
+    #[cfg(feature = "ui")]
+    fn spawn_typst_ui_node(
+        &mut self,
+        template: impl Into<std::path::PathBuf>,
+        input: impl Into<Dict>,
+        options: TypstJobOptions,
+    ) -> EntityCommands<'_>;
+}
+
+impl TypstCommandsExt for Commands<'_, '_> {
+    fn typst_job(
+        &mut self,
+        template: impl Into<PathBufOrTemplate>,
+        input: impl Into<Dict>,
+        options: TypstJobOptions,
+        then: impl FnOnce(&mut World, TypstRenderTarget) + Send + 'static,
+    ) {
+        let template = template.into();
+        let input = input.into();
+        self.queue(move |world: &mut World| {
+            if let Some(target) = submit(world, template, input, options) {
+                then(world, target);
+            }
+        });
+    }
+
+    #[cfg(feature = "sprite")]
+    fn spawn_typst_sprite(
+        &mut self,
+        template: impl Into<std::path::PathBuf>,
+        input: impl Into<Dict>,
+        options: TypstJobOptions,
+    ) -> EntityCommands<'_> {
+        self.spawn(crate::sprite::TypstSprite::new(template, input).with_options(options))
+    }
+
+    #[cfg(feature = "ui")]
+    fn spawn_typst_ui_node(
+        &mut self,
+        template: impl Into<std::path::PathBuf>,
+        input: impl Into<Dict>,
+        options: TypstJobOptions,
+    ) -> EntityCommands<'_> {
+        self.spawn(crate::ui::TypstUiNode::new(template, input).with_options(options))
+    }
+}
+
+/// Queue typst jobs for an entity through [`EntityCommands`], attaching their render targets to it.
+pub trait TypstEntityCommandsExt {
+    /// Submit a job as per [`TypstTextureServer::submit_with_dict_input`], inserting the bundle `attach` builds
+    /// from its render target into this entity, i.e. `|target| Sprite::from_image(target.image)`. The entity is
+    /// made the job's [`TypstJobOptions::owner`] unless it already has one.
+    fn typst_job<B: Bundle>(
+        &mut self,
+        template: impl Into<PathBufOrTemplate>,
+        input: impl Into<Dict>,
+        options: TypstJobOptions,
+        attach: impl FnOnce(TypstRenderTarget) -> B + Send + 'static,
+    ) -> &mut Self;
+}
+
+impl TypstEntityCommandsExt for EntityCommands<'_> {
+    fn typst_job<B: Bundle>(
+        &mut self,
+        template: impl Into<PathBufOrTemplate>,
+        input: impl Into<Dict>,
+        mut options: TypstJobOptions,
+        attach: impl FnOnce(TypstRenderTarget) -> B + Send + 'static,
+    ) -> &mut Self {
+        let template = template.into();
+        let input = input.into();
+        options.owner.get_or_insert(self.id());
+        self.queue(move |mut entity: EntityWorldMut| {
+            if let Some(target) =
+                entity.world_scope(|world| submit(world, template, input, options))
+            {
+                entity.insert(attach(target));
+            }
+        })
+    }
+}
+
+fn submit(
+    world: &mut World,
+    template: PathBufOrTemplate,
+    input: Dict,
+    options: TypstJobOptions,
+) -> Option<TypstRenderTarget> {
+    let Some(mut template_server) = world.get_resource_mut::<TypstTextureServer>() else {
+        typst_log!(
+            Setup,
+            Error,
+            "[TYPST COMMAND ERROR] No TypstTextureServer to queue a job with, is the TypstTexturesPlugin added?"
+        );
+        return None;
+    };
+    Some(template_server.submit_with_dict_input(template, input, options))
+}
@@ -0,0 +1,231 @@
+use std::path::Path;
+
+use bevy_asset::{Assets, RenderAssetUsages};
+use bevy_image::Image;
+use tiny_skia::Pixmap;
+use typst::{foundations::Dict, layout::PagedDocument};
+use typst_as_lib::TypstAsLibError;
+use wgpu_types::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::{
+    TypstTextureServer, asset_loading::TypstTemplate, file_resolver::StructuredInMemoryTemplate,
+    font_library::TypstEngineFonts, raster::TypstRgbaBuffer,
+};
+
+/// Colour of changed pixels in the diff panel of a [`TypstComparison`].
+const DIFF_HIGHLIGHT: [u8; 4] = [255, 0, 255, 255];
+
+/// Settings for [`StructuredInMemoryTemplate::compare`].
+#[derive(Debug, Clone, Copy)]
+pub struct TypstCompareOptions {
+    pub pixels_per_pt: f32,
+    /// The page of each document to compare, clamped to the last page.
+    pub page: usize,
+    /// How much any channel of a pixel can differ before it counts as changed.
+    pub tolerance: u8,
+}
+
+impl Default for TypstCompareOptions {
+    fn default() -> Self {
+        Self {
+            pixels_per_pt: 1.0,
+            page: 0,
+            tolerance: 0,
+        }
+    }
+}
+
+/// The result of comparing two renders: the "before" render, the "after" render, and a diff side by side, with
+/// changed pixels highlighted in magenta over a faded copy of the "before" render.
+#[derive(Debug, Clone)]
+pub struct TypstComparison {
+    /// Premultiplied RGBA8 pixels of the composited comparison.
+    pub composite: TypstRgbaBuffer,
+    /// Pixels that differ, counting pixels only covered by one of the renders when they differ in size.
+    pub changed_pixels: u64,
+    /// The pixels compared, the area of the larger render's bounds.
+    pub total_pixels: u64,
+}
+
+impl TypstComparison {
+    /// The percentage of pixels that changed, from 0 to 100.
+    pub fn diff_percentage(&self) -> f64 {
+        if self.total_pixels == 0 {
+            0.0
+        } else {
+            self.changed_pixels as f64 / self.total_pixels as f64 * 100.0
+        }
+    }
+
+    /// The composited comparison as an image, for showing in-game or saving.
+    pub fn to_image(&self) -> Image {
+        Image::new(
+            Extent3d {
+                width: self.composite.width,
+                height: self.composite.height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            self.composite.data.clone(),
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        )
+    }
+
+    fn new(before: &Pixmap, after: &Pixmap, tolerance: u8) -> Self {
+        let width = before.width().max(after.width());
+        let height = before.height().max(after.height());
+        let panel_stride = before.width() + after.width();
+        let composite_width = panel_stride + width;
+        let mut data = vec![0; composite_width as usize * height as usize * 4];
+        let mut changed_pixels = 0;
+        for y in 0..height {
+            for x in 0..width {
+                let a = pixel(before, x, y);
+                let b = pixel(after, x, y);
+                if let Some(a) = a {
+                    put(&mut data, composite_width, x, y, a);
+                }
+                if let Some(b) = b {
+                    put(&mut data, composite_width, before.width() + x, y, b);
+                }
+                let changed = match (a, b) {
+                    (Some(a), Some(b)) => a.iter().zip(b).any(|(a, b)| a.abs_diff(b) > tolerance),
+                    (None, None) => false,
+                    _ => true,
+                };
+                let diff = if changed {
+                    changed_pixels += 1;
+                    DIFF_HIGHLIGHT
+                } else {
+                    // Premultiplied, so fading the whole pixel keeps it valid.
+                    a.unwrap_or_default().map(|channel| channel / 4)
+                };
+                put(&mut data, composite_width, panel_stride + x, y, diff);
+            }
+        }
+        Self {
+            composite: TypstRgbaBuffer {
+                width: composite_width,
+                height,
+                data,
+            },
+            changed_pixels,
+            total_pixels: width as u64 * height as u64,
+        }
+    }
+}
+
+fn pixel(pixmap: &Pixmap, x: u32, y: u32) -> Option<[u8; 4]> {
+    let pixel = pixmap.pixel(x, y)?;
+    Some([pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()])
+}
+
+fn put(data: &mut [u8], width: u32, x: u32, y: u32, pixel: [u8; 4]) {
+    let index = (y * width + x) as usize * 4;
+    data[index..index + 4].copy_from_slice(&pixel);
+}
+
+impl StructuredInMemoryTemplate {
+    /// Render this template with `input` and `other` with `other_input` synchronously, and compare the results.
+    /// Pass the same template twice to compare two input sets, or two versions of a template to check a change
+    /// to it doesn't break existing inputs.
+    pub fn compare(
+        &self,
+        input: Dict,
+        other: &StructuredInMemoryTemplate,
+        other_input: Dict,
+        options: TypstCompareOptions,
+    ) -> Result<TypstComparison, TypstAsLibError> {
+        self.compare_with_fonts(
+            input,
+            other,
+            other_input,
+            options,
+            &TypstEngineFonts::default(),
+        )
+    }
+
+    /// Compare renders as per [`StructuredInMemoryTemplate::compare`], with the font config and library fonts
+    /// of `fonts`.
+    pub fn compare_with_fonts(
+        &self,
+        input: Dict,
+        other: &StructuredInMemoryTemplate,
+        other_input: Dict,
+        options: TypstCompareOptions,
+        fonts: &TypstEngineFonts,
+    ) -> Result<TypstComparison, TypstAsLibError> {
+        let before = self.render_page(input, options, fonts)?;
+        let after = other.render_page(other_input, options, fonts)?;
+        Ok(TypstComparison::new(&before, &after, options.tolerance))
+    }
+
+    fn render_page(
+        &self,
+        input: Dict,
+        options: TypstCompareOptions,
+        fonts: &TypstEngineFonts,
+    ) -> Result<Pixmap, TypstAsLibError> {
+        let (engine, _) = fonts.to_engine(self.clone());
+        let document = engine
+            .compile_with_input::<_, PagedDocument>(input)
+            .output?;
+        Ok(match document.pages.len() {
+            0 => Pixmap::new(1, 1).expect("a 1x1 pixmap is valid"),
+            len => typst_render::render(
+                &document.pages[options.page.min(len - 1)],
+                options.pixels_per_pt,
+            ),
+        })
+    }
+}
+
+impl TypstTextureServer {
+    /// Compare renders of two loaded templates known to this server (or the same one twice), as per
+    /// [`StructuredInMemoryTemplate::compare`].
+    pub fn compare_renders(
+        &self,
+        templates: &Assets<TypstTemplate>,
+        (template_path, input): (impl AsRef<Path>, Dict),
+        (other_path, other_input): (impl AsRef<Path>, Dict),
+        options: TypstCompareOptions,
+    ) -> Result<TypstComparison, TypstCompareError> {
+        let get = |path: &Path| {
+            self.templates
+                .get(path)
+                .and_then(|handle| templates.get(handle))
+                .ok_or(TypstCompareError::TemplateNotLoaded)
+        };
+        get(template_path.as_ref())?
+            .0
+            .compare_with_fonts(
+                input,
+                &get(other_path.as_ref())?.0,
+                other_input,
+                options,
+                &self.engine_fonts,
+            )
+            .map_err(TypstCompareError::Compile)
+    }
+}
+
+#[derive(Debug)]
+pub enum TypstCompareError {
+    /// Either template isn't known to the server, or hasn't finished loading.
+    TemplateNotLoaded,
+    Compile(TypstAsLibError),
+}
+
+impl std::fmt::Display for TypstCompareError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypstCompareError::TemplateNotLoaded => {
+                write!(f, "TypstCompareError::TemplateNotLoaded")
+            }
+            TypstCompareError::Compile(e) => write!(f, "TypstCompareError::Compile: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for TypstCompareError {}
@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+
+use bevy_asset::{Assets, Handle};
+use typst::{
+    syntax::{FileId, Source, VirtualPath},
+    text::{Font, FontInfo},
+};
+
+use crate::{
+    TypstTextureServer,
+    asset_loading::TypstTemplate,
+    file_resolver::{FileAccessLog, StructuredInMemoryTemplate},
+    lazy_fonts::LazyFont,
+};
+
+impl StructuredInMemoryTemplate {
+    /// Layer `overlay` over `base`, for theme packs and mods that replace some of a template's files without
+    /// re-shipping the rest. Where both have a file at the same path, the overlay's is used, and the overlay's
+    /// fonts are used over the base's fonts of the same family and style.
+    ///
+    /// The base's main file is kept unless the overlay's main file is at the same path, or has no path at all.
+    /// The base's `package.toml` is kept too, with the overlay's asset requests, compiler features and inputs
+    /// added to it. The merged template is named after both, as `base+overlay`.
+    pub fn merge(base: Self, overlay: Self) -> Self {
+        let mut merged = base;
+        let main_id = |template: &Self| {
+            template
+                .main_path
+                .as_deref()
+                .map(|path| FileId::new(None, VirtualPath::new(path)))
+        };
+        let base_main = main_id(&merged);
+        let overlay_main = main_id(&overlay);
+        for source in overlay.source_resolver {
+            if Some(source.id()) == base_main {
+                merged.loaded_main = source.text().to_owned();
+            } else {
+                merged.replace_source(source);
+            }
+        }
+        match overlay_main {
+            Some(id) if Some(id) != base_main => {
+                merged.replace_source(Source::new(id, overlay.loaded_main));
+            }
+            _ if !overlay.loaded_main.is_empty() => merged.loaded_main = overlay.loaded_main,
+            _ => {}
+        }
+
+        for (id, bytes) in overlay.file_resolver {
+            merged.file_resolver.retain(|(existing, _)| *existing != id);
+            merged.file_resolver.push((id, bytes));
+        }
+
+        let replaced = |info: &FontInfo| {
+            overlay
+                .loaded_fonts
+                .iter()
+                .map(Font::info)
+                .chain(overlay.lazy_fonts.iter().map(LazyFont::info))
+                .any(|replacement| {
+                    replacement.family == info.family && replacement.variant == info.variant
+                })
+        };
+        merged.loaded_fonts.retain(|font| !replaced(font.info()));
+        merged.lazy_fonts.retain(|font| !replaced(font.info()));
+        let base_fonts = std::mem::replace(&mut merged.loaded_fonts, overlay.loaded_fonts);
+        merged.loaded_fonts.extend(base_fonts);
+        let base_lazy_fonts = std::mem::replace(&mut merged.lazy_fonts, overlay.lazy_fonts);
+        merged.lazy_fonts.extend(base_lazy_fonts);
+
+        let toml = &mut merged.loaded_toml;
+        toml.asset_requests
+            .extend(overlay.loaded_toml.asset_requests);
+        for feature in overlay.loaded_toml.compiler_features {
+            if !toml.compiler_features.contains(&feature) {
+                toml.compiler_features.push(feature);
+            }
+        }
+        toml.inputs.keys.extend(overlay.loaded_toml.inputs.keys);
+        toml.inputs.docs.extend(overlay.loaded_toml.inputs.docs);
+
+        merged
+            .load_diagnostics
+            .issues
+            .extend(overlay.load_diagnostics.issues);
+        let base_providers = std::mem::replace(&mut merged.file_providers, overlay.file_providers);
+        merged.file_providers.0.extend(base_providers.0);
+        merged.file_accesses = FileAccessLog::default();
+        merged.path_given = PathBuf::from(format!(
+            "{}+{}",
+            merged.path_given.display(),
+            overlay.path_given.display()
+        ));
+        merged
+    }
+
+    /// Add a source, replacing any with the same id.
+    fn replace_source(&mut self, source: Source) {
+        self.source_resolver
+            .retain(|existing| existing.id() != source.id());
+        self.source_resolver.push(source);
+    }
+}
+
+impl TypstTextureServer {
+    /// Merge loaded templates, each layered over those before it as per [`StructuredInMemoryTemplate::merge`],
+    /// i.e. `[base, theme, mod]`. `None` if there are no templates or any of them hasn't loaded. The result can be
+    /// queued like any other new template, but isn't updated if its layers are reloaded.
+    pub fn compose(
+        templates: &Assets<TypstTemplate>,
+        layers: &[Handle<TypstTemplate>],
+    ) -> Option<StructuredInMemoryTemplate> {
+        let mut layers = layers
+            .iter()
+            .map(|handle| templates.get(handle).map(|template| template.0.clone()));
+        let base = layers.next()??;
+        layers.try_fold(base, |merged, layer| {
+            Some(StructuredInMemoryTemplate::merge(merged, layer?))
+        })
+    }
+}
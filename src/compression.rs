@@ -0,0 +1,151 @@
+use basis_universal::{
+    BasisTextureFormat, ColorSpace, Compressor, CompressorParams, TranscodeParameters, Transcoder,
+    TranscoderTextureFormat, UASTC_QUALITY_DEFAULT,
+};
+use std::sync::Once;
+
+use bevy_asset::RenderAssetUsages;
+use bevy_image::Image;
+use wgpu_types::{AstcBlock, AstcChannel, Extent3d, TextureDimension, TextureFormat};
+
+/// GPU-compressed formats that rasterized output can be transcoded into via basis-universal.
+/// Output dimensions are padded up to a multiple of 4 pixels with transparent pixels on the right and bottom
+/// to fit the 4x4 block size, which [`crate::TypstRenderInfo::size_px`] includes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypstTextureCompression {
+    /// BC7, for desktop GPUs.
+    Bc7,
+    /// ASTC with 4x4 blocks, for mobile GPUs.
+    Astc4x4,
+}
+
+impl TypstTextureCompression {
+    fn transcoder_format(self) -> TranscoderTextureFormat {
+        match self {
+            TypstTextureCompression::Bc7 => TranscoderTextureFormat::BC7_RGBA,
+            TypstTextureCompression::Astc4x4 => TranscoderTextureFormat::ASTC_4x4_RGBA,
+        }
+    }
+
+    fn texture_format(self) -> TextureFormat {
+        match self {
+            TypstTextureCompression::Bc7 => TextureFormat::Bc7RgbaUnormSrgb,
+            TypstTextureCompression::Astc4x4 => TextureFormat::Astc {
+                block: AstcBlock::B4x4,
+                channel: AstcChannel::UnormSrgb,
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TypstCompressionError {
+    Compression(String),
+    Transcoding(String),
+}
+
+impl std::fmt::Display for TypstCompressionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypstCompressionError::Compression(error) => {
+                write!(f, "TypstCompressionError::Compression: {error}")
+            }
+            TypstCompressionError::Transcoding(error) => {
+                write!(f, "TypstCompressionError::Transcoding: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TypstCompressionError {}
+
+/// basis-universal's encoder and transcoder tables, built once for every job that's compressed.
+static BASIS_INIT: Once = Once::new();
+
+/// The dimensions of an image of the given size once padded to whole blocks.
+pub(crate) fn padded_size(width: u32, height: u32) -> (u32, u32) {
+    (width.div_ceil(4) * 4, height.div_ceil(4) * 4)
+}
+
+/// Pad RGBA8 pixel data to whole blocks, as per [`padded_size`].
+pub(crate) fn pad(rgba: Vec<u8>, width: u32, height: u32) -> Vec<u8> {
+    let (padded_width, padded_height) = padded_size(width, height);
+    if (padded_width, padded_height) == (width, height) {
+        return rgba;
+    }
+    let mut padded = vec![0u8; (padded_width * padded_height * 4) as usize];
+    for row in 0..height as usize {
+        let source = row * width as usize * 4;
+        let target = row * padded_width as usize * 4;
+        padded[target..target + width as usize * 4]
+            .copy_from_slice(&rgba[source..source + width as usize * 4]);
+    }
+    padded
+}
+
+/// Encode RGBA8 pixel data, already padded to whole blocks, as UASTC then transcode it into the requested
+/// GPU format.
+pub(crate) fn compress(
+    padded: &[u8],
+    padded_width: u32,
+    padded_height: u32,
+    compression: TypstTextureCompression,
+    asset_usage: RenderAssetUsages,
+) -> Result<Image, TypstCompressionError> {
+    BASIS_INIT.call_once(|| {
+        basis_universal::encoder_init();
+        basis_universal::transcoder_init();
+    });
+
+    let mut params = CompressorParams::new();
+    params.set_basis_format(BasisTextureFormat::UASTC4x4);
+    params.set_uastc_quality_level(UASTC_QUALITY_DEFAULT);
+    params.set_color_space(ColorSpace::Srgb);
+    params.set_generate_mipmaps(false);
+    params.set_print_status_to_stdout(false);
+    params
+        .source_image_mut(0)
+        .init(padded, padded_width, padded_height, 4);
+    let mut compressor = Compressor::new(1);
+    // Safety: the params outlive the compressor's use of them within this function.
+    unsafe {
+        if !compressor.init(&params) {
+            return Err(TypstCompressionError::Compression(
+                "could not initialise compressor".to_owned(),
+            ));
+        }
+        compressor
+            .process()
+            .map_err(|error| TypstCompressionError::Compression(format!("{error:?}")))?;
+    }
+
+    let basis_file = compressor.basis_file();
+    let mut transcoder = Transcoder::new();
+    transcoder.prepare_transcoding(basis_file).map_err(|_| {
+        TypstCompressionError::Transcoding("could not prepare transcoding".to_owned())
+    })?;
+    let data = transcoder
+        .transcode_image_level(
+            basis_file,
+            compression.transcoder_format(),
+            TranscodeParameters {
+                image_index: 0,
+                level_index: 0,
+                ..Default::default()
+            },
+        )
+        .map_err(|error| TypstCompressionError::Transcoding(format!("{error:?}")))?;
+    transcoder.end_transcoding();
+
+    Ok(Image::new(
+        Extent3d {
+            width: padded_width,
+            height: padded_height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        compression.texture_format(),
+        asset_usage,
+    ))
+}
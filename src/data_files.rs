@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+use typst::{
+    foundations::Bytes,
+    syntax::{FileId, VirtualPath},
+};
+
+use crate::file_resolver::StructuredInMemoryTemplate;
+
+/// A file made available to a single job's typst program, i.e. `json("data.json")`.
+/// See [`crate::TypstJobOptions::data_files`].
+#[derive(Debug, Clone)]
+pub struct TypstDataFile {
+    /// Path of the file relative to the template root.
+    pub path: PathBuf,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum TypstDataFileError {
+    Json(serde_json::Error),
+    Toml(toml::ser::Error),
+    #[cfg(feature = "data-csv")]
+    Csv(csv::Error),
+    #[cfg(feature = "data-yaml")]
+    Yaml(serde_yaml::Error),
+}
+
+impl std::fmt::Display for TypstDataFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypstDataFileError::Json(error) => write!(f, "TypstDataFileError::Json: {error}"),
+            TypstDataFileError::Toml(error) => write!(f, "TypstDataFileError::Toml: {error}"),
+            #[cfg(feature = "data-csv")]
+            TypstDataFileError::Csv(error) => write!(f, "TypstDataFileError::Csv: {error}"),
+            #[cfg(feature = "data-yaml")]
+            TypstDataFileError::Yaml(error) => write!(f, "TypstDataFileError::Yaml: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for TypstDataFileError {}
+
+impl TypstDataFile {
+    pub fn new(path: impl Into<PathBuf>, bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            path: path.into(),
+            bytes: bytes.into(),
+        }
+    }
+
+    /// Serialize `value` as JSON, for reading with typst's `json()`.
+    pub fn json(
+        path: impl Into<PathBuf>,
+        value: &impl Serialize,
+    ) -> Result<Self, TypstDataFileError> {
+        let bytes = serde_json::to_vec(value).map_err(TypstDataFileError::Json)?;
+        Ok(Self::new(path, bytes))
+    }
+
+    /// Serialize `value` as TOML, for reading with typst's `toml()`.
+    pub fn toml(
+        path: impl Into<PathBuf>,
+        value: &impl Serialize,
+    ) -> Result<Self, TypstDataFileError> {
+        let string = toml::to_string(value).map_err(TypstDataFileError::Toml)?;
+        Ok(Self::new(path, string))
+    }
+
+    /// Serialize `rows` as CSV with a header row, for reading with typst's `csv()`.
+    #[cfg(feature = "data-csv")]
+    pub fn csv<T: Serialize>(
+        path: impl Into<PathBuf>,
+        rows: impl IntoIterator<Item = T>,
+    ) -> Result<Self, TypstDataFileError> {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        for row in rows {
+            writer.serialize(row).map_err(TypstDataFileError::Csv)?;
+        }
+        let bytes = writer
+            .into_inner()
+            .map_err(|error| TypstDataFileError::Csv(error.into_error().into()))?;
+        Ok(Self::new(path, bytes))
+    }
+
+    /// Serialize `value` as YAML, for reading with typst's `yaml()`.
+    #[cfg(feature = "data-yaml")]
+    pub fn yaml(
+        path: impl Into<PathBuf>,
+        value: &impl Serialize,
+    ) -> Result<Self, TypstDataFileError> {
+        let string = serde_yaml::to_string(value).map_err(TypstDataFileError::Yaml)?;
+        Ok(Self::new(path, string))
+    }
+}
+
+impl StructuredInMemoryTemplate {
+    /// This template with the given data files added to its file resolver.
+    pub fn with_data_files(mut self, data_files: &[TypstDataFile]) -> Self {
+        self.file_resolver
+            .extend(data_files.iter().map(|data_file| {
+                (
+                    FileId::new(None, VirtualPath::new(&data_file.path)),
+                    Bytes::new(data_file.bytes.clone()),
+                )
+            }));
+        self
+    }
+}
@@ -0,0 +1,54 @@
+use std::{collections::BTreeSet, path::PathBuf};
+
+use typst::{
+    layout::{Frame, FrameItem, PagedDocument},
+    syntax::FileId,
+};
+
+/// What a template actually used during its last compile, from [`crate::TypstTextureServer::template_dependencies`].
+/// Useful for pruning unused files and fonts from archives. Files are those the template's file resolver was
+/// asked for, which is all of them as [`crate::engine::TypstTemplateEngine`] clears typst's memoization cache
+/// around each compile, so none are skipped for having been cached by an earlier one.
+#[derive(Debug, Clone, Default)]
+pub struct TypstTemplateDependencies {
+    /// Files and typst modules resolved from the template, relative to its root. The main file is always used and isn't listed.
+    pub files: BTreeSet<PathBuf>,
+    /// Font families used by text in the compiled document.
+    pub fonts: BTreeSet<String>,
+    /// Package specs (i.e. `@preview/cetz:0.3.0`) of the packages files were resolved from.
+    pub packages: BTreeSet<String>,
+}
+
+impl TypstTemplateDependencies {
+    pub(crate) fn collect(accessed: &[FileId], document: &PagedDocument) -> Self {
+        let mut fonts = BTreeSet::new();
+        for page in &document.pages {
+            collect_fonts(&page.frame, &mut fonts);
+        }
+        Self {
+            files: accessed
+                .iter()
+                .filter(|id| id.package().is_none())
+                .map(|id| id.vpath().as_rootless_path().to_path_buf())
+                .collect(),
+            fonts,
+            packages: accessed
+                .iter()
+                .filter_map(|id| id.package())
+                .map(|package| package.to_string())
+                .collect(),
+        }
+    }
+}
+
+fn collect_fonts(frame: &Frame, fonts: &mut BTreeSet<String>) {
+    for (_, item) in frame.items() {
+        match item {
+            FrameItem::Group(group) => collect_fonts(&group.frame, fonts),
+            FrameItem::Text(text) => {
+                fonts.insert(text.font.info().family.clone());
+            }
+            _ => {}
+        }
+    }
+}
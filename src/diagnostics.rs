@@ -0,0 +1,144 @@
+use std::path::PathBuf;
+
+use typst::{
+    diag::{Severity, SourceDiagnostic},
+    syntax::{FileId, Source, Span},
+};
+
+use crate::file_resolver::StructuredInMemoryTemplate;
+
+/// A typst diagnostic resolved against the sources of the template it came from, for showing errors in a
+/// dev console and jumping to the offending line in an editor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypstDiagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// The file the diagnostic points at, relative to the template's root. `None` for diagnostics that aren't
+    /// tied to a file, such as errors from outside the compiler.
+    pub path: Option<PathBuf>,
+    /// Where in `path` the diagnostic points at, if it could be found.
+    pub span: Option<TypstSourceSpan>,
+    /// The line of source the span starts on, without its line break.
+    pub excerpt: Option<String>,
+    pub hints: Vec<String>,
+}
+
+impl TypstDiagnostic {
+    /// A diagnostic not tied to any source.
+    pub fn detached(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            path: None,
+            span: None,
+            excerpt: None,
+            hints: Vec::new(),
+        }
+    }
+}
+
+impl std::fmt::Display for TypstDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}", path.display())?;
+            if let Some(span) = &self.span {
+                write!(f, ":{}:{}", span.start_line, span.start_column)?;
+            }
+            write!(f, ": ")?;
+        }
+        match self.severity {
+            Severity::Error => write!(f, "error: {}", self.message)?,
+            Severity::Warning => write!(f, "warning: {}", self.message)?,
+        }
+        for hint in &self.hints {
+            write!(f, " (hint: {hint})")?;
+        }
+        if let (Some(span), Some(excerpt)) = (&self.span, &self.excerpt) {
+            let gutter = span.start_line.to_string();
+            write!(f, "\n{gutter} | {excerpt}")?;
+            write!(
+                f,
+                "\n{:width$} | {:>column$}",
+                "",
+                "^",
+                width = gutter.len(),
+                column = span.start_column
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A range of a source file. Lines and columns start from 1, with columns counted in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypstSourceSpan {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl StructuredInMemoryTemplate {
+    /// Resolve diagnostics from compiling this template to the files, lines and columns they point at.
+    pub fn resolve_diagnostics(&self, diagnostics: &[SourceDiagnostic]) -> Vec<TypstDiagnostic> {
+        let main = Source::detached(self.loaded_main.clone());
+        diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let (span, excerpt) = self
+                    .locate(&main, diagnostic.span)
+                    .map_or((None, None), |(span, excerpt)| (Some(span), Some(excerpt)));
+                TypstDiagnostic {
+                    severity: diagnostic.severity,
+                    message: diagnostic.message.to_string(),
+                    path: diagnostic.span.id().map(|id| self.file_path(&main, id)),
+                    span,
+                    excerpt,
+                    hints: diagnostic
+                        .hints
+                        .iter()
+                        .map(|hint| hint.to_string())
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// The path a file of this template is reported under. The main file is given to typst without a path, so
+    /// it's reported under [`StructuredInMemoryTemplate::main_path`], or the name the template was given.
+    fn file_path(&self, main: &Source, id: FileId) -> PathBuf {
+        if main.id() == id {
+            self.main_path
+                .clone()
+                .unwrap_or_else(|| self.path_given.clone())
+        } else {
+            id.vpath().as_rootless_path().to_path_buf()
+        }
+    }
+
+    fn source<'a>(&'a self, main: &'a Source, id: FileId) -> Option<&'a Source> {
+        if main.id() == id {
+            return Some(main);
+        }
+        self.source_resolver.iter().find(|source| source.id() == id)
+    }
+
+    fn locate(&self, main: &Source, span: Span) -> Option<(TypstSourceSpan, String)> {
+        let source = self.source(main, span.id()?)?;
+        let range = source.range(span)?;
+        let lines = source.lines();
+        let start_line = lines.byte_to_line(range.start)?;
+        let excerpt = source
+            .text()
+            .get(lines.line_to_range(start_line)?)?
+            .trim_end_matches(['\n', '\r'])
+            .to_owned();
+        let span = TypstSourceSpan {
+            start_line: start_line + 1,
+            start_column: lines.byte_to_column(range.start)? + 1,
+            end_line: lines.byte_to_line(range.end)? + 1,
+            end_column: lines.byte_to_column(range.end)? + 1,
+        };
+        Some((span, excerpt))
+    }
+}
@@ -0,0 +1,140 @@
+use std::path::{Path, PathBuf};
+
+use bevy_asset::{AssetLoader, AssetPath, AssetServer, AssetServerMode};
+use bevy_reflect::TypePath;
+use bevy_tasks::futures_lite::StreamExt;
+
+use crate::{
+    asset_loading::{
+        TypstArchiveLimits, TypstAssetError, TypstTemplate, TypstTemplateSettings,
+        read_asset_requests,
+    },
+    file_resolver::StructuredInMemoryTemplate,
+    shared_files::SharedFilesLoad,
+};
+
+/// Loads an uncompressed project directory as a template, for working on templates without re-zipping them
+/// after every edit. The directory is marked by a `.typdir` file at its root, whose contents are ignored, so
+/// `cards/card.typdir` loads everything else under `cards/` as if it had been zipped.
+///
+/// Each file is read as a dependency of the template, so with hot reloading enabled an edit to any of them
+/// reloads it. Files added to the directory are only picked up once the template reloads for another reason.
+/// Hidden files and folders, whose names start with `.`, are skipped.
+#[derive(Default, TypePath)]
+pub struct TypstDirectoryLoader {
+    pub archive_limits: TypstArchiveLimits,
+    /// The asset server whose sources the directory's files are listed from, as loaders can't list folders
+    /// themselves. Directories fail to load without it.
+    pub asset_server: Option<AssetServer>,
+}
+
+impl std::fmt::Debug for TypstDirectoryLoader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TypstDirectoryLoader")
+            .field("archive_limits", &self.archive_limits)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AssetLoader for TypstDirectoryLoader {
+    type Asset = TypstTemplate;
+
+    type Settings = TypstTemplateSettings;
+
+    type Error = TypstAssetError;
+
+    async fn load(
+        &self,
+        _reader: &mut dyn bevy_asset::io::Reader,
+        settings: &Self::Settings,
+        load_context: &mut bevy_asset::LoadContext<'_>,
+    ) -> std::result::Result<Self::Asset, Self::Error> {
+        let _sharing = SharedFilesLoad::start();
+        let marker = load_context.path().path().to_path_buf();
+        let root = marker.parent().unwrap_or(Path::new("")).to_path_buf();
+        let source = load_context.path().source().clone_owned();
+        let asset_server =
+            self.asset_server
+                .as_ref()
+                .ok_or_else(|| TypstAssetError::AssetRead {
+                    path: root.clone(),
+                    reason: "no asset server to list the directory with".to_owned(),
+                })?;
+        let paths = list_files(asset_server, load_context.path(), &root, &marker).await?;
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            let bytes = load_context
+                .read_asset_bytes(AssetPath::from(path.clone()).with_source(source.clone()))
+                .await
+                .map_err(|error| TypstAssetError::AssetRead {
+                    path: path.clone(),
+                    reason: error.to_string(),
+                })?;
+            let relative = path.strip_prefix(&root).unwrap_or(&path).to_path_buf();
+            files.push((relative, bytes));
+        }
+        let _span =
+            bevy_log::info_span!("typst_template_load", path = %load_context.path()).entered();
+        let mut template =
+            StructuredInMemoryTemplate::from_files(files, settings, &self.archive_limits)?;
+        drop(_span);
+        read_asset_requests(&mut template, load_context, settings).await?;
+        Ok(TypstTemplate(template))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["typdir"]
+    }
+}
+
+/// Every file under `root` in the asset source of `template`, besides `marker`.
+async fn list_files(
+    asset_server: &AssetServer,
+    template: &AssetPath<'_>,
+    root: &Path,
+    marker: &Path,
+) -> Result<Vec<PathBuf>, TypstAssetError> {
+    let read_error = |path: &Path, error: &dyn std::fmt::Display| TypstAssetError::AssetRead {
+        path: path.to_path_buf(),
+        reason: error.to_string(),
+    };
+    let source = asset_server
+        .get_source(template.source())
+        .map_err(|error| read_error(root, &error))?;
+    // The same reader `LoadContext::read_asset_bytes` reads the files with, so that the files listed are those
+    // that are then read, i.e. the processed copies of them while the directory is being processed.
+    let reader = match asset_server.mode() {
+        AssetServerMode::Unprocessed => source.reader(),
+        AssetServerMode::Processed => source
+            .processed_reader()
+            .map_err(|error| read_error(root, &error))?,
+    };
+    let mut files = vec![];
+    let mut folders = vec![root.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        let mut entries = reader
+            .read_directory(&folder)
+            .await
+            .map_err(|error| read_error(&folder, &error))?;
+        while let Some(path) = entries.next().await {
+            if path == marker
+                || path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+            {
+                continue;
+            }
+            if reader
+                .is_directory(&path)
+                .await
+                .map_err(|error| read_error(&path, &error))?
+            {
+                folders.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,60 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    asset_loading::{TypstArchiveLimits, TypstTemplateSettings},
+    file_resolver::StructuredInMemoryTemplate,
+};
+
+/// Pack a typst project directory into a processed template in `OUT_DIR`, from a build script, for
+/// [`crate::include_typst_project`] to embed in the binary. Fails the build if the project can't be loaded as a
+/// template, such as when it has no main file. Hidden files and folders, whose names start with `.`, are skipped.
+///
+/// ```rust,ignore
+/// // build.rs, with bevy_typst_textures as a build dependency with the `embedded-projects` feature.
+/// fn main() {
+///     bevy_typst_textures::embed::embed_project("typst/card", "card");
+/// }
+///
+/// // Anywhere in the crate.
+/// let template = bevy_typst_textures::include_typst_project!("card");
+/// ```
+pub fn embed_project(dir: impl AsRef<Path>, name: &str) {
+    let dir = dir.as_ref();
+    println!("cargo::rerun-if-changed={}", dir.display());
+    let mut files = vec![];
+    read_dir_recursive(dir, dir, &mut files);
+    let mut template = StructuredInMemoryTemplate::from_files(
+        files,
+        &TypstTemplateSettings::default(),
+        &TypstArchiveLimits::unlimited(),
+    )
+    .unwrap_or_else(|error| panic!("typst project {} can't be embedded: {error}", dir.display()));
+    template.path_given = PathBuf::from(name);
+    let out_dir = std::env::var_os("OUT_DIR").expect("embed_project is run from a build script");
+    let out = Path::new(&out_dir).join(format!("{name}.typbin"));
+    std::fs::write(&out, template.save_processed())
+        .unwrap_or_else(|error| panic!("couldn't write {}: {error}", out.display()));
+}
+
+fn read_dir_recursive(root: &Path, dir: &Path, files: &mut Vec<(PathBuf, Vec<u8>)>) {
+    let entries = std::fs::read_dir(dir)
+        .unwrap_or_else(|error| panic!("couldn't read {}: {error}", dir.display()));
+    for entry in entries {
+        let path = entry
+            .unwrap_or_else(|error| panic!("couldn't read {}: {error}", dir.display()))
+            .path();
+        if path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+        if path.is_dir() {
+            read_dir_recursive(root, &path, files);
+        } else {
+            let bytes = std::fs::read(&path)
+                .unwrap_or_else(|error| panic!("couldn't read {}: {error}", path.display()));
+            files.push((path.strip_prefix(root).unwrap().to_path_buf(), bytes));
+        }
+    }
+}
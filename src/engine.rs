@@ -0,0 +1,130 @@
+use typst::{
+    Document, Features, Library, LibraryExt, World,
+    diag::{FileResult, Warned},
+    foundations::{Bytes, Datetime, Dict},
+    syntax::{FileId, Source},
+    text::{Font, FontBook, FontList, TextElem},
+    utils::LazyHash,
+};
+use typst_as_lib::{TypstAsLibError, TypstEngine, TypstTemplateMainFile, TypstWorld};
+
+/// How many compiles typst's memoized results are kept for. Live editing keeps them for a few, so a document
+/// recompiled after a small edit reuses the work of the last compile, at the cost of the memory they hold.
+#[cfg(feature = "typst-live-edit")]
+const COMEMO_MAX_AGE: usize = 10;
+#[cfg(not(feature = "typst-live-edit"))]
+const COMEMO_MAX_AGE: usize = 0;
+
+/// An engine built from a template, see [`crate::file_resolver::StructuredInMemoryTemplate::to_engine`]. Its
+/// documents are compiled with a standard library of their own, so the template's
+/// [`crate::file_resolver::BevyTypstDotToml::compiler_features`] are enabled for them, and the fallback families
+/// of the [`crate::font_config::TypstFontConfig`] it was built with are their default font list.
+pub struct TypstTemplateEngine {
+    engine: TypstEngine<TypstTemplateMainFile>,
+    features: Features,
+    /// The default `text(font: ..)`, from the [`crate::font_config::TypstFontConfig`].
+    font_list: Option<FontList>,
+}
+
+impl TypstTemplateEngine {
+    pub(crate) fn new(
+        engine: TypstEngine<TypstTemplateMainFile>,
+        features: Features,
+        font_list: Option<FontList>,
+    ) -> Self {
+        Self {
+            engine,
+            features,
+            font_list,
+        }
+    }
+
+    /// Compile the template's main file with `inputs` as `sys.inputs`.
+    pub fn compile_with_input<D, Doc>(&self, inputs: D) -> Warned<Result<Doc, TypstAsLibError>>
+    where
+        D: Into<Dict>,
+        Doc: Document,
+    {
+        let world = match self.engine.world_builder().build() {
+            Ok(world) => world,
+            Err(error) => {
+                return Warned {
+                    output: Err(error),
+                    warnings: Default::default(),
+                };
+            }
+        };
+        let mut library = Library::builder()
+            .with_inputs(inputs.into())
+            .with_features(self.features.clone())
+            .build();
+        if let Some(font_list) = &self.font_list {
+            library.styles.set(TextElem::font, font_list.clone());
+        }
+        let world = TemplateWorld {
+            world,
+            library: LazyHash::new(library),
+        };
+        // Cleared before as well as after, so that every file the document uses is asked of the file resolvers,
+        // for the access log of the template's dependencies.
+        #[cfg(not(feature = "typst-live-edit"))]
+        comemo::evict(0);
+        let Warned { output, warnings } = typst::compile::<Doc>(&world);
+        // As typst-as-lib does after each of its own compiles, to keep memoized results from piling up.
+        comemo::evict(COMEMO_MAX_AGE);
+        Warned {
+            output: output.map_err(Into::into),
+            warnings,
+        }
+    }
+
+    /// Compile the template's main file without inputs.
+    pub fn compile<Doc: Document>(&self) -> Warned<Result<Doc, TypstAsLibError>> {
+        self.compile_with_input(Dict::new())
+    }
+}
+
+impl std::fmt::Debug for TypstTemplateEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TypstTemplateEngine")
+            .field("features", &self.features)
+            .field("font_list", &self.font_list)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A typst-as-lib world with the library swapped out for the template's own.
+struct TemplateWorld<'a> {
+    world: TypstWorld<'a>,
+    library: LazyHash<Library>,
+}
+
+impl World for TemplateWorld<'_> {
+    fn library(&self) -> &LazyHash<Library> {
+        &self.library
+    }
+
+    fn book(&self) -> &LazyHash<FontBook> {
+        self.world.book()
+    }
+
+    fn main(&self) -> FileId {
+        self.world.main()
+    }
+
+    fn source(&self, id: FileId) -> FileResult<Source> {
+        self.world.source(id)
+    }
+
+    fn file(&self, id: FileId) -> FileResult<Bytes> {
+        self.world.file(id)
+    }
+
+    fn font(&self, index: usize) -> Option<Font> {
+        self.world.font(index)
+    }
+
+    fn today(&self, offset: Option<i64>) -> Option<Datetime> {
+        self.world.today(offset)
+    }
+}
@@ -0,0 +1,105 @@
+use bevy_asset::AssetPath;
+use bevy_ecs::message::MessageWriter;
+use typst::{
+    foundations::{Dict, IntoValue, Value},
+    layout::PagedDocument,
+};
+
+use crate::{
+    TypstJob, TypstRenderInfo, events::TypstJobFailed, file_resolver::StructuredInMemoryTemplate,
+    font_library::TypstEngineFonts, job_error::TypstJobError, logging::typst_log,
+    raster::RasterSettings,
+};
+
+/// The document rendered in place of a job that failed with [`crate::TypstJobOptions::error_texture`] set.
+const ERROR_CARD_SOURCE: &str = r##"#set page(width: 360pt, height: auto, margin: 10pt, fill: rgb("#3a1010"))
+#set text(size: 9pt, fill: rgb("#ffe0e0"))
+#text(weight: "bold", fill: rgb("#ff7070"))[#sys.inputs.title]
+#for diagnostic in sys.inputs.diagnostics {
+  block(width: 100%, inset: 5pt, radius: 2pt, fill: rgb("#00000050"), diagnostic)
+}
+"##;
+
+impl TypstJob {
+    /// Log and report this job as failed, sending an error card as its output if it has
+    /// [`crate::TypstJobOptions::error_texture`] set.
+    pub(crate) fn fail(
+        &self,
+        failed: &mut MessageWriter<TypstJobFailed>,
+        fonts: &TypstEngineFonts,
+        template: Option<&AssetPath>,
+        error: TypstJobError,
+        sources: Option<&StructuredInMemoryTemplate>,
+    ) {
+        let failure = TypstJobFailed::new(self.id, template, error, sources);
+        match &failure.error {
+            TypstJobError::Compile(_) => {
+                for diagnostic in &failure.diagnostics {
+                    typst_log!(
+                        Compile,
+                        Error,
+                        "[TYPST FATAL ERROR for {:?}] {}",
+                        template,
+                        diagnostic
+                    );
+                }
+            }
+            error => error.log(&template),
+        }
+        if self.job_options.error_texture {
+            self.send_error_card(&failure, fonts);
+        }
+        failed.write(failure);
+    }
+
+    /// Render an error card listing `failure`'s diagnostics and send it as this job's output.
+    fn send_error_card(&self, failure: &TypstJobFailed, fonts: &TypstEngineFonts) {
+        let title = match &failure.template {
+            Some(path) => format!("Typst error in {path}"),
+            None => "Typst error".to_owned(),
+        };
+        let diagnostics: Vec<Value> = failure
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string().into_value())
+            .collect();
+        let mut input = Dict::new();
+        input.insert("title".into(), title.into_value());
+        input.insert("diagnostics".into(), diagnostics.into_value());
+        let (engine, _) = fonts.to_engine(StructuredInMemoryTemplate::from_source(
+            "bevy_error_card.typ",
+            ERROR_CARD_SOURCE,
+        ));
+        let document = match engine.compile_with_input::<_, PagedDocument>(input).output {
+            Ok(document) => document,
+            Err(error) => {
+                typst_log!(
+                    Render,
+                    Warn,
+                    "[TYPST ERROR CARD WARNING] Couldn't compile the error card: {error}"
+                );
+                return;
+            }
+        };
+        let Some(page) = document.pages.first() else {
+            return;
+        };
+        let pixels_per_pt = self.job_options.pixels_per_pt;
+        let rendered = typst_render::render(page, pixels_per_pt);
+        let settings = RasterSettings::from_options(&self.job_options);
+        let page_size = page.frame.size();
+        let _ = self.info_target.try_send(TypstRenderInfo {
+            page_count: 1,
+            page_index: 0,
+            page_size_pt: (page_size.x.to_pt(), page_size.y.to_pt()),
+            size_px: settings.output_size(rendered.width(), rendered.height()),
+            content_size_px: settings
+                .orientation
+                .output_size(rendered.width(), rendered.height()),
+            pixels_per_pt,
+            ..Default::default()
+        });
+        let (image, _) = settings.build_image(rendered);
+        let _ = self.send_target.try_send(image);
+    }
+}
@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use bevy_asset::{AssetEvent, AssetId, AssetPath, AssetServer, Assets};
+use bevy_ecs::{
+    message::{Message, MessageReader, MessageWriter},
+    system::{Res, ResMut},
+};
+use bevy_image::Image;
+use bevy_platform::time::Instant;
+use typst::diag::SourceDiagnostic;
+
+use crate::{
+    TypstJobId, TypstRenderInfo, TypstTextureServer,
+    asset_loading::TypstTemplate,
+    diagnostics::TypstDiagnostic,
+    file_resolver::{StructuredInMemoryTemplate, TypstTemplateDiagnostics},
+    job_error::TypstJobError,
+    logging::typst_log,
+};
+
+/// Sent once a [`TypstTemplate`] has been added to its asset collection, along with any recoverable
+/// problems encountered while loading it (for example fonts that typst couldn't parse).
+#[derive(Debug, Clone, Message)]
+pub struct TypstTemplateReady {
+    pub id: AssetId<TypstTemplate>,
+    /// The asset path of the template, if it was loaded from one.
+    pub path: Option<AssetPath<'static>>,
+    pub diagnostics: TypstTemplateDiagnostics,
+}
+
+pub(crate) fn system_emit_template_ready(
+    mut asset_events: MessageReader<AssetEvent<TypstTemplate>>,
+    mut ready: MessageWriter<TypstTemplateReady>,
+    asset_server: Res<AssetServer>,
+    templates: Res<Assets<TypstTemplate>>,
+) {
+    for event in asset_events.read() {
+        let AssetEvent::Added { id } = event else {
+            continue;
+        };
+        let Some(template) = templates.get(*id) else {
+            continue;
+        };
+        let path = asset_server.get_path(*id).map(|path| path.into_owned());
+        for diagnostic in &template.0.load_diagnostics {
+            typst_log!(
+                Template,
+                Warn,
+                "[TYPST TEMPLATE WARNING for {:?}] {}",
+                path,
+                diagnostic
+            );
+        }
+        ready.write(TypstTemplateReady {
+            id: *id,
+            path,
+            diagnostics: template.0.load_diagnostics.clone(),
+        });
+    }
+}
+
+/// Sent once a job's image has been added to (or, for jobs rendering into an existing image, written over in)
+/// `Assets<Image>`, so systems can react to renders becoming available rather than polling for them.
+#[derive(Debug, Clone, Message)]
+pub struct TypstJobCompleted {
+    pub job: TypstJobId,
+    /// The rendered image, i.e. to compare against [`crate::TypstRenderTarget::image`].
+    pub image: AssetId<Image>,
+    /// The asset path of the job's template, if it was loaded from one.
+    pub template: Option<AssetPath<'static>>,
+    /// The time from the job being picked up from the queue until its image was available.
+    pub duration: Duration,
+    /// The part of `duration` spent compiling the job's template.
+    pub compile_time: Duration,
+    /// The part of `duration` spent rasterizing the page.
+    pub raster_time: Duration,
+    /// The width and height of the image in pixels.
+    pub dimensions: (u32, u32),
+    pub info: TypstRenderInfo,
+    /// Warnings typst reported while compiling the job.
+    pub warnings: Vec<SourceDiagnostic>,
+}
+
+/// Sent when a job fails, with its error resolved into diagnostics pointing at the template's sources where
+/// possible, i.e. for listing errors in an in-game console.
+#[derive(Debug, Clone, Message)]
+pub struct TypstJobFailed {
+    pub job: TypstJobId,
+    /// The asset path of the job's template, if it was loaded from one.
+    pub template: Option<AssetPath<'static>>,
+    pub error: TypstJobError,
+    pub diagnostics: Vec<TypstDiagnostic>,
+}
+
+impl TypstJobFailed {
+    pub(crate) fn new(
+        job: TypstJobId,
+        template: Option<&AssetPath>,
+        error: TypstJobError,
+        sources: Option<&StructuredInMemoryTemplate>,
+    ) -> Self {
+        Self {
+            job,
+            template: template.map(|path| path.clone_owned()),
+            diagnostics: error.diagnostics(sources),
+            error,
+        }
+    }
+}
+
+/// A job that has been rendered, waiting for its image to reach the asset server.
+#[derive(Debug)]
+pub(crate) struct PendingCompletion {
+    pub(crate) job: TypstJobId,
+    pub(crate) template: Option<AssetPath<'static>>,
+    pub(crate) started: Instant,
+    pub(crate) compile_time: Duration,
+    pub(crate) raster_time: Duration,
+    pub(crate) info: TypstRenderInfo,
+    pub(crate) warnings: Vec<SourceDiagnostic>,
+}
+
+pub(crate) fn system_emit_job_completed(
+    mut asset_events: MessageReader<AssetEvent<Image>>,
+    mut completed: MessageWriter<TypstJobCompleted>,
+    mut template_server: ResMut<TypstTextureServer>,
+) {
+    for event in asset_events.read() {
+        let id = match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => id,
+            AssetEvent::Removed { id } | AssetEvent::Unused { id } => {
+                template_server.completing.remove(id);
+                continue;
+            }
+            _ => continue,
+        };
+        let Some(pending) = template_server.completing.remove(id) else {
+            continue;
+        };
+        completed.write(TypstJobCompleted {
+            job: pending.job,
+            image: *id,
+            template: pending.template,
+            duration: pending.started.elapsed(),
+            compile_time: pending.compile_time,
+            raster_time: pending.raster_time,
+            dimensions: pending.info.size_px,
+            info: pending.info,
+            warnings: pending.warnings,
+        });
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use typst::foundations::Bytes;
+
+use crate::file_resolver::StructuredInMemoryTemplate;
+
+/// A source of files for a template beyond those it was loaded with, such as a database, a mod archive format,
+/// or generated content. Providers are asked for files the template doesn't have itself, in the order they
+/// were added.
+///
+/// Closures of `Fn(&Path) -> Option<Bytes>` are providers, i.e.
+/// `template.with_file_provider(|path: &Path| (path == Path::new("stats.json")).then(|| Bytes::new(stats_json())))`.
+pub trait TypstFileProvider: Send + Sync {
+    /// The contents of the file at `path`, relative to the template's root, or `None` if this provider doesn't
+    /// have it. Typst sources are read through this too, and must be UTF-8.
+    fn file(&self, path: &Path) -> Option<Bytes>;
+
+    /// The files this provider keeps that it read through the store of files shared between templates, so
+    /// they're kept in the store for as long as the provider is. Providers that don't share their files
+    /// needn't implement this.
+    fn shared_files(&self) -> Vec<Bytes> {
+        Vec::new()
+    }
+}
+
+impl<F> TypstFileProvider for F
+where
+    F: Fn(&Path) -> Option<Bytes> + Send + Sync,
+{
+    fn file(&self, path: &Path) -> Option<Bytes> {
+        self(path)
+    }
+}
+
+/// The [`TypstFileProvider`]s of a template, shared between clones of it.
+#[derive(Clone, Default)]
+pub struct TypstFileProviders(pub Vec<Arc<dyn TypstFileProvider>>);
+
+impl TypstFileProviders {
+    pub fn push(&mut self, provider: impl TypstFileProvider + 'static) {
+        self.0.push(Arc::new(provider));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The file at `path` from the first provider that has it.
+    pub fn file(&self, path: &Path) -> Option<Bytes> {
+        self.0.iter().find_map(|provider| provider.file(path))
+    }
+
+    /// The shared files kept by every provider, see [`TypstFileProvider::shared_files`].
+    pub fn shared_files(&self) -> Vec<Bytes> {
+        self.0
+            .iter()
+            .flat_map(|provider| provider.shared_files())
+            .collect()
+    }
+}
+
+/// The providers of a template mounted under a folder of another, see [`StructuredInMemoryTemplate::mount`].
+pub(crate) struct MountedProviders {
+    folder: PathBuf,
+    providers: TypstFileProviders,
+}
+
+impl MountedProviders {
+    pub(crate) fn new(folder: &Path, providers: TypstFileProviders) -> Self {
+        Self {
+            folder: folder.to_path_buf(),
+            providers,
+        }
+    }
+}
+
+impl TypstFileProvider for MountedProviders {
+    fn file(&self, path: &Path) -> Option<Bytes> {
+        self.providers.file(path.strip_prefix(&self.folder).ok()?)
+    }
+
+    fn shared_files(&self) -> Vec<Bytes> {
+        self.providers.shared_files()
+    }
+}
+
+impl std::fmt::Debug for TypstFileProviders {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TypstFileProviders({} providers)", self.0.len())
+    }
+}
+
+impl StructuredInMemoryTemplate {
+    /// This template with another [`TypstFileProvider`] to read files it doesn't have from.
+    pub fn with_file_provider(mut self, provider: impl TypstFileProvider + 'static) -> Self {
+        self.file_providers.push(provider);
+        self
+    }
+}
@@ -1,15 +1,36 @@
 use derive_more::*;
 use std::{
-    collections::BTreeMap,
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
     io::{Read, Seek},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
-use typst_as_lib::{TypstEngine, TypstTemplateMainFile};
+use typst_as_lib::{TypstEngine, file_resolver::FileResolver};
 use zip::ZipArchive;
 
-use typst::syntax::{FileId, Source, VirtualPath};
+use typst::{
+    Feature, Features,
+    diag::{FileError, FileResult},
+    foundations::Bytes,
+    syntax::{FileId, Source, VirtualPath},
+    text::Font,
+};
 
-use crate::asset_loading::TypstAssetError;
+use crate::{
+    asset_loading::{
+        TypstArchiveLimitExceeded, TypstArchiveLimits, TypstAssetError, TypstTemplateSettings,
+    },
+    engine::TypstTemplateEngine,
+    file_provider::{MountedProviders, TypstFileProviders},
+    font_config::{TypstFontConfig, TypstFontSource},
+    input_schema::TypstInputSchema,
+    lazy_archive::LazyZipMembers,
+    lazy_fonts::LazyFont,
+    logging::typst_log,
+    prelude::prelude_source,
+    shared_files::{share_bytes, share_fonts},
+};
 
 use serde::{Deserialize, Serialize};
 #[cfg(any(feature = "typst-asset-fonts", feature = "typst-search-system-fonts",))]
@@ -19,6 +40,7 @@ use typst_as_lib::typst_kit_options::TypstKitFontOptions;
 pub enum FilePreloaderError {
     NoPackageDotToml,
     MalformedPackageToml,
+    MalformedTypstToml,
     NoMainDotTyp,
 }
 
@@ -27,137 +49,1366 @@ impl std::fmt::Display for FilePreloaderError {
         match self {
             FilePreloaderError::NoPackageDotToml => write!(f, "No 'package.toml' file"),
             FilePreloaderError::MalformedPackageToml => write!(f, "Malformed 'package.toml' file"),
-            FilePreloaderError::NoMainDotTyp => write!(f, "No 'main.typ' file"),
+            FilePreloaderError::MalformedTypstToml => write!(f, "Malformed 'typst.toml' file"),
+            FilePreloaderError::NoMainDotTyp => {
+                write!(f, "No 'main.typ' file or other recognised entrypoint")
+            }
         }
     }
 }
 
+/// A recoverable problem encountered while loading a template. These don't stop the template
+/// from loading, but are reported through [`crate::events::TypstTemplateReady`].
+#[derive(Debug, Clone)]
+pub enum TemplateLoadDiagnostic {
+    /// A font file in the archive could not be parsed by typst and was skipped.
+    FontParseFailed { entry: String, reason: String },
+    /// A font is a variable font, which typst only uses the default instance of, so other weights and widths are
+    /// synthesized or fall back to other fonts. Static instances of the font can be added alongside it.
+    VariableFont { entry: String, family: String },
+    /// A text file wasn't UTF-8 and was converted, which may have garbled characters if the guessed encoding is wrong.
+    TextReencoded {
+        entry: String,
+        encoding: TypstTextEncoding,
+    },
+    /// A file with an extension typst doesn't use directly. It's still available to the template as bytes.
+    UnknownFile { entry: String },
+    /// A file that likely doesn't work the way its author meant, such as a font that isn't loaded as one or a
+    /// nested archive. It's still available to the template as bytes.
+    SuspiciousFile { entry: String, reason: String },
+    /// A field of `package.toml` had the wrong shape, and was ignored.
+    MalformedManifestField { field: String, reason: String },
+    /// `package.toml` has a field this crate doesn't know, which may be a typo.
+    UnknownManifestField { field: String },
+    /// A file listed in `asset_requests` couldn't be read from the game's assets.
+    AssetRequestFailed { path: PathBuf, reason: String },
+    /// An entry of the archive or directory was left out of the template.
+    SkippedEntry { entry: String, reason: String },
+    /// A template listed in `dependencies` couldn't be loaded.
+    DependencyFailed { path: PathBuf, reason: String },
+}
+
+impl std::fmt::Display for TemplateLoadDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateLoadDiagnostic::FontParseFailed { entry, reason } => {
+                write!(f, "Could not parse font '{entry}': {reason}")
+            }
+            TemplateLoadDiagnostic::VariableFont { entry, family } => {
+                write!(
+                    f,
+                    "'{entry}' is a variable font, only the default instance of '{family}' is used"
+                )
+            }
+            TemplateLoadDiagnostic::TextReencoded { entry, encoding } => {
+                write!(f, "'{entry}' isn't UTF-8, decoded it as {encoding}")
+            }
+            TemplateLoadDiagnostic::UnknownFile { entry } => {
+                write!(f, "'{entry}' isn't a kind of file typst templates use")
+            }
+            TemplateLoadDiagnostic::SuspiciousFile { entry, reason } => {
+                write!(f, "'{entry}' {reason}")
+            }
+            TemplateLoadDiagnostic::MalformedManifestField { field, reason } => {
+                write!(
+                    f,
+                    "Ignored malformed 'package.toml' field '{field}': {reason}"
+                )
+            }
+            TemplateLoadDiagnostic::UnknownManifestField { field } => {
+                write!(f, "Unknown 'package.toml' field '{field}'")
+            }
+            TemplateLoadDiagnostic::SkippedEntry { entry, reason } => {
+                write!(f, "Skipped '{entry}': {reason}")
+            }
+            TemplateLoadDiagnostic::AssetRequestFailed { path, reason } => {
+                write!(
+                    f,
+                    "Could not read requested asset '{}': {reason}",
+                    path.display()
+                )
+            }
+            TemplateLoadDiagnostic::DependencyFailed { path, reason } => {
+                write!(
+                    f,
+                    "Could not load dependency '{}': {reason}",
+                    path.display()
+                )
+            }
+        }
+    }
+}
+
+/// The recoverable problems found while loading a template, so tools and editors can show that it loaded
+/// but with problems.
+#[derive(Debug, Clone, Default)]
+pub struct TypstTemplateDiagnostics {
+    pub issues: Vec<TemplateLoadDiagnostic>,
+}
+
+impl TypstTemplateDiagnostics {
+    pub fn push(&mut self, issue: TemplateLoadDiagnostic) {
+        self.issues.push(issue);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, TemplateLoadDiagnostic> {
+        self.issues.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a TypstTemplateDiagnostics {
+    type Item = &'a TemplateLoadDiagnostic;
+    type IntoIter = std::slice::Iter<'a, TemplateLoadDiagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.issues.iter()
+    }
+}
+
+impl From<Vec<TemplateLoadDiagnostic>> for TypstTemplateDiagnostics {
+    fn from(issues: Vec<TemplateLoadDiagnostic>) -> Self {
+        Self { issues }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct StructuredInMemoryTemplate {
     pub loaded_toml: BevyTypstDotToml,
     pub loaded_fonts: Vec<typst::text::Font>,
+    /// Fonts read with [`TypstTemplateSettings::lazy_fonts`], parsed when an engine is first built from the
+    /// template.
+    pub lazy_fonts: Vec<LazyFont>,
     pub loaded_main: String,
     pub path_given: PathBuf,
-    pub file_resolver: Vec<(FileId, Vec<u8>)>,
+    /// The path of the main file within the template, for diagnostics. Templates made from a single source are
+    /// reported under `path_given` when this isn't set.
+    pub main_path: Option<PathBuf>,
+    /// The file the main source is given to typst as, so relative paths within it resolve from its folder. When
+    /// not set, it's given without a path, as if at the template's root. See [`StructuredInMemoryTemplate::with_entry`].
+    pub main_id: Option<FileId>,
+    /// The parsed main file, kept by [`StructuredInMemoryTemplate::edit_source`] so that edits to it only reparse
+    /// what changed. Engines are built from it rather than `loaded_main` while their texts match.
+    pub main_source: Option<Source>,
+    /// Files other than typst sources, such as images and data. Fonts and binary files loaded from archives
+    /// share their bytes with any other template that has the same file.
+    pub file_resolver: Vec<(FileId, Bytes)>,
     pub source_resolver: Vec<Source>,
+    /// Recoverable problems found while loading this template.
+    pub load_diagnostics: TypstTemplateDiagnostics,
+    /// Files that engines built from this template have resolved. Shared between clones of the template.
+    pub file_accesses: FileAccessLog,
+    /// Where files the template doesn't have itself are read from, see [`StructuredInMemoryTemplate::with_file_provider`].
+    pub file_providers: TypstFileProviders,
+}
+
+/// A record of the files resolved by a template's engines, shared between clones.
+#[derive(Debug, Clone, Default)]
+pub struct FileAccessLog(Arc<Mutex<BTreeSet<FileId>>>);
+
+impl FileAccessLog {
+    pub fn clear(&self) {
+        if let Ok(mut accesses) = self.0.lock() {
+            accesses.clear();
+        }
+    }
+
+    pub fn record(&self, id: FileId) {
+        if let Ok(mut accesses) = self.0.lock() {
+            accesses.insert(id);
+        }
+    }
+
+    pub fn accessed(&self) -> Vec<FileId> {
+        self.0
+            .lock()
+            .map(|accesses| accesses.iter().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Resolves a template's in-memory files and sources, falling back to its [`TypstFileProviders`], and
+/// recording each access in a [`FileAccessLog`].
+struct TrackedFileResolver {
+    files: HashMap<FileId, Bytes>,
+    sources: HashMap<FileId, Source>,
+    providers: TypstFileProviders,
+    /// Files read from `providers`, so each is only read once per engine.
+    provided_files: Mutex<HashMap<FileId, Bytes>>,
+    provided_sources: Mutex<HashMap<FileId, Source>>,
+    log: FileAccessLog,
+}
+
+impl TrackedFileResolver {
+    fn provide(&self, id: FileId) -> Option<Bytes> {
+        if id.package().is_some() || self.providers.is_empty() {
+            return None;
+        }
+        if let Some(bytes) = self
+            .provided_files
+            .lock()
+            .ok()
+            .and_then(|provided| provided.get(&id).cloned())
+        {
+            return Some(bytes);
+        }
+        let bytes = self.providers.file(id.vpath().as_rootless_path())?;
+        if let Ok(mut provided) = self.provided_files.lock() {
+            provided.insert(id, bytes.clone());
+        }
+        Some(bytes)
+    }
+
+    fn provide_source(&self, id: FileId) -> FileResult<Option<Source>> {
+        if let Some(source) = self
+            .provided_sources
+            .lock()
+            .ok()
+            .and_then(|provided| provided.get(&id).cloned())
+        {
+            return Ok(Some(source));
+        }
+        let Some(bytes) = self.provide(id) else {
+            return Ok(None);
+        };
+        let text = std::str::from_utf8(&bytes).map_err(|_| FileError::InvalidUtf8)?;
+        let source = Source::new(id, text.to_owned());
+        if let Ok(mut provided) = self.provided_sources.lock() {
+            provided.insert(id, source.clone());
+        }
+        Ok(Some(source))
+    }
+}
+
+impl FileResolver for TrackedFileResolver {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        if id.package().is_some() {
+            // Served by the package resolver after this one, but logged so packages count as dependencies.
+            self.log.record(id);
+        }
+        let bytes = match self.files.get(&id) {
+            Some(bytes) => Cow::Borrowed(bytes),
+            None => Cow::Owned(
+                self.provide(id)
+                    .ok_or_else(|| FileError::NotFound(id.vpath().as_rootless_path().into()))?,
+            ),
+        };
+        self.log.record(id);
+        Ok(bytes)
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        if id.package().is_some() {
+            // Served by the package resolver after this one, but logged so packages count as dependencies.
+            self.log.record(id);
+        }
+        let source = match self.sources.get(&id) {
+            Some(source) => Cow::Borrowed(source),
+            None => Cow::Owned(
+                self.provide_source(id)?
+                    .ok_or_else(|| FileError::NotFound(id.vpath().as_rootless_path().into()))?,
+            ),
+        };
+        self.log.record(id);
+        Ok(source)
+    }
 }
 
 impl StructuredInMemoryTemplate {
-    pub fn to_engine(self) -> (TypstEngine<TypstTemplateMainFile>, BevyTypstDotToml) {
-        let engine = TypstEngine::builder()
-            .main_file(self.loaded_main)
-            .with_static_file_resolver(self.file_resolver)
-            .with_static_source_file_resolver(self.source_resolver)
-            .fonts(self.loaded_fonts);
+    pub fn to_engine(self) -> (TypstTemplateEngine, BevyTypstDotToml) {
+        self.to_engine_with_fonts(&TypstFontConfig::default(), &[])
+    }
+
+    /// Build an engine with the template's fonts and the given library fonts, as per a [`TypstFontConfig`].
+    pub fn to_engine_with_fonts(
+        self,
+        font_config: &TypstFontConfig,
+        library: &[Font],
+    ) -> (TypstTemplateEngine, BevyTypstDotToml) {
+        let mut fonts = self.loaded_fonts;
+        fonts.extend(self.lazy_fonts.iter().filter_map(LazyFont::font));
+        let fonts = font_config.arrange(fonts, library);
+        let font_list = font_config.font_list(&fonts);
+        let mut sources: HashMap<FileId, Source> = self
+            .source_resolver
+            .into_iter()
+            .map(|source| (source.id(), source))
+            .collect();
+        let prelude = prelude_source();
+        sources.entry(prelude.id()).or_insert(prelude);
+        let engine = match (self.main_source, self.main_id) {
+            (Some(source), _) if source.text() == self.loaded_main => {
+                TypstEngine::builder().main_file(source)
+            }
+            (_, Some(id)) => TypstEngine::builder().main_file(Source::new(id, self.loaded_main)),
+            (_, None) => TypstEngine::builder().main_file(self.loaded_main),
+        };
+        let engine = engine
+            .add_file_resolver(TrackedFileResolver {
+                files: self.file_resolver.into_iter().collect(),
+                sources,
+                providers: self.file_providers,
+                provided_files: Mutex::default(),
+                provided_sources: Mutex::default(),
+                log: self.file_accesses,
+            })
+            .fonts(fonts);
         #[cfg(all(
             feature = "typst-packages",
             any(feature = "typst-resolve-ureq", feature = "typst-resolve-reqwest")
         ))]
         let engine = engine.with_package_file_resolver();
-        #[cfg(feature = "typst-asset-fonts")]
-        let engine = engine.search_fonts_with(
-            TypstKitFontOptions::default()
-                .include_system_fonts(cfg!(feature = "typst-search-system-fonts")),
-        );
-        #[cfg(all(
-            feature = "typst-search-system-fonts",
-            not(feature = "typst-asset-fonts")
-        ))]
-        let engine =
-            engine.search_fonts_with(TypstKitFontOptions::default().include_system_fonts(true));
-        let engine = engine.build();
+        #[cfg(any(feature = "typst-asset-fonts", feature = "typst-search-system-fonts"))]
+        let engine = {
+            let options = TypstKitFontOptions::default().include_system_fonts(
+                cfg!(feature = "typst-search-system-fonts")
+                    && font_config.uses(TypstFontSource::System),
+            );
+            #[cfg(feature = "typst-asset-fonts")]
+            let options = options.include_embedded_fonts(font_config.uses(TypstFontSource::Asset));
+            engine.search_fonts_with(options)
+        };
+        let features = compiler_features(&self.loaded_toml.compiler_features);
+        let engine = TypstTemplateEngine::new(engine.build(), features, font_list);
         (engine, self.loaded_toml)
     }
 
-    pub fn from_zip<R: Read + Seek>(mut zip: ZipArchive<R>) -> Result<Self, TypstAssetError> {
+    /// This template with extra typst compiler features enabled, by name as per [`BevyTypstDotToml::compiler_features`].
+    pub fn with_compiler_features(mut self, features: &[String]) -> Self {
+        for feature in features {
+            if !self.loaded_toml.compiler_features.contains(feature) {
+                self.loaded_toml.compiler_features.push(feature.clone());
+            }
+        }
+        self
+    }
+
+    /// This template with the typst file at `path`, relative to its root, compiled in place of its main file, for
+    /// archives holding several documents that share fonts and assets. Every other file stays available to
+    /// import, the main file included. `None` if the template has no typst file at `path`.
+    pub fn with_entry(mut self, path: &Path) -> Option<Self> {
+        let id = FileId::new(None, VirtualPath::new(path));
+        if self.main_path.as_deref().map(VirtualPath::new).as_ref() == Some(id.vpath()) {
+            return Some(self);
+        }
+        let entry = self
+            .source_resolver
+            .iter()
+            .find(|source| source.id() == id)?
+            .text()
+            .to_owned();
+        let main = std::mem::replace(&mut self.loaded_main, entry);
+        if let Some(main_path) = self.main_path.replace(path.to_path_buf()) {
+            let main_id = FileId::new(None, VirtualPath::new(main_path));
+            self.source_resolver.push(Source::new(main_id, main));
+        }
+        self.main_id = Some(id);
+        self.main_source = None;
+        Some(self)
+    }
+
+    /// The paths of the typst files this template can be compiled from with [`StructuredInMemoryTemplate::with_entry`],
+    /// relative to its root, starting with its main file.
+    pub fn entry_points(&self) -> Vec<PathBuf> {
+        self.main_path
+            .iter()
+            .cloned()
+            .chain(
+                self.source_resolver
+                    .iter()
+                    .filter(|source| source.id().package().is_none())
+                    .map(|source| source.id().vpath().as_rootless_path().to_path_buf()),
+            )
+            .collect()
+    }
+
+    /// A template consisting of only a main file with the given source, registered under `name`.
+    pub fn from_source(name: impl Into<PathBuf>, source: impl Into<String>) -> Self {
+        Self {
+            loaded_main: source.into(),
+            path_given: name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Add a file read from the game's assets for [`BevyTypstDotToml::asset_requests`], under
+    /// [`ASSET_REQUESTS_ROOT`]. Files the template already has at that path take priority. Without a hint, the
+    /// file is treated as per its extension.
+    pub fn add_requested_asset(
+        &mut self,
+        path: &Path,
+        hint: Option<&FileTypeHint>,
+        bytes: Vec<u8>,
+    ) {
+        let entry = Path::new(ASSET_REQUESTS_ROOT).join(path);
+        let id = FileId::new(None, VirtualPath::new(&entry));
+        if self.has_file(id) {
+            return;
+        }
+        let extension = path.extension().and_then(|os| os.to_str());
+        match hint {
+            Some(FileTypeHint::Font) => self.add_font(&entry, bytes),
+            Some(FileTypeHint::Typst) => self.add_source(id, &entry, bytes),
+            Some(FileTypeHint::Image) => self.file_resolver.push((id, share_bytes(bytes))),
+            None => match extension {
+                _ if is_font(path) => self.add_font(&entry, bytes),
+                Some("typ") => self.add_source(id, &entry, bytes),
+                _ => self.file_resolver.push((id, share_bytes(bytes))),
+            },
+        }
+    }
+
+    /// Make the files and fonts of another template available to this one under `folder`, i.e. to mount a
+    /// [`BevyTypstDotToml::dependencies`] entry. Files this template already has at those paths take priority,
+    /// and the other template's [`crate::file_provider::TypstFileProvider`]s are asked after this one's.
+    pub fn mount(&mut self, folder: &Path, dependency: &StructuredInMemoryTemplate) {
+        let remap = |id: FileId| {
+            FileId::new(
+                None,
+                VirtualPath::new(folder.join(id.vpath().as_rootless_path())),
+            )
+        };
+        let mut sources: Vec<Source> = dependency
+            .source_resolver
+            .iter()
+            .filter(|source| source.id().package().is_none())
+            .map(|source| Source::new(remap(source.id()), source.text().to_owned()))
+            .collect();
+        if let Some(main_path) = &dependency.main_path {
+            let id = FileId::new(None, VirtualPath::new(folder.join(main_path)));
+            sources.push(Source::new(id, dependency.loaded_main.clone()));
+        }
+        for source in sources {
+            if !self.has_file(source.id()) {
+                self.source_resolver.push(source);
+            }
+        }
+        for (id, bytes) in &dependency.file_resolver {
+            let id = remap(*id);
+            if id.package().is_none() && !self.has_file(id) {
+                self.file_resolver.push((id, bytes.clone()));
+            }
+        }
+        self.loaded_fonts
+            .extend(dependency.loaded_fonts.iter().cloned());
+        self.lazy_fonts
+            .extend(dependency.lazy_fonts.iter().cloned());
+        if !dependency.file_providers.is_empty() {
+            self.file_providers.push(MountedProviders::new(
+                folder,
+                dependency.file_providers.clone(),
+            ));
+        }
+    }
+
+    /// If this template has a source or file with the given id.
+    fn has_file(&self, id: FileId) -> bool {
+        self.file_resolver
+            .iter()
+            .any(|(existing, _)| *existing == id)
+            || self.source_resolver.iter().any(|source| source.id() == id)
+    }
+
+    fn add_font(&mut self, entry: &Path, bytes: Vec<u8>) {
+        read_fonts(
+            entry,
+            bytes,
+            &mut self.loaded_fonts,
+            &mut self.load_diagnostics,
+        );
+    }
+
+    fn add_source(&mut self, id: FileId, entry: &Path, bytes: Vec<u8>) {
+        match decode_text(bytes, true) {
+            Ok((text, encoding)) => {
+                if encoding.is_reencoded() {
+                    self.load_diagnostics
+                        .push(TemplateLoadDiagnostic::TextReencoded {
+                            entry: entry.display().to_string(),
+                            encoding,
+                        });
+                }
+                self.source_resolver.push(Source::new(id, text));
+            }
+            Err(reason) => self
+                .load_diagnostics
+                .push(TemplateLoadDiagnostic::AssetRequestFailed {
+                    path: entry.to_path_buf(),
+                    reason,
+                }),
+        }
+    }
+
+    /// Load a template from a zip archive, using [`DEFAULT_ENTRY_POINTS`] to find the main file.
+    pub fn from_zip<R: Read + Seek>(zip: ZipArchive<R>) -> Result<Self, TypstAssetError> {
+        Self::from_zip_with_entry_points(zip, DEFAULT_ENTRY_POINTS)
+    }
+
+    /// Load a template from a zip archive. The `[package]` table of a `typst.toml` fills in the name, version,
+    /// authors, and entrypoint of `package.toml` where it doesn't set them, so existing typst projects
+    /// can be used as-is. The main file is the `entrypoint` if set, otherwise the first of `entry_points`
+    /// found, preferring files at the root of the archive.
+    pub fn from_zip_with_entry_points<R: Read + Seek>(
+        zip: ZipArchive<R>,
+        entry_points: &[&str],
+    ) -> Result<Self, TypstAssetError> {
+        Self::from_zip_with_limits(zip, entry_points, &TypstArchiveLimits::default())
+    }
+
+    /// Load a template from a zip archive as per [`StructuredInMemoryTemplate::from_zip_with_entry_points`],
+    /// failing with [`TypstAssetError::LimitExceeded`] if the archive is over any of the given limits.
+    pub fn from_zip_with_limits<R: Read + Seek>(
+        zip: ZipArchive<R>,
+        entry_points: &[&str],
+        limits: &TypstArchiveLimits,
+    ) -> Result<Self, TypstAssetError> {
+        read_zip(
+            zip,
+            &TypstTemplateSettings::default(),
+            entry_points,
+            limits,
+            None,
+            &mut |_, _| {},
+        )
+    }
+
+    /// Load a template from a zip archive with the given [`TypstTemplateSettings`], failing with
+    /// [`TypstAssetError::LimitExceeded`] if the archive is over any of the given limits.
+    pub fn from_zip_with_settings<R: Read + Seek>(
+        zip: ZipArchive<R>,
+        settings: &TypstTemplateSettings,
+        limits: &TypstArchiveLimits,
+    ) -> Result<Self, TypstAssetError> {
+        read_zip(
+            zip,
+            settings,
+            DEFAULT_ENTRY_POINTS,
+            limits,
+            None,
+            &mut |_, _| {},
+        )
+    }
+
+    /// Load a template from the bytes of a zip archive, such as one downloaded from a server or embedded with
+    /// `include_bytes!`, named `name` as per [`StructuredInMemoryTemplate::path_given`]. Files its `package.toml`
+    /// requests from the game's assets aren't read, as that needs the asset server.
+    pub fn from_zip_bytes(
+        name: impl Into<PathBuf>,
+        zip_bytes: impl Into<Arc<[u8]>>,
+    ) -> Result<Self, TypstAssetError> {
+        let mut template = Self::from_zip_bytes_with_settings(
+            zip_bytes.into(),
+            &TypstTemplateSettings::default(),
+            &TypstArchiveLimits::default(),
+        )?;
+        template.path_given = name.into();
+        Ok(template)
+    }
+
+    /// Load a template from the bytes of a zip archive as per [`StructuredInMemoryTemplate::from_zip_with_settings`].
+    /// With [`TypstTemplateSettings::lazy_members`] set, the archive is kept so binary files can be
+    /// decompressed when they're first used rather than now.
+    pub fn from_zip_bytes_with_settings(
+        bytes: Arc<[u8]>,
+        settings: &TypstTemplateSettings,
+        limits: &TypstArchiveLimits,
+    ) -> Result<Self, TypstAssetError> {
+        Self::from_zip_bytes_with_progress(bytes, settings, limits, &mut |_, _| {})
+    }
+
+    /// As per [`StructuredInMemoryTemplate::from_zip_bytes_with_settings`], calling `progress` with the number
+    /// of entries read and the number there are after each entry.
+    pub(crate) fn from_zip_bytes_with_progress(
+        bytes: Arc<[u8]>,
+        settings: &TypstTemplateSettings,
+        limits: &TypstArchiveLimits,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<Self, TypstAssetError> {
+        let zip =
+            ZipArchive::new(std::io::Cursor::new(bytes.clone())).map_err(TypstAssetError::Zip)?;
+        let lazy = settings.lazy_members.then_some(bytes);
+        read_zip(zip, settings, DEFAULT_ENTRY_POINTS, limits, lazy, progress)
+    }
+
+    /// Load a template from files given by their paths relative to the template's root, such as those of a
+    /// project directory, with the given [`TypstTemplateSettings`]. Unlike archives, a folder that every file is
+    /// inside of isn't taken to be the root unless it's set as [`TypstTemplateSettings::root`].
+    pub fn from_files(
+        files: impl IntoIterator<Item = (PathBuf, Vec<u8>)>,
+        settings: &TypstTemplateSettings,
+        limits: &TypstArchiveLimits,
+    ) -> Result<Self, TypstAssetError> {
+        let layout = TemplateLayout::new(settings, None)?;
+        let mut total_bytes = 0;
+        let mut count = 0;
+        let mut set = TemplateFileSet::new(settings);
+        for (path, buf) in files {
+            count += 1;
+            check_entry_count(count, limits)?;
+            let Some(path) = set.locate(&layout, &path.to_string_lossy()) else {
+                continue;
+            };
+            count_limited(buf.len() as u64, &path, limits, &mut total_bytes)?;
+            set.add(&path, buf)?;
+        }
+        set.finish(settings.main.as_deref(), DEFAULT_ENTRY_POINTS)
+    }
+}
+
+/// Read a zip archive's files into a template. Given the archive's bytes as `lazy`, binary files are left in the
+/// archive to be read by a [`LazyZipMembers`] provider instead. `progress` is called with the number of entries
+/// read and the number there are as each is read.
+fn read_zip<R: Read + Seek>(
+    mut zip: ZipArchive<R>,
+    settings: &TypstTemplateSettings,
+    entry_points: &[&str],
+    limits: &TypstArchiveLimits,
+    lazy: Option<Arc<[u8]>>,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<StructuredInMemoryTemplate, TypstAssetError> {
+    check_entry_count(zip.len(), limits)?;
+    let file_names: Vec<String> = zip
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .map(str::to_owned)
+        .collect();
+    let layout = TemplateLayout::new(settings, Some(&file_names))?;
+    let mut total_bytes = 0;
+    let mut declared_bytes = 0;
+    let mut files = TemplateFileSet::new(settings);
+    let mut lazy_members = HashMap::new();
+    let password = settings.password.as_deref();
+    let entry_count = zip.len();
+    for ix in 0..entry_count {
+        progress(ix, entry_count);
+        let mut file = open_zip_entry(&mut zip, ix, password)?;
+        if !file.is_file() {
+            continue;
+        }
+        let Some(path) = files.locate(&layout, file.name()) else {
+            continue;
+        };
+        if lazy.is_some() && is_binary(&path) {
+            // Count the size the archive declares, as the member won't be decompressed until it's used. The
+            // provider counts the bytes it really decompresses to in its place.
+            count_limited(file.size(), &path, limits, &mut total_bytes)?;
+            declared_bytes += file.size();
+            if files.claim(&path) {
+                lazy_members.insert(path, ix);
+            }
+            continue;
+        }
+        let buf = read_limited(&mut file, &path, limits, &mut total_bytes)?;
+        files.add(&path, buf)?;
+    }
+    progress(entry_count, entry_count);
+    let mut template = files.finish(settings.main.as_deref(), entry_points)?;
+    if let Some(bytes) = lazy
+        && !lazy_members.is_empty()
+    {
+        template.file_providers.push(LazyZipMembers::new(
+            bytes,
+            lazy_members,
+            limits,
+            settings.password.clone(),
+            total_bytes - declared_bytes,
+        )?);
+    }
+    Ok(template)
+}
+
+/// Open an entry of a zip archive, decrypting it with `password` if it's encrypted.
+pub(crate) fn open_zip_entry<'a, R: Read + Seek>(
+    zip: &'a mut ZipArchive<R>,
+    index: usize,
+    password: Option<&str>,
+) -> Result<zip::read::ZipFile<'a, R>, TypstAssetError> {
+    let encrypted = zip
+        .by_index_raw(index)
+        .map_err(TypstAssetError::Zip)?
+        .encrypted();
+    match password {
+        Some(password) if encrypted => zip.by_index_decrypt(index, password.as_bytes()),
+        _ => zip.by_index(index),
+    }
+    .map_err(TypstAssetError::Zip)
+}
+
+/// If a file is one typst reads as bytes, as per [`BINARY_EXTENSIONS`].
+fn is_binary(path: &Path) -> bool {
+    path.extension()
+        .and_then(|os| os.to_str())
+        .is_some_and(|extension| BINARY_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+/// Where a template's files are within an archive or directory, as per [`TypstTemplateSettings`].
+pub(crate) struct TemplateLayout {
+    root: Option<PathBuf>,
+    ignore: Vec<glob::Pattern>,
+}
+
+/// Where an entry of an archive or directory belongs in a template.
+pub(crate) enum EntryLocation {
+    /// The entry's path relative to the template's root.
+    Path(PathBuf),
+    /// The entry is OS metadata, outside of the root, or matches an ignore pattern.
+    Ignored,
+    /// The entry can't be part of the template, for the given reason.
+    Rejected(String),
+}
+
+impl TemplateLayout {
+    /// The layout given by `settings`. Without a root set, a folder that every one of `guess_from` is inside of
+    /// is used as the root, if there is one.
+    pub(crate) fn new(
+        settings: &TypstTemplateSettings,
+        guess_from: Option<&[String]>,
+    ) -> Result<Self, TypstAssetError> {
+        let ignore = settings
+            .ignore
+            .iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern).map_err(|error| {
+                    TypstAssetError::InvalidSettings(format!(
+                        "ignore pattern '{pattern}' is invalid: {error}"
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let root = match &settings.root {
+            Some(root) => match normalize_entry(&root.to_string_lossy()) {
+                Ok(Some(root)) => Some(root),
+                _ => {
+                    return Err(TypstAssetError::InvalidSettings(format!(
+                        "root '{}' isn't a folder within the template",
+                        root.display()
+                    )));
+                }
+            },
+            None => guess_from.and_then(shared_root),
+        };
+        Ok(Self { root, ignore })
+    }
+
+    /// Where an entry, named by its path within the archive or directory, belongs in the template.
+    pub(crate) fn locate(&self, name: &str) -> EntryLocation {
+        let path = match normalize_entry(name) {
+            Ok(Some(path)) => path,
+            Ok(None) => return EntryLocation::Ignored,
+            Err(reason) => return EntryLocation::Rejected(reason),
+        };
+        let path = match &self.root {
+            Some(root) => match path.strip_prefix(root) {
+                Ok(path) => path.to_path_buf(),
+                Err(_) => return EntryLocation::Ignored,
+            },
+            None => path,
+        };
+        if self
+            .ignore
+            .iter()
+            .any(|pattern| pattern.matches_path(&path))
+        {
+            return EntryLocation::Ignored;
+        }
+        EntryLocation::Path(path)
+    }
+}
+
+/// Folders of metadata that operating systems and archivers add, which are never part of a template.
+const METADATA_FOLDERS: &[&str] = &["__MACOSX"];
+
+/// Files of metadata that operating systems add, which are never part of a template.
+const METADATA_FILES: &[&str] = &[".DS_Store", "Thumbs.db", "desktop.ini"];
+
+/// Normalize the path of an archive entry: separators become `/`, names are NFC normalized so paths typed in
+/// a template match however the archiver encoded them, and `.` segments are dropped. `Ok(None)` for OS
+/// metadata, and an error for paths that are absolute or leave the archive.
+fn normalize_entry(name: &str) -> Result<Option<PathBuf>, String> {
+    use unicode_normalization::UnicodeNormalization;
+    let name: String = name.replace('\\', "/").nfc().collect();
+    if name.starts_with('/')
+        || name
+            .split('/')
+            .next()
+            .is_some_and(|first| first.ends_with(':'))
+    {
+        return Err("its path is absolute".to_owned());
+    }
+    let mut path = PathBuf::new();
+    let segments: Vec<&str> = name
+        .split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect();
+    for (ix, segment) in segments.iter().enumerate() {
+        if *segment == ".." {
+            return Err("its path leads outside of the archive".to_owned());
+        }
+        let is_file = ix + 1 == segments.len();
+        if (!is_file && METADATA_FOLDERS.contains(segment))
+            || (is_file && (METADATA_FILES.contains(segment) || segment.starts_with("._")))
+        {
+            return Ok(None);
+        }
+        path.push(segment);
+    }
+    Ok((!segments.is_empty()).then_some(path))
+}
+
+/// The folder every one of `names` is inside of, if there is one. OS metadata and entries that would be
+/// rejected aren't counted.
+fn shared_root(names: &[String]) -> Option<PathBuf> {
+    let mut roots = names
+        .iter()
+        .filter_map(|name| normalize_entry(name).ok().flatten())
+        .map(|path| {
+            let mut components = path.components();
+            let root = components.next()?;
+            components.next().map(|_| PathBuf::from(root.as_os_str()))
+        });
+    let first = roots.next()??;
+    roots
+        .all(|root| root.as_ref() == Some(&first))
+        .then_some(first)
+}
+
+/// A template's files as they're read from an archive or directory, sorted by what typst uses them for.
+#[derive(Default)]
+pub(crate) struct TemplateFileSet {
+    paths: BTreeSet<PathBuf>,
+    typst_dot_toml: Option<BevyTypstDotToml>,
+    typst_manifest: Option<TypstManifest>,
+    typ_files: Vec<(PathBuf, String)>,
+    loaded_fonts: Vec<typst::text::Font>,
+    /// Set as per [`TypstTemplateSettings::lazy_fonts`].
+    defer_fonts: bool,
+    lazy_fonts: Vec<LazyFont>,
+    file_resolver: Vec<(FileId, Bytes)>,
+    load_diagnostics: TypstTemplateDiagnostics,
+}
+
+impl TemplateFileSet {
+    pub(crate) fn new(settings: &TypstTemplateSettings) -> Self {
+        Self {
+            defer_fonts: settings.lazy_fonts,
+            ..Default::default()
+        }
+    }
+
+    /// Where an entry belongs in the template as per [`TemplateLayout::locate`], or `None` if it should be
+    /// skipped, noting entries that were rejected as diagnostics.
+    pub(crate) fn locate(&mut self, layout: &TemplateLayout, name: &str) -> Option<PathBuf> {
+        match layout.locate(name) {
+            EntryLocation::Path(path) => Some(path),
+            EntryLocation::Ignored => None,
+            EntryLocation::Rejected(reason) => {
+                self.load_diagnostics
+                    .push(TemplateLoadDiagnostic::SkippedEntry {
+                        entry: name.to_owned(),
+                        reason,
+                    });
+                None
+            }
+        }
+    }
+
+    /// Claim a path for a file, or note it as skipped and return `false` if another file already has it.
+    pub(crate) fn claim(&mut self, path: &Path) -> bool {
+        if self.paths.insert(path.to_path_buf()) {
+            return true;
+        }
+        self.load_diagnostics
+            .push(TemplateLoadDiagnostic::SkippedEntry {
+                entry: path.display().to_string(),
+                reason: "another entry has the same path once normalized".to_owned(),
+            });
+        false
+    }
+
+    /// Add a file, by its path relative to the template's root.
+    pub(crate) fn add(&mut self, path: &Path, buf: Vec<u8>) -> Result<(), TypstAssetError> {
         use serde::Deserialize;
-        let mut typst_dot_toml_path = None;
-        let mut main_dot_typ = None;
-        let mut loaded_fonts = vec![];
-        let mut source_resolver = vec![];
-        let mut file_resolver = vec![];
-        let mut prefix = None;
-        for ix in 0..zip.len() {
-            let mut file = zip.by_index(ix).map_err(TypstAssetError::Zip)?;
-            if prefix.is_none() {
-                prefix = Some(file.name().to_owned())
-            }
-            if file.is_file() {
-                let path_buf = PathBuf::from(file.name());
-                if path_buf.starts_with("__MACOSX") {
-                    continue;
+        if !self.claim(path) {
+            return Ok(());
+        }
+        match path.extension().and_then(|os| os.to_str()) {
+            Some("typ") => {
+                let (string_buf, encoding) =
+                    decode_text(buf, true).map_err(|reason| invalid_text(path, reason))?;
+                self.note_encoding(path, encoding);
+                self.typ_files.push((path.to_path_buf(), string_buf));
+            }
+            _ if is_font(path) && self.defer_fonts => {
+                let fonts = LazyFont::read(share_bytes(buf));
+                match fonts.first() {
+                    Some(_) => self.lazy_fonts.extend(fonts),
+                    None => self
+                        .load_diagnostics
+                        .push(TemplateLoadDiagnostic::FontParseFailed {
+                            entry: path.display().to_string(),
+                            reason: "typst could not read the font's metadata".to_owned(),
+                        }),
                 }
-                let path = path_buf.strip_prefix(prefix.as_ref().unwrap()).unwrap();
-                match path.extension().and_then(|os| os.to_str()) {
-                    Some("typ") => {
-                        if path.file_name().unwrap() == "main.typ" {
-                            let mut string_buf = String::new();
-                            file.read_to_string(&mut string_buf)
-                                .map_err(TypstAssetError::Io)?;
-                            main_dot_typ = Some(string_buf);
-                        } else {
-                            let mut string_buf = String::new();
-                            file.read_to_string(&mut string_buf)
-                                .map_err(TypstAssetError::Io)?;
-                            let source =
-                                Source::new(FileId::new(None, VirtualPath::new(path)), string_buf);
-                            source_resolver.push(source);
-                        }
-                    }
-                    Some("otf") => {
-                        let mut buf = Vec::new();
-                        file.read_to_end(&mut buf).map_err(TypstAssetError::Io)?;
-                        if let Some(font) =
-                            typst::text::Font::new(typst::foundations::Bytes::new(buf), 0)
-                        {
-                            loaded_fonts.push(font);
-                        }
-                    }
-                    Some("toml") if path.file_name().unwrap() == "package.toml" => {
-                        let mut string_buf = String::new();
-                        file.read_to_string(&mut string_buf)
-                            .map_err(TypstAssetError::Io)?;
-                        typst_dot_toml_path = Some(
-                            BevyTypstDotToml::deserialize(toml::Deserializer::new(&string_buf))
-                                .map_err(|_| {
-                                    TypstAssetError::Preloader(
-                                        FilePreloaderError::MalformedPackageToml,
-                                    )
-                                })?,
-                        );
-                    }
-                    _ => {
-                        let mut buf = Vec::new();
-                        file.read_to_end(&mut buf).map_err(TypstAssetError::Io)?;
-                        file_resolver.push((FileId::new(None, VirtualPath::new(path)), buf));
-                    }
+            }
+            _ if is_font(path) => read_fonts(
+                path,
+                buf,
+                &mut self.loaded_fonts,
+                &mut self.load_diagnostics,
+            ),
+            Some("toml") if path.file_name().unwrap() == "package.toml" => {
+                let string_buf = manifest_text(path, buf)?;
+                let (toml, issues) = BevyTypstDotToml::from_str_lenient(&string_buf)
+                    .map_err(TypstAssetError::Preloader)?;
+                self.load_diagnostics.issues.extend(issues);
+                self.typst_dot_toml = Some(toml);
+            }
+            Some("toml") if path.file_name().unwrap() == "typst.toml" => {
+                let string_buf = manifest_text(path, buf)?;
+                self.typst_manifest = Some(
+                    TypstManifest::deserialize(toml::Deserializer::new(&string_buf)).map_err(
+                        |_| TypstAssetError::Preloader(FilePreloaderError::MalformedTypstToml),
+                    )?,
+                );
+            }
+            Some(extension) if TEXT_DATA_EXTENSIONS.contains(&extension) => {
+                let (text, encoding) =
+                    decode_text(buf, true).map_err(|reason| invalid_text(path, reason))?;
+                self.note_encoding(path, encoding);
+                self.file_resolver.push((
+                    FileId::new(None, VirtualPath::new(path)),
+                    Bytes::new(text.into_bytes()),
+                ));
+            }
+            extension => {
+                if let Some(reason) = extension.and_then(suspicious_extension) {
+                    self.load_diagnostics
+                        .push(TemplateLoadDiagnostic::SuspiciousFile {
+                            entry: path.display().to_string(),
+                            reason: reason.to_owned(),
+                        });
+                } else if !extension.is_some_and(|extension| {
+                    BINARY_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+                }) {
+                    self.load_diagnostics
+                        .push(TemplateLoadDiagnostic::UnknownFile {
+                            entry: path.display().to_string(),
+                        });
                 }
+                self.file_resolver
+                    .push((FileId::new(None, VirtualPath::new(path)), share_bytes(buf)));
             }
         }
-        let loaded_main =
-            main_dot_typ.ok_or(TypstAssetError::Preloader(FilePreloaderError::NoMainDotTyp))?;
-        let loaded_toml = typst_dot_toml_path.unwrap_or_default();
+        Ok(())
+    }
+
+    fn note_encoding(&mut self, path: &Path, encoding: TypstTextEncoding) {
+        if encoding.is_reencoded() {
+            self.load_diagnostics
+                .push(TemplateLoadDiagnostic::TextReencoded {
+                    entry: path.display().to_string(),
+                    encoding,
+                });
+        }
+    }
+
+    /// Build the template, with `main` as its main file if set, otherwise as per [`BevyTypstDotToml::entrypoint`].
+    pub(crate) fn finish(
+        self,
+        main: Option<&Path>,
+        entry_points: &[&str],
+    ) -> Result<StructuredInMemoryTemplate, TypstAssetError> {
+        let mut typ_files = self.typ_files;
+        let mut loaded_toml: BevyTypstDotToml = self.typst_dot_toml.unwrap_or_default();
+        if let Some(package) = self.typst_manifest.and_then(|manifest| manifest.package) {
+            package.fill_missing(&mut loaded_toml);
+        }
+        if let Some(main) = main {
+            loaded_toml.entrypoint = Some(main.to_path_buf());
+        }
+        let main_index = match loaded_toml.entrypoint.clone() {
+            Some(entrypoint) => typ_files.iter().position(|(path, _)| *path == entrypoint),
+            None => find_entry_point(&typ_files, entry_points),
+        }
+        .ok_or(TypstAssetError::Preloader(FilePreloaderError::NoMainDotTyp))?;
+        let (main_path, loaded_main) = typ_files.remove(main_index);
+        let main_id = FileId::new(None, VirtualPath::new(&main_path));
+        let source_resolver = typ_files
+            .into_iter()
+            .map(|(path, text)| Source::new(FileId::new(None, VirtualPath::new(path)), text))
+            .collect();
         Ok(StructuredInMemoryTemplate {
             loaded_toml,
-            loaded_fonts,
+            loaded_fonts: self.loaded_fonts,
+            lazy_fonts: self.lazy_fonts,
             path_given: PathBuf::from("/"),
-            file_resolver,
+            main_path: Some(main_path),
+            main_id: Some(main_id),
+            main_source: None,
+            file_resolver: self.file_resolver,
             source_resolver,
             loaded_main,
+            load_diagnostics: self.load_diagnostics,
+            file_accesses: FileAccessLog::default(),
+            file_providers: TypstFileProviders::default(),
+        })
+    }
+}
+
+pub(crate) fn check_entry_count(
+    count: usize,
+    limits: &TypstArchiveLimits,
+) -> Result<(), TypstAssetError> {
+    match limits.max_entries {
+        Some(limit) if count > limit => Err(TypstAssetError::LimitExceeded(
+            TypstArchiveLimitExceeded::Entries { count, limit },
+        )),
+        _ => Ok(()),
+    }
+}
+/// Read an archive entry, counting it towards `total_bytes` and failing if it goes over either size limit.
+pub(crate) fn read_limited(
+    file: &mut impl Read,
+    path: &Path,
+    limits: &TypstArchiveLimits,
+    total_bytes: &mut u64,
+) -> Result<Vec<u8>, TypstAssetError> {
+    let mut buf = Vec::new();
+    let entry_limit = limits.max_entry_bytes.unwrap_or(u64::MAX);
+    let total_limit = limits.max_total_bytes.unwrap_or(u64::MAX);
+    // Read one byte past the tighter limit so that going over it can be detected.
+    let read_limit = entry_limit.min(total_limit.saturating_sub(*total_bytes));
+    file.take(read_limit.saturating_add(1))
+        .read_to_end(&mut buf)
+        .map_err(TypstAssetError::Io)?;
+    count_limited(buf.len() as u64, path, limits, total_bytes)?;
+    Ok(buf)
+}
+
+/// Count a file of `len` bytes towards `total_bytes`, failing if it goes over either size limit.
+fn count_limited(
+    len: u64,
+    path: &Path,
+    limits: &TypstArchiveLimits,
+    total_bytes: &mut u64,
+) -> Result<(), TypstAssetError> {
+    let entry_limit = limits.max_entry_bytes.unwrap_or(u64::MAX);
+    let total_limit = limits.max_total_bytes.unwrap_or(u64::MAX);
+    if len > entry_limit {
+        return Err(TypstAssetError::LimitExceeded(
+            TypstArchiveLimitExceeded::EntryBytes {
+                entry: path.display().to_string(),
+                limit: entry_limit,
+            },
+        ));
+    }
+    *total_bytes += len;
+    if *total_bytes > total_limit {
+        return Err(TypstAssetError::LimitExceeded(
+            TypstArchiveLimitExceeded::TotalBytes { limit: total_limit },
+        ));
+    }
+    Ok(())
+}
+
+/// Decode a file that must be text, such as a manifest.
+fn manifest_text(path: &Path, buf: Vec<u8>) -> Result<String, TypstAssetError> {
+    decode_text(buf, false)
+        .map(|(text, _)| text)
+        .map_err(|reason| invalid_text(path, reason))
+}
+
+fn invalid_text(path: &Path, reason: String) -> TypstAssetError {
+    TypstAssetError::InvalidText {
+        entry: path.display().to_string(),
+        reason,
+    }
+}
+
+/// The folder of a template that files from [`BevyTypstDotToml::asset_requests`] are placed in.
+pub const ASSET_REQUESTS_ROOT: &str = "assets";
+
+/// Extensions of data files that typst reads as text, which are converted to UTF-8 like sources are.
+const TEXT_DATA_EXTENSIONS: &[&str] = &["json", "csv", "yaml", "yml", "toml", "txt", "xml"];
+
+/// Extensions of files typst reads as bytes: images, PDFs, bibliographies and citation styles, CBOR data, and
+/// wasm plugins. Files with any other extension are reported as a [`TemplateLoadDiagnostic::UnknownFile`].
+const BINARY_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "svg", "webp", "bmp", "pdf", "bib", "csl", "cbor", "wasm",
+];
+
+/// Why a file with the given extension likely doesn't work the way its author meant, if it doesn't.
+fn suspicious_extension(extension: &str) -> Option<&'static str> {
+    match extension.to_lowercase().as_str() {
+        "typ" => Some("isn't loaded as a typst source, as its extension isn't a lowercase '.typ'"),
+        "woff" | "woff2" => Some("isn't loaded as a font, typst doesn't support WOFF fonts"),
+        "zip" | "tar" | "gz" | "tgz" | "7z" | "rar" => {
+            Some("is an archive, which isn't extracted, so its files aren't part of the template")
+        }
+        _ => None,
+    }
+}
+
+/// The encoding a text file was decoded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypstTextEncoding {
+    Utf8,
+    /// UTF-8 with a byte order mark, as saved by some Windows editors. The mark is stripped.
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    /// The fallback for text that isn't valid UTF-8 and has no byte order mark.
+    Latin1,
+}
+
+impl TypstTextEncoding {
+    /// If the text was converted rather than just having a byte order mark stripped.
+    pub fn is_reencoded(&self) -> bool {
+        !matches!(self, TypstTextEncoding::Utf8 | TypstTextEncoding::Utf8Bom)
+    }
+}
+
+impl std::fmt::Display for TypstTextEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TypstTextEncoding::Utf8 => "UTF-8",
+            TypstTextEncoding::Utf8Bom => "UTF-8 with BOM",
+            TypstTextEncoding::Utf16Le => "UTF-16LE",
+            TypstTextEncoding::Utf16Be => "UTF-16BE",
+            TypstTextEncoding::Latin1 => "Latin-1",
         })
     }
 }
 
+/// Decode text, stripping any byte order mark and decoding UTF-16 when the mark calls for it. Text that isn't
+/// valid UTF-8 is decoded as Latin-1 when `fallback` is set, and is an error otherwise.
+pub(crate) fn decode_text(
+    bytes: Vec<u8>,
+    fallback: bool,
+) -> Result<(String, TypstTextEncoding), String> {
+    let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+        if !bytes.len().is_multiple_of(2) {
+            return Err("UTF-16 text has an odd number of bytes".to_owned());
+        }
+        let units = bytes
+            .chunks_exact(2)
+            .map(|unit| from_bytes([unit[0], unit[1]]));
+        char::decode_utf16(units)
+            .collect::<Result<String, _>>()
+            .map_err(|error| format!("invalid UTF-16: {error}"))
+    };
+    match bytes.as_slice() {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8(rest.to_vec())
+            .map(|text| (text, TypstTextEncoding::Utf8Bom))
+            .map_err(|error| format!("invalid UTF-8 after byte order mark: {error}")),
+        [0xFF, 0xFE, rest @ ..] => {
+            utf16(rest, u16::from_le_bytes).map(|text| (text, TypstTextEncoding::Utf16Le))
+        }
+        [0xFE, 0xFF, rest @ ..] => {
+            utf16(rest, u16::from_be_bytes).map(|text| (text, TypstTextEncoding::Utf16Be))
+        }
+        _ => match String::from_utf8(bytes) {
+            Ok(text) => Ok((text, TypstTextEncoding::Utf8)),
+            Err(error) if fallback => Ok((
+                error.into_bytes().into_iter().map(char::from).collect(),
+                TypstTextEncoding::Latin1,
+            )),
+            Err(error) => Err(format!("not UTF-8: {}", error.utf8_error())),
+        },
+    }
+}
+
+/// File names recognised as a template's main file when no entrypoint is declared, in order of preference.
+pub const DEFAULT_ENTRY_POINTS: &[&str] = &["main.typ", "lib.typ", "index.typ"];
+
+/// The index of the first of `entry_points` present in `typ_files`, preferring any of them at the root over
+/// one in a subfolder.
+fn find_entry_point(typ_files: &[(PathBuf, String)], entry_points: &[&str]) -> Option<usize> {
+    let find = |matches: &dyn Fn(&Path, &str) -> bool| {
+        entry_points
+            .iter()
+            .find_map(|name| typ_files.iter().position(|(path, _)| matches(path, name)))
+    };
+    find(&|path, name| path == Path::new(name))
+        .or_else(|| find(&|path, name| path.file_name().is_some_and(|file| file == name)))
+}
+
+/// The parts of a typst `typst.toml` package manifest this crate reads.
+#[derive(Debug, Clone, Deserialize, Default)]
+struct TypstManifest {
+    #[serde(default)]
+    package: Option<TypstManifestPackage>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+struct TypstManifestPackage {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    entrypoint: Option<PathBuf>,
+}
+
+impl TypstManifestPackage {
+    /// Fill in any fields `toml` doesn't set from this manifest, so `package.toml` takes priority when both exist.
+    fn fill_missing(self, toml: &mut BevyTypstDotToml) {
+        toml.name = toml.name.take().or(self.name);
+        toml.version = toml.version.take().or(self.version);
+        if toml.authors.is_empty() {
+            toml.authors = self.authors;
+        }
+        toml.entrypoint = toml.entrypoint.take().or(self.entrypoint);
+    }
+}
+
+/// Extensions of font files, including collections of several fonts.
+const FONT_EXTENSIONS: &[&str] = &["otf", "ttf", "otc", "ttc"];
+
+/// If a file is a font, as per [`FONT_EXTENSIONS`].
+fn is_font(path: &Path) -> bool {
+    path.extension()
+        .and_then(|os| os.to_str())
+        .is_some_and(|extension| FONT_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+/// Read every font of a font file, which may be a collection, noting fonts that can't be parsed and variable
+/// fonts, which typst only uses the default instance of.
+fn read_fonts(
+    entry: &Path,
+    buf: Vec<u8>,
+    fonts: &mut Vec<typst::text::Font>,
+    diagnostics: &mut TypstTemplateDiagnostics,
+) {
+    match share_fonts(buf) {
+        Ok(read) => {
+            for font in read {
+                if font.ttf().is_variable() {
+                    diagnostics.push(TemplateLoadDiagnostic::VariableFont {
+                        entry: entry.display().to_string(),
+                        family: font.info().family.clone(),
+                    });
+                }
+                fonts.push(font);
+            }
+        }
+        Err(bytes) => diagnostics.push(TemplateLoadDiagnostic::FontParseFailed {
+            entry: entry.display().to_string(),
+            reason: font_failure_reason(&bytes),
+        }),
+    }
+}
+
+/// Best-effort guess at why typst couldn't parse a font, based on its leading signature.
+fn font_failure_reason(buf: &[u8]) -> String {
+    match buf.get(0..4) {
+        None => "file is empty or truncated".to_owned(),
+        Some(b"wOFF") | Some(b"wOF2") => "WOFF/WOFF2 fonts are not supported".to_owned(),
+        Some([0, 1, 0, 0]) | Some(b"OTTO") | Some(b"true") | Some(b"ttcf") => {
+            "typst could not parse the font tables".to_owned()
+        }
+        Some(_) => "unrecognised font signature".to_owned(),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct BevyTypstDotToml {
     #[serde(default)]
     pub name: Option<String>,
     #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
     pub authors: Vec<String>,
+    /// Files to read from the game's assets when the template loads, by asset path, so they can be shared with
+    /// the rest of the game. Each is available to the template under [`ASSET_REQUESTS_ROOT`], i.e.
+    /// `"portraits/ada.png"` is read as `image("/assets/portraits/ada.png")`.
     #[serde(default)]
     pub asset_requests: BTreeMap<PathBuf, Option<FileTypeHint>>,
     #[serde(default)]
     pub package_requests: Vec<String>,
+    /// The template's main file, relative to its root. When not set, the first of [`DEFAULT_ENTRY_POINTS`] found is used.
+    #[serde(default)]
+    pub entrypoint: Option<PathBuf>,
+    /// Experimental typst compiler features to enable, using typst's CLI names (`"html"`, `"a11y-extras"`).
+    #[serde(default)]
+    pub compiler_features: Vec<String>,
+    /// The inputs this template expects, checked before each job is compiled.
+    #[serde(default)]
+    pub inputs: TypstInputSchema,
+    /// Other templates this one uses, such as shared libraries of typst functions, by the folder each is mounted
+    /// under and its asset path. They're loaded along with this template, so `ui = "libs/ui.zip"` makes that
+    /// template's files available under `/ui/`, as in `#import "/ui/lib.typ": button`. Files this template has
+    /// itself take priority.
+    #[serde(default)]
+    pub dependencies: BTreeMap<PathBuf, PathBuf>,
+}
+
+/// The fields of `package.toml`, for reporting unknown ones.
+const MANIFEST_FIELDS: &[&str] = &[
+    "name",
+    "version",
+    "authors",
+    "asset_requests",
+    "package_requests",
+    "entrypoint",
+    "compiler_features",
+    "inputs",
+    "dependencies",
+];
+
+impl BevyTypstDotToml {
+    /// Parse a `package.toml`, ignoring fields with the wrong shape rather than failing, and reporting them and
+    /// any unknown fields as diagnostics. Fails only if the file isn't valid TOML.
+    pub fn from_str_lenient(
+        source: &str,
+    ) -> Result<(Self, Vec<TemplateLoadDiagnostic>), FilePreloaderError> {
+        let mut table: toml::Table =
+            toml::from_str(source).map_err(|_| FilePreloaderError::MalformedPackageToml)?;
+        let mut diagnostics = vec![];
+        for field in table.keys().cloned().collect::<Vec<_>>() {
+            if !MANIFEST_FIELDS.contains(&field.as_str()) {
+                diagnostics.push(TemplateLoadDiagnostic::UnknownManifestField { field });
+                continue;
+            }
+            let mut alone = toml::Table::new();
+            alone.insert(field.clone(), table[&field].clone());
+            if let Err(error) = toml::Value::Table(alone).try_into::<BevyTypstDotToml>() {
+                table.remove(&field);
+                diagnostics.push(TemplateLoadDiagnostic::MalformedManifestField {
+                    field,
+                    reason: error.message().to_owned(),
+                });
+            }
+        }
+        let toml = toml::Value::Table(table)
+            .try_into()
+            .map_err(|_| FilePreloaderError::MalformedPackageToml)?;
+        Ok((toml, diagnostics))
+    }
+}
+
+/// Parse typst compiler features from their CLI names, warning about and skipping unknown ones.
+fn compiler_features(names: &[String]) -> Features {
+    names
+        .iter()
+        .filter_map(|name| match name.as_str() {
+            "html" => Some(Feature::Html),
+            "a11y-extras" => Some(Feature::A11yExtras),
+            unknown => {
+                typst_log!(
+                    Template,
+                    Warn,
+                    "[TYPST WARNING] Unknown compiler feature '{unknown}'"
+                );
+                None
+            }
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -166,3 +1417,162 @@ pub enum FileTypeHint {
     Font,
     Typst,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io::{Cursor, Write},
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+    use super::{check_entry_count, open_zip_entry, read_limited};
+    use crate::{
+        asset_loading::{TypstArchiveLimitExceeded, TypstArchiveLimits, TypstAssetError},
+        file_provider::TypstFileProvider,
+        lazy_archive::LazyZipMembers,
+    };
+
+    fn limits(
+        entries: Option<usize>,
+        entry_bytes: Option<u64>,
+        total: Option<u64>,
+    ) -> TypstArchiveLimits {
+        TypstArchiveLimits {
+            max_entries: entries,
+            max_entry_bytes: entry_bytes,
+            max_total_bytes: total,
+        }
+    }
+
+    /// A zip archive of one deflated file of `len` bytes, whose headers declare it to be `declared` bytes.
+    fn lying_zip(len: usize, declared: u32) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("image.png", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&vec![7; len]).unwrap();
+        let mut bytes = writer.finish().unwrap().into_inner();
+        let declared = declared.to_le_bytes();
+        // The uncompressed size of the local file header, then of the central directory's header.
+        bytes[22..26].copy_from_slice(&declared);
+        let central = bytes
+            .windows(4)
+            .position(|window| window == [0x50, 0x4b, 0x01, 0x02])
+            .unwrap();
+        bytes[central + 24..central + 28].copy_from_slice(&declared);
+        bytes
+    }
+
+    #[test]
+    fn entry_over_limit() {
+        let result = read_limited(
+            &mut Cursor::new(vec![0; 101]),
+            Path::new("big.png"),
+            &limits(None, Some(100), None),
+            &mut 0,
+        );
+        assert!(matches!(
+            result,
+            Err(TypstAssetError::LimitExceeded(TypstArchiveLimitExceeded::EntryBytes { entry, limit: 100 }))
+                if entry == "big.png"
+        ));
+    }
+
+    #[test]
+    fn entry_at_limit() {
+        let mut total = 0;
+        let buf = read_limited(
+            &mut Cursor::new(vec![0; 100]),
+            Path::new("big.png"),
+            &limits(None, Some(100), Some(100)),
+            &mut total,
+        )
+        .unwrap();
+        assert_eq!(buf.len(), 100);
+        assert_eq!(total, 100);
+    }
+
+    #[test]
+    fn total_over_limit() {
+        let limits = limits(None, None, Some(150));
+        let mut total = 0;
+        read_limited(
+            &mut Cursor::new(vec![0; 100]),
+            Path::new("a.png"),
+            &limits,
+            &mut total,
+        )
+        .unwrap();
+        let result = read_limited(
+            &mut Cursor::new(vec![0; 100]),
+            Path::new("b.png"),
+            &limits,
+            &mut total,
+        );
+        assert!(matches!(
+            result,
+            Err(TypstAssetError::LimitExceeded(
+                TypstArchiveLimitExceeded::TotalBytes { limit: 150 }
+            ))
+        ));
+    }
+
+    #[test]
+    fn entry_count() {
+        let limits = limits(Some(3), None, None);
+        assert!(check_entry_count(3, &limits).is_ok());
+        assert!(matches!(
+            check_entry_count(4, &limits),
+            Err(TypstAssetError::LimitExceeded(
+                TypstArchiveLimitExceeded::Entries { count: 4, limit: 3 }
+            ))
+        ));
+        assert!(check_entry_count(usize::MAX, &TypstArchiveLimits::unlimited()).is_ok());
+    }
+
+    #[test]
+    fn lying_header_is_limited_by_what_it_decompresses_to() {
+        let mut zip = ZipArchive::new(Cursor::new(lying_zip(1000, 10))).unwrap();
+        let mut file = open_zip_entry(&mut zip, 0, None).unwrap();
+        assert_eq!(file.size(), 10);
+        let result = read_limited(
+            &mut file,
+            Path::new("image.png"),
+            &limits(None, Some(100), None),
+            &mut 0,
+        );
+        assert!(matches!(
+            result,
+            Err(TypstAssetError::LimitExceeded(
+                TypstArchiveLimitExceeded::EntryBytes { limit: 100, .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn lazy_member_counts_what_it_decompresses_to() {
+        let bytes: Arc<[u8]> = lying_zip(1000, 10).into();
+        let members = HashMap::from([(PathBuf::from("image.png"), 0)]);
+        // Within the limits by the size the archive declares, but not by what was read while loading plus
+        // what the member really decompresses to.
+        let lazy = LazyZipMembers::new(
+            bytes.clone(),
+            members.clone(),
+            &limits(None, None, Some(1500)),
+            None,
+            600,
+        )
+        .unwrap();
+        assert!(lazy.file(Path::new("image.png")).is_none());
+        assert_eq!(lazy.decompressed_count(), (0, 1));
+
+        let lazy = LazyZipMembers::new(bytes, members, &limits(None, None, Some(1500)), None, 500)
+            .unwrap();
+        assert_eq!(lazy.file(Path::new("image.png")).unwrap().len(), 1000);
+        assert_eq!(lazy.decompressed_count(), (1, 1));
+    }
+}
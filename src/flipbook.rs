@@ -0,0 +1,221 @@
+use std::path::PathBuf;
+
+use bevy_asset::Assets;
+use bevy_ecs::{
+    change_detection::DetectChangesMut,
+    component::Component,
+    query::Changed,
+    system::{Query, Res, ResMut},
+};
+use bevy_image::Image;
+use bevy_time::Time;
+use typst::foundations::Dict;
+
+use crate::{
+    SharedCompile, TypstJobOptions, TypstRenderInfo, TypstRenderTarget, TypstTextureServer,
+};
+
+/// Plays each page of a typst document as a frame of an animation, on the entity's [`bevy_sprite::Sprite`] (with the
+/// `sprite` feature) or the base colour texture of its [`bevy_pbr::StandardMaterial`] (with the `pbr` feature).
+/// The document is compiled once when this component is added or changed and each of its pages is rasterized
+/// from that compile, with playback starting once all of them have loaded.
+///
+/// `options.specific_page` and `options.page_label` are ignored.
+#[derive(Debug, Clone, Component)]
+#[require(TypstFlipbookFrames)]
+pub struct TypstFlipbook {
+    pub template: PathBuf,
+    pub input: Dict,
+    pub options: TypstJobOptions,
+    /// Pages shown per second.
+    pub fps: f32,
+    /// Start from the first page again after the last, rather than stopping on it.
+    pub looping: bool,
+}
+
+impl TypstFlipbook {
+    pub fn new(template: impl Into<PathBuf>, fps: f32) -> Self {
+        Self {
+            template: template.into(),
+            input: Dict::new(),
+            options: TypstJobOptions::default(),
+            fps,
+            looping: true,
+        }
+    }
+
+    pub fn with_input(mut self, input: impl Into<Dict>) -> Self {
+        self.input = input.into();
+        self
+    }
+
+    pub fn with_options(mut self, options: TypstJobOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+}
+
+/// The rendered pages and playback state of a [`TypstFlipbook`], managed by this crate.
+#[derive(Debug, Clone, Default, Component)]
+pub struct TypstFlipbookFrames {
+    /// A render of each page, in order. Only the first page is queued until the page count is known.
+    pub frames: Vec<TypstRenderTarget>,
+    /// The page count of the document, once the first page has been rendered.
+    pub page_count: Option<usize>,
+    /// The index of the frame being shown.
+    pub current: Option<usize>,
+    /// Seconds of playback so far. Advancing this doesn't mark the component as changed, only moving to another
+    /// frame does.
+    pub elapsed: f32,
+    /// The document compiled for the first page, which the rest are rasterized from.
+    compile: SharedCompile,
+}
+
+impl TypstFlipbookFrames {
+    /// If every page has been rendered and loaded, so playback can start.
+    pub fn is_ready(&self, images: &Assets<Image>) -> bool {
+        self.page_count == Some(self.frames.len())
+            && self
+                .frames
+                .iter()
+                .all(|frame| images.contains(&frame.image))
+    }
+
+    /// If a non-looping flipbook has reached its last page.
+    pub fn is_finished(&self, flipbook: &TypstFlipbook) -> bool {
+        !flipbook.looping
+            && self
+                .page_count
+                .is_some_and(|count| self.current == Some(count - 1))
+    }
+
+    fn submit_page(
+        &mut self,
+        template_server: &mut TypstTextureServer,
+        flipbook: &TypstFlipbook,
+        page: usize,
+    ) {
+        let mut options = flipbook.options.clone();
+        options.specific_page = Some(page);
+        options.page_label = None;
+        let template = template_server.resolve_template(flipbook.template.clone().into());
+        self.frames.push(template_server.submit_shared(
+            template,
+            flipbook.input.clone(),
+            options,
+            Some(self.compile.clone()),
+        ));
+    }
+}
+
+pub(crate) fn system_queue_typst_flipbooks(
+    mut template_server: ResMut<TypstTextureServer>,
+    mut flipbooks: Query<(&TypstFlipbook, &mut TypstFlipbookFrames), Changed<TypstFlipbook>>,
+) {
+    for (flipbook, mut frames) in &mut flipbooks {
+        for frame in frames.frames.drain(..) {
+            template_server.cancel(frame.job);
+        }
+        *frames = TypstFlipbookFrames::default();
+        frames.submit_page(&mut template_server, flipbook, 0);
+    }
+}
+
+pub(crate) fn system_queue_typst_flipbook_pages(
+    mut template_server: ResMut<TypstTextureServer>,
+    infos: Res<Assets<TypstRenderInfo>>,
+    mut flipbooks: Query<(&TypstFlipbook, &mut TypstFlipbookFrames)>,
+) {
+    for (flipbook, mut frames) in &mut flipbooks {
+        if frames.page_count.is_some() {
+            continue;
+        }
+        let Some(info) = frames
+            .frames
+            .first()
+            .and_then(|frame| infos.get(&frame.info))
+        else {
+            continue;
+        };
+        let page_count = info.page_count.max(1);
+        frames.page_count = Some(page_count);
+        for page in 1..page_count {
+            frames.submit_page(&mut template_server, flipbook, page);
+        }
+    }
+}
+
+pub(crate) fn system_play_typst_flipbooks(
+    time: Option<Res<Time>>,
+    images: Res<Assets<Image>>,
+    mut flipbooks: Query<(&TypstFlipbook, &mut TypstFlipbookFrames)>,
+) {
+    let delta = time.map_or(0.0, |time| time.delta_secs());
+    for (flipbook, mut frames) in &mut flipbooks {
+        if !frames.is_ready(&images) || frames.is_finished(flipbook) {
+            continue;
+        }
+        let frame_count = frames.frames.len();
+        if frames.current.is_some() {
+            frames.bypass_change_detection().elapsed += delta;
+        }
+        let frame = (frames.elapsed * flipbook.fps.max(0.0)) as usize;
+        let frame = if flipbook.looping {
+            frame % frame_count
+        } else {
+            frame.min(frame_count - 1)
+        };
+        if frames.current != Some(frame) {
+            frames.current = Some(frame);
+        }
+    }
+}
+
+#[cfg(feature = "sprite")]
+pub(crate) fn system_show_typst_flipbook_sprites(
+    mut flipbooks: Query<
+        (&TypstFlipbookFrames, &mut bevy_sprite::Sprite),
+        Changed<TypstFlipbookFrames>,
+    >,
+) {
+    for (frames, mut sprite) in &mut flipbooks {
+        if let Some(frame) = frames
+            .current
+            .and_then(|current| frames.frames.get(current))
+            && sprite.image != frame.image
+        {
+            sprite.image = frame.image.clone();
+        }
+    }
+}
+
+#[cfg(feature = "pbr")]
+pub(crate) fn system_show_typst_flipbook_materials(
+    mut materials: ResMut<Assets<bevy_pbr::StandardMaterial>>,
+    flipbooks: Query<
+        (
+            &TypstFlipbookFrames,
+            &bevy_pbr::MeshMaterial3d<bevy_pbr::StandardMaterial>,
+        ),
+        Changed<TypstFlipbookFrames>,
+    >,
+) {
+    for (frames, material) in &flipbooks {
+        let Some(frame) = frames
+            .current
+            .and_then(|current| frames.frames.get(current))
+        else {
+            continue;
+        };
+        if let Some(material) = materials.get_mut(&material.0)
+            && material.base_color_texture.as_ref() != Some(&frame.image)
+        {
+            material.base_color_texture = Some(frame.image.clone());
+        }
+    }
+}
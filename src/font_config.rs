@@ -0,0 +1,223 @@
+use bevy_ecs::resource::Resource;
+use typst::{
+    foundations::Regex,
+    text::{Covers, Font, FontFamily, FontList},
+};
+
+/// Where the fonts given to typst come from, see [`TypstFontConfig::order`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypstFontSource {
+    /// Fonts embedded in the template itself.
+    Template,
+    /// Fonts of the [`crate::font_library::TypstFontLibrary`].
+    Library,
+    /// typst's own default fonts, with the `typst-asset-fonts` feature.
+    Asset,
+    /// Fonts installed on the system, with the `typst-search-system-fonts` feature.
+    System,
+}
+
+/// A script to prefer fallback fonts for, see [`TypstFontConfig::script_fallbacks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypstScript {
+    /// Chinese characters, also used in Japanese and Korean text.
+    Han,
+    /// Japanese hiragana and katakana.
+    Kana,
+    Hangul,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Thai,
+    Cyrillic,
+    Greek,
+    /// Emoji, for which fonts with color glyphs are preferred unless [`TypstFontConfig::prefer_color_emoji`] is off.
+    Emoji,
+}
+
+impl TypstScript {
+    /// A character of the script, i.e. for checking if a font has the script's glyphs.
+    pub fn sample(&self) -> char {
+        match self {
+            TypstScript::Han => '漢',
+            TypstScript::Kana => 'か',
+            TypstScript::Hangul => '한',
+            TypstScript::Arabic => 'ع',
+            TypstScript::Hebrew => 'א',
+            TypstScript::Devanagari => 'क',
+            TypstScript::Thai => 'ก',
+            TypstScript::Cyrillic => 'Ж',
+            TypstScript::Greek => 'Ω',
+            TypstScript::Emoji => '😀',
+        }
+    }
+
+    /// A regex matching the characters of the script, for the coverage of its fallback families.
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            TypstScript::Han => r"\p{Han}",
+            TypstScript::Kana => r"[\p{Hiragana}\p{Katakana}]",
+            TypstScript::Hangul => r"\p{Hangul}",
+            TypstScript::Arabic => r"\p{Arabic}",
+            TypstScript::Hebrew => r"\p{Hebrew}",
+            TypstScript::Devanagari => r"\p{Devanagari}",
+            TypstScript::Thai => r"\p{Thai}",
+            TypstScript::Cyrillic => r"\p{Cyrillic}",
+            TypstScript::Greek => r"\p{Greek}",
+            // Along with the joiners, selectors and modifiers of emoji sequences, so they aren't split up.
+            TypstScript::Emoji => {
+                r"[\p{Extended_Pictographic}\p{Emoji_Modifier}\p{Regional_Indicator}\u{200D}\u{FE0F}\u{20E3}\u{E0020}-\u{E007F}]"
+            }
+        }
+    }
+
+    /// A font family that's only used for the characters of this script.
+    fn family(&self, name: &str) -> FontFamily {
+        let covers = Regex::new(self.pattern()).ok().map(Covers::Regex);
+        FontFamily::with_coverage(name, covers)
+    }
+}
+
+/// Which fonts are given to typst, and in what order, for every job. Set with
+/// [`crate::TypstTexturesPlugin::font_config`] or by changing the resource, applying to jobs compiled after.
+///
+/// Typst uses the first font of a family and style it's given, and when a font lacks a glyph, falls back to the
+/// font most like it that has one. The order of the fonts only breaks ties between fonts that are otherwise
+/// equally alike, so fonts that should be used for some characters are given to typst as families instead,
+/// see [`TypstFontConfig::script_fallbacks`].
+#[derive(Debug, Clone, Resource)]
+pub struct TypstFontConfig {
+    /// The sources fonts are taken from, in order, with sources left out not used at all. `Asset` and `System`
+    /// fonts are searched for by typst-as-lib after those given from memory, so always come after `Template`
+    /// and `Library` fonts, and need their cargo features. Defaults to `[Template, Library, Asset, System]`.
+    pub order: Vec<TypstFontSource>,
+    /// Font families to set each script's characters in, in order, i.e. `(TypstScript::Han, vec!["Noto Sans
+    /// CJK SC".into()])`. These are put ahead of typst's default font in every document's font list, i.e.
+    /// `text(font: ..)`, each covering only its script's characters, so they're used over the default font for
+    /// them. Templates that set their own `text(font: ..)` replace the list, and have to list the families
+    /// they want themselves.
+    pub script_fallbacks: Vec<(TypstScript, Vec<String>)>,
+    /// Set emoji in the template's and library's fonts with color glyphs, from `COLR`, `CBDT`, `sbix` or `SVG`
+    /// tables, so they aren't drawn in monochrome by text fonts that happen to have a few. Their families are put
+    /// in the default font list after the [`TypstScript::Emoji`] fallbacks, covering only emoji, the same as
+    /// [`TypstFontConfig::script_fallbacks`]. Defaults to `true`.
+    pub prefer_color_emoji: bool,
+    /// Font families, compared case-insensitively, that are never given to typst from the template or library,
+    /// for fonts that are broken or unlicensed for a release.
+    pub deny: Vec<String>,
+}
+
+impl Default for TypstFontConfig {
+    fn default() -> Self {
+        Self {
+            order: vec![
+                TypstFontSource::Template,
+                TypstFontSource::Library,
+                TypstFontSource::Asset,
+                TypstFontSource::System,
+            ],
+            script_fallbacks: vec![],
+            prefer_color_emoji: true,
+            deny: vec![],
+        }
+    }
+}
+
+impl TypstFontConfig {
+    pub fn uses(&self, source: TypstFontSource) -> bool {
+        self.order.contains(&source)
+    }
+
+    /// If system fonts are searched for, i.e. [`TypstFontSource::System`] is in the order.
+    #[cfg(feature = "typst-search-system-fonts")]
+    pub fn system_fonts(&self) -> bool {
+        self.uses(TypstFontSource::System)
+    }
+
+    /// Turn searching for system fonts on or off, such as from a "use system fonts" option in a game's
+    /// settings. Engines are built anew for each frame's jobs, so jobs compiled after the change use it.
+    #[cfg(feature = "typst-search-system-fonts")]
+    pub fn set_system_fonts(&mut self, enabled: bool) {
+        self.order
+            .retain(|source| *source != TypstFontSource::System);
+        if enabled {
+            self.order.push(TypstFontSource::System);
+        }
+    }
+
+    /// Draw emoji with a family ahead of the default font, such as a color emoji font bundled with the game and
+    /// added to the [`crate::font_library::TypstFontLibrary`], so chat messages and other player text can use
+    /// them. As with other [`TypstFontConfig::script_fallbacks`], templates that set their own `text(font: ..)`
+    /// have to list the family themselves.
+    pub fn with_emoji_font(mut self, family: impl Into<String>) -> Self {
+        match self
+            .script_fallbacks
+            .iter_mut()
+            .find(|(script, _)| *script == TypstScript::Emoji)
+        {
+            Some((_, families)) => families.push(family.into()),
+            None => self
+                .script_fallbacks
+                .push((TypstScript::Emoji, vec![family.into()])),
+        }
+        self
+    }
+
+    /// If fonts of a family are denied.
+    pub fn denies(&self, family: &str) -> bool {
+        self.deny
+            .iter()
+            .any(|denied| denied.eq_ignore_ascii_case(family))
+    }
+
+    /// The fonts given to typst from a template's fonts and the library's, per this config.
+    pub(crate) fn arrange(&self, mut template: Vec<Font>, library: &[Font]) -> Vec<Font> {
+        let mut fonts = Vec::with_capacity(template.len() + library.len());
+        for source in &self.order {
+            match source {
+                TypstFontSource::Template => fonts.append(&mut template),
+                TypstFontSource::Library => fonts.extend(library.iter().cloned()),
+                TypstFontSource::Asset | TypstFontSource::System => {}
+            }
+        }
+        fonts.retain(|font| !self.denies(&font.info().family));
+        fonts
+    }
+
+    /// The font list documents are compiled with by default, with the script fallbacks and then the families of
+    /// color emoji fonts among `fonts` ahead of typst's default font, or `None` if there aren't any and typst's
+    /// default is kept as is.
+    pub(crate) fn font_list(&self, fonts: &[Font]) -> Option<FontList> {
+        let mut families: Vec<FontFamily> = self
+            .script_fallbacks
+            .iter()
+            .flat_map(|(script, families)| families.iter().map(|name| script.family(name)))
+            .collect();
+        if self.prefer_color_emoji {
+            let sample = TypstScript::Emoji.sample() as u32;
+            for font in fonts {
+                let family = TypstScript::Emoji.family(&font.info().family);
+                if is_color_font(font)
+                    && font.info().coverage.contains(sample)
+                    && !families.contains(&family)
+                {
+                    families.push(family);
+                }
+            }
+        }
+        if families.is_empty() {
+            return None;
+        }
+        families.push(FontFamily::new(DEFAULT_FAMILY));
+        Some(FontList(families))
+    }
+}
+
+/// The font typst sets text in when a document doesn't set one.
+const DEFAULT_FAMILY: &str = "libertinus serif";
+
+/// If a font has color glyphs, such as those of emoji fonts.
+pub fn is_color_font(font: &Font) -> bool {
+    let tables = font.ttf().tables();
+    tables.colr.is_some() || tables.cbdt.is_some() || tables.sbix.is_some() || tables.svg.is_some()
+}
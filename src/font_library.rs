@@ -0,0 +1,195 @@
+use std::{
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use bevy_ecs::{change_detection::DetectChanges, resource::Resource, system::Res};
+use typst::text::{Font, FontBook, FontVariant};
+
+use crate::{
+    engine::TypstTemplateEngine,
+    file_resolver::{BevyTypstDotToml, StructuredInMemoryTemplate},
+    font_config::TypstFontConfig,
+    logging::typst_log,
+    shared_files::share_fonts,
+};
+
+/// Fonts available to every template, on top of those each embeds, so that fonts used throughout a game don't
+/// have to be zipped into every template. Templates' own fonts take priority over these for the same family
+/// and style, unless the [`crate::font_config::TypstFontConfig`] orders them otherwise. Changes apply to jobs
+/// compiled after them.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct TypstFontLibrary {
+    fonts: Vec<Font>,
+    book: FontBook,
+}
+
+impl TypstFontLibrary {
+    /// Add every font of a font file's contents, which may be a collection such as a `.ttc`, returning how
+    /// many were added.
+    pub fn add_bytes(&mut self, bytes: impl Into<Vec<u8>>) -> Result<usize, TypstFontError> {
+        let fonts = share_fonts(bytes.into()).map_err(|_| TypstFontError::Unparseable)?;
+        let count = fonts.len();
+        fonts.into_iter().for_each(|font| self.add_font(font));
+        Ok(count)
+    }
+
+    /// Add every font of a font file on disk, returning how many were added. Reads the file immediately.
+    pub fn add_file(&mut self, path: impl AsRef<Path>) -> Result<usize, TypstFontError> {
+        let bytes = std::fs::read(path).map_err(TypstFontError::Io)?;
+        self.add_bytes(bytes)
+    }
+
+    /// Add an already parsed font.
+    pub fn add_font(&mut self, font: Font) {
+        self.book.push(font.info().clone());
+        self.fonts.push(font);
+    }
+
+    /// The font typst would pick from the library for a family and variant. Families are compared
+    /// case-insensitively.
+    pub fn font(&self, family: &str, variant: FontVariant) -> Option<Font> {
+        let index = self.book.select(&family.to_lowercase(), variant)?;
+        self.fonts.get(index).cloned()
+    }
+
+    pub fn fonts(&self) -> &[Font] {
+        &self.fonts
+    }
+
+    /// The families of the fonts in the library, without duplicates.
+    pub fn families(&self) -> Vec<&str> {
+        let mut families: Vec<&str> = self
+            .fonts
+            .iter()
+            .map(|font| font.info().family.as_str())
+            .collect();
+        families.sort_unstable();
+        families.dedup();
+        families
+    }
+
+    pub fn clear(&mut self) {
+        self.fonts.clear();
+        self.book = FontBook::new();
+    }
+}
+
+/// A font given to a single job, see [`crate::TypstJobOptions::fonts`].
+#[derive(Debug, Clone, Hash)]
+pub enum TypstJobFont {
+    /// The contents of a font file, which may be a collection, such as one a player picked for their banner.
+    Bytes(Vec<u8>),
+    /// Every font of a family in the [`TypstFontLibrary`], compared case-insensitively, for fonts too rarely used
+    /// to give every template.
+    Library(String),
+}
+
+/// The [`TypstFontConfig`] and [`TypstFontLibrary`] fonts as of the start of the frame, shared between the
+/// [`crate::TypstTextureServer`] and the static document loader for compiling templates outside of the job queue,
+/// such as for [`crate::TypstTextureServer::compile_scene`].
+#[derive(Clone, Default, Resource)]
+pub struct TypstEngineFonts(Arc<RwLock<(TypstFontConfig, Vec<Font>)>>);
+
+impl TypstEngineFonts {
+    /// Build an engine for `template` with these fonts, as per [`StructuredInMemoryTemplate::to_engine_with_fonts`].
+    pub fn to_engine(
+        &self,
+        template: StructuredInMemoryTemplate,
+    ) -> (TypstTemplateEngine, BevyTypstDotToml) {
+        let fonts = self.0.read().unwrap();
+        template.to_engine_with_fonts(&fonts.0, &fonts.1)
+    }
+}
+
+impl std::fmt::Debug for TypstEngineFonts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "TypstEngineFonts({} library fonts)",
+            self.0.read().unwrap().1.len()
+        )
+    }
+}
+
+/// Runs in `First`, copying changes to the font config and library into the [`TypstEngineFonts`].
+pub(crate) fn system_share_engine_fonts(
+    engine_fonts: Res<TypstEngineFonts>,
+    font_config: Option<Res<TypstFontConfig>>,
+    font_library: Option<Res<TypstFontLibrary>>,
+) {
+    let config_changed = font_config.as_ref().is_some_and(DetectChanges::is_changed);
+    let library_changed = font_library.as_ref().is_some_and(DetectChanges::is_changed);
+    if !config_changed && !library_changed {
+        return;
+    }
+    let mut fonts = engine_fonts.0.write().unwrap();
+    *fonts = (
+        font_config.as_deref().cloned().unwrap_or_default(),
+        font_library
+            .as_ref()
+            .map_or_else(Vec::new, |library| library.fonts().to_vec()),
+    );
+}
+
+/// The fonts of a job's [`TypstJobFont`]s, logging any that can't be parsed or found.
+pub(crate) fn job_fonts(fonts: &[TypstJobFont], library: &[Font]) -> Vec<Font> {
+    let mut resolved = vec![];
+    for font in fonts {
+        match font {
+            TypstJobFont::Bytes(bytes) => match share_fonts(bytes.clone()) {
+                Ok(fonts) => resolved.extend(fonts),
+                Err(_) => typst_log!(
+                    Job,
+                    Warn,
+                    "[TYPST FONT ERROR] A job's font couldn't be parsed, skipping it"
+                ),
+            },
+            TypstJobFont::Library(family) => {
+                let before = resolved.len();
+                resolved.extend(
+                    library
+                        .iter()
+                        .filter(|font| font.info().family.eq_ignore_ascii_case(family))
+                        .cloned(),
+                );
+                if resolved.len() == before {
+                    typst_log!(
+                        Job,
+                        Warn,
+                        "[TYPST FONT ERROR] A job asked for the font family '{family}', which isn't in the library"
+                    );
+                }
+            }
+        }
+    }
+    resolved
+}
+
+impl StructuredInMemoryTemplate {
+    /// This template with more fonts available to it, after its own.
+    pub fn with_fonts(mut self, fonts: impl IntoIterator<Item = Font>) -> Self {
+        self.loaded_fonts.extend(fonts);
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum TypstFontError {
+    Io(std::io::Error),
+    /// Typst couldn't parse any fonts from the file.
+    Unparseable,
+}
+
+impl std::fmt::Display for TypstFontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypstFontError::Io(error) => write!(f, "TypstFontError::Io: {error}"),
+            TypstFontError::Unparseable => {
+                write!(f, "TypstFontError::Unparseable: No fonts could be parsed")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TypstFontError {}
@@ -0,0 +1,224 @@
+use std::collections::BTreeSet;
+
+use ttf_parser::{GlyphId, RawFace, Tag, gsub::SubstitutionSubtable};
+
+/// Cut a font down to the glyphs of the given characters, along with any glyphs its substitutions reach from
+/// them, such as ligatures and alternates, so that typst can still shape text with it. The outlines of every
+/// other glyph are emptied while glyph ids are kept as they are, so every other table stays valid. `None` if
+/// it can't be subset (such as fonts with CFF outlines), in which case the font should be kept whole.
+pub(crate) fn subset(data: &[u8], index: u32, chars: &BTreeSet<char>) -> Option<Vec<u8>> {
+    let raw = RawFace::parse(data, index).ok()?;
+    let face = ttf_parser::Face::parse(data, index).ok()?;
+    let head = raw.table(Tag::from_bytes(b"head"))?;
+    let loca = raw.table(Tag::from_bytes(b"loca"))?;
+    let glyf = raw.table(Tag::from_bytes(b"glyf"))?;
+    let long_loca = read_u16(head, 50)? == 1;
+    let num_glyphs = face.number_of_glyphs();
+    let offset = |glyph: u16| -> Option<usize> {
+        match long_loca {
+            true => read_u32(loca, glyph as usize * 4).map(|offset| offset as usize),
+            false => read_u16(loca, glyph as usize * 2).map(|offset| offset as usize * 2),
+        }
+    };
+    let outline = |glyph: u16| -> Option<&[u8]> { glyf.get(offset(glyph)?..offset(glyph + 1)?) };
+
+    let mut keep = BTreeSet::from([GlyphId(0)]);
+    keep.extend(chars.iter().filter_map(|c| face.glyph_index(*c)));
+    close_over_substitutions(&face, &mut keep);
+    // Composite glyphs are drawn from other glyphs, which have to be kept too.
+    let mut pending: Vec<GlyphId> = keep.iter().copied().collect();
+    while let Some(glyph) = pending.pop() {
+        let Some(outline) = outline(glyph.0) else {
+            continue;
+        };
+        for component in components(outline) {
+            if component.0 < num_glyphs && keep.insert(component) {
+                pending.push(component);
+            }
+        }
+    }
+
+    let mut new_glyf = vec![];
+    let mut new_loca = Vec::with_capacity((num_glyphs as usize + 1) * 4);
+    for glyph in 0..num_glyphs {
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+        if keep.contains(&GlyphId(glyph)) {
+            new_glyf.extend_from_slice(outline(glyph)?);
+            new_glyf.resize(new_glyf.len().next_multiple_of(4), 0);
+        }
+    }
+    new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+    let mut new_head = head.to_vec();
+    // Offsets are always written long, and the checksum adjustment is recalculated once the font is written.
+    new_head
+        .get_mut(50..52)?
+        .copy_from_slice(&1u16.to_be_bytes());
+    new_head.get_mut(8..12)?.copy_from_slice(&[0; 4]);
+
+    let tables: Vec<(Tag, &[u8])> = raw
+        .table_records
+        .into_iter()
+        .map(|record| match &record.tag.to_bytes() {
+            b"glyf" => Some((record.tag, new_glyf.as_slice())),
+            b"loca" => Some((record.tag, new_loca.as_slice())),
+            b"head" => Some((record.tag, new_head.as_slice())),
+            _ => Some((record.tag, raw.table(record.tag)?)),
+        })
+        .collect::<Option<_>>()?;
+    Some(write_font(
+        read_u32(data, sfnt_offset(data, index)?)?,
+        &tables,
+    ))
+}
+
+/// Add every glyph that a substitution in the font's `GSUB` table can produce from the kept glyphs, until
+/// there are no more to add.
+fn close_over_substitutions(face: &ttf_parser::Face, keep: &mut BTreeSet<GlyphId>) {
+    let Some(gsub) = face.tables().gsub else {
+        return;
+    };
+    loop {
+        let mut found = vec![];
+        for lookup in gsub.lookups {
+            for subtable in lookup.subtables.into_iter::<SubstitutionSubtable>() {
+                for glyph in keep.iter().copied() {
+                    let Some(index) = subtable.coverage().get(glyph) else {
+                        continue;
+                    };
+                    match subtable {
+                        SubstitutionSubtable::Single(single) => match single {
+                            ttf_parser::gsub::SingleSubstitution::Format1 { delta, .. } => {
+                                found.push(GlyphId(glyph.0.wrapping_add(delta as u16)));
+                            }
+                            ttf_parser::gsub::SingleSubstitution::Format2 {
+                                substitutes, ..
+                            } => found.extend(substitutes.get(index)),
+                        },
+                        SubstitutionSubtable::Multiple(multiple) => {
+                            if let Some(sequence) = multiple.sequences.get(index) {
+                                found.extend(sequence.substitutes);
+                            }
+                        }
+                        SubstitutionSubtable::Alternate(alternate) => {
+                            if let Some(set) = alternate.alternate_sets.get(index) {
+                                found.extend(set.alternates);
+                            }
+                        }
+                        SubstitutionSubtable::Ligature(ligature) => {
+                            if let Some(set) = ligature.ligature_sets.get(index) {
+                                found.extend(
+                                    set.into_iter()
+                                        .filter(|ligature| {
+                                            ligature
+                                                .components
+                                                .into_iter()
+                                                .all(|component| keep.contains(&component))
+                                        })
+                                        .map(|ligature| ligature.glyph),
+                                );
+                            }
+                        }
+                        SubstitutionSubtable::ReverseChainSingle(reverse) => {
+                            found.extend(reverse.substitutes.get(index));
+                        }
+                        // Contextual lookups only point at other lookups, which are gone through anyway.
+                        SubstitutionSubtable::Context(_)
+                        | SubstitutionSubtable::ChainContext(_) => {}
+                    }
+                }
+            }
+        }
+        let before = keep.len();
+        keep.extend(found);
+        if keep.len() == before {
+            return;
+        }
+    }
+}
+
+/// The glyphs a composite glyph's outline is made of, or none for simple glyphs.
+fn components(outline: &[u8]) -> Vec<GlyphId> {
+    const ARGS_ARE_WORDS: u16 = 0x0001;
+    const HAS_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const HAS_X_Y_SCALE: u16 = 0x0040;
+    const HAS_TWO_BY_TWO: u16 = 0x0080;
+    let mut found = vec![];
+    if outline.len() < 10 || (read_u16(outline, 0).unwrap_or(0) as i16) >= 0 {
+        return found;
+    }
+    let mut at = 10;
+    while let (Some(flags), Some(glyph)) = (read_u16(outline, at), read_u16(outline, at + 2)) {
+        found.push(GlyphId(glyph));
+        at += 4 + if flags & ARGS_ARE_WORDS != 0 { 4 } else { 2 };
+        if flags & HAS_SCALE != 0 {
+            at += 2;
+        } else if flags & HAS_X_Y_SCALE != 0 {
+            at += 4;
+        } else if flags & HAS_TWO_BY_TWO != 0 {
+            at += 8;
+        }
+        if flags & MORE_COMPONENTS == 0 {
+            break;
+        }
+    }
+    found
+}
+
+/// Where the table directory of the font at `index` starts, accounting for font collections.
+fn sfnt_offset(data: &[u8], index: u32) -> Option<usize> {
+    match data.get(0..4)? {
+        b"ttcf" => read_u32(data, 12 + index as usize * 4).map(|offset| offset as usize),
+        _ => Some(0),
+    }
+}
+
+/// Write a single font file out of its tables, which have to be sorted by tag.
+fn write_font(version: u32, tables: &[(Tag, &[u8])]) -> Vec<u8> {
+    let count = tables.len() as u16;
+    let entry_selector = count.checked_ilog2().unwrap_or(0) as u16;
+    let search_range = (1u16 << entry_selector) * 16;
+    let mut out = vec![];
+    out.extend_from_slice(&version.to_be_bytes());
+    out.extend_from_slice(&count.to_be_bytes());
+    out.extend_from_slice(&search_range.to_be_bytes());
+    out.extend_from_slice(&entry_selector.to_be_bytes());
+    out.extend_from_slice(&(count * 16 - search_range).to_be_bytes());
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, table) in tables {
+        out.extend_from_slice(&tag.to_bytes());
+        out.extend_from_slice(&checksum(table).to_be_bytes());
+        out.extend_from_slice(&(offset as u32).to_be_bytes());
+        out.extend_from_slice(&(table.len() as u32).to_be_bytes());
+        offset += table.len().next_multiple_of(4);
+    }
+    let mut head_at = None;
+    for (tag, table) in tables {
+        if &tag.to_bytes() == b"head" {
+            head_at = Some(out.len());
+        }
+        out.extend_from_slice(table);
+        out.resize(out.len().next_multiple_of(4), 0);
+    }
+    if let Some(head_at) = head_at {
+        let adjustment = 0xB1B0AFBAu32.wrapping_sub(checksum(&out));
+        out[head_at + 8..head_at + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    out
+}
+
+fn checksum(table: &[u8]) -> u32 {
+    table.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
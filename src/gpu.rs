@@ -0,0 +1,518 @@
+use std::path::PathBuf;
+
+use bevy_asset::{Assets, Handle, RenderAssetUsages};
+use bevy_camera::{Camera, Camera2d, ClearColorConfig, RenderTarget, visibility::RenderLayers};
+use bevy_color::Color;
+use bevy_ecs::{
+    change_detection::{DetectChanges, DetectChangesMut},
+    component::Component,
+    entity::Entity,
+    query::Has,
+    system::{Commands, Query, Res, ResMut},
+};
+use bevy_image::Image;
+use bevy_mesh::{Indices, Mesh, Mesh2d, PrimitiveTopology};
+use bevy_sprite_render::{ColorMaterial, MeshMaterial2d};
+use lyon_tessellation::{
+    BuffersBuilder, FillOptions, FillRule as LyonFillRule, FillTessellator, FillVertex,
+    LineCap as LyonLineCap, LineJoin as LyonLineJoin, StrokeOptions, StrokeTessellator,
+    StrokeVertex, VertexBuffers,
+    math::point,
+    path::{Path, path::Builder},
+};
+use typst::{
+    foundations::Dict,
+    layout::{Abs, Frame, FrameItem, PagedDocument, Point, Ratio, Transform},
+    text::TextItem,
+    visualize::{
+        ColorSpace, CurveItem, FillRule, FixedStroke, Geometry, LineCap, LineJoin, Paint, Shape,
+    },
+};
+use wgpu_types::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+
+use crate::{
+    PathBufOrTemplate, TypstTextureServer, asset_loading::TypstTemplate, job_error::TypstJobError,
+    logging::typst_log,
+};
+
+/// How far tessellated curves may stray from the true outline, in pixels.
+const TOLERANCE: f32 = 0.1;
+
+/// A typst document drawn by the GPU into a render target, for documents that update often such as live
+/// dashboards. Rather than rasterizing on the CPU and uploading the pixels, the page's shapes and glyph outlines
+/// are tessellated into a mesh that's drawn into [`TypstGpuDocument::image`] by an offscreen 2D camera, so a
+/// re-render only uploads the new mesh.
+///
+/// The entity this is on becomes that camera, drawing only `render_layer`, which nothing else should be on. The
+/// camera is set up when this component is first seen, so changing `render_layer` afterwards has no effect. The
+/// document is recompiled whenever this component changes, as a job of `queue` that's run after the
+/// [`TypstTextureServer`]'s own queued jobs, so it's held while jobs are paused and counts towards the queue's
+/// `jobs_per_frame`.
+///
+/// This draws solid fills and strokes only: images, gradients, tilings, dashes, clipping and colour glyphs are
+/// left out, so use the regular job queue for documents that rely on them.
+#[derive(Debug, Component)]
+pub struct TypstGpuDocument {
+    pub template: PathBuf,
+    pub input: Dict,
+    pub pixels_per_pt: f32,
+    /// The page to draw, clamped to the last page.
+    pub page: usize,
+    pub render_layer: usize,
+    /// The [`TypstTextureServer::queues`] entry whose per-frame limit redraws count towards, or the main queue.
+    pub queue: Option<String>,
+    image: Handle<Image>,
+    mesh: Handle<Mesh>,
+    material: Handle<ColorMaterial>,
+    template_handle: Option<Handle<TypstTemplate>>,
+    /// If the document has changed since it was last drawn.
+    dirty: bool,
+}
+
+impl TypstGpuDocument {
+    pub fn new(
+        template_server: &TypstTextureServer,
+        template: impl Into<PathBuf>,
+        render_layer: usize,
+    ) -> Self {
+        Self {
+            template: template.into(),
+            input: Dict::new(),
+            pixels_per_pt: 1.0,
+            page: 0,
+            render_layer,
+            queue: None,
+            image: template_server.asset_server.add(target_image(1, 1)),
+            mesh: template_server.asset_server.add(Mesh::new(
+                PrimitiveTopology::TriangleList,
+                RenderAssetUsages::RENDER_WORLD,
+            )),
+            material: template_server.asset_server.add(ColorMaterial::default()),
+            template_handle: None,
+            dirty: true,
+        }
+    }
+
+    pub fn with_input(mut self, input: impl Into<Dict>) -> Self {
+        self.input = input.into();
+        self
+    }
+
+    pub fn with_pixels_per_pt(mut self, pixels_per_pt: f32) -> Self {
+        self.pixels_per_pt = pixels_per_pt;
+        self
+    }
+
+    pub fn with_page(mut self, page: usize) -> Self {
+        self.page = page;
+        self
+    }
+
+    pub fn with_queue(mut self, queue: impl Into<String>) -> Self {
+        self.queue = Some(queue.into());
+        self
+    }
+
+    /// The render target this document is drawn into, i.e. to show on a sprite or UI node.
+    pub fn image(&self) -> Handle<Image> {
+        self.image.clone()
+    }
+
+    /// Compile the document and tessellate its page, if its template has loaded and its queue has room this frame.
+    fn redraw(
+        &mut self,
+        template_server: &mut TypstTextureServer,
+        templates: &Assets<TypstTemplate>,
+        images: &mut Assets<Image>,
+        meshes: &mut Assets<Mesh>,
+    ) {
+        let handle = self.template_handle.get_or_insert_with(|| {
+            template_server.resolve_template(PathBufOrTemplate::PathBuf(self.template.clone()))
+        });
+        let Some(template) = templates.get(&*handle) else {
+            return;
+        };
+        if !template_server.take_job_slot(self.queue.as_deref()) {
+            return;
+        }
+        self.dirty = false;
+        let (engine, _) = template_server.engine_fonts.to_engine(template.0.clone());
+        let document = match engine
+            .compile_with_input::<_, PagedDocument>(self.input.clone())
+            .output
+        {
+            Ok(document) => document,
+            Err(error) => {
+                TypstJobError::compile(error).log(&self.template);
+                return;
+            }
+        };
+        let Some(page) = document
+            .pages
+            .get(self.page.min(document.pages.len().saturating_sub(1)))
+        else {
+            return;
+        };
+        let size = page.frame.size();
+        let width = (self.pixels_per_pt * size.x.to_f32()).round().max(1.0) as u32;
+        let height = (self.pixels_per_pt * size.y.to_f32()).round().max(1.0) as u32;
+        if let Some(image) = images.get_mut(&self.image)
+            && (image.width(), image.height()) != (width, height)
+        {
+            *image = target_image(width, height);
+        }
+        let mut tessellation = Tessellation {
+            buffers: VertexBuffers::new(),
+            origin: [width as f32 / 2.0, height as f32 / 2.0],
+        };
+        tessellation.frame(
+            &page.frame,
+            Transform::scale(
+                Ratio::new(self.pixels_per_pt as f64),
+                Ratio::new(self.pixels_per_pt as f64),
+            ),
+        );
+        if let Some(mesh) = meshes.get_mut(&self.mesh) {
+            *mesh = tessellation.into_mesh();
+        }
+    }
+}
+
+/// An empty image that can be rendered into.
+fn target_image(width: u32, height: u32) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT;
+    image
+}
+
+#[derive(Debug, Clone, Copy)]
+struct GpuVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+/// Vector content of a page, tessellated into triangles in the camera's space.
+struct Tessellation {
+    buffers: VertexBuffers<GpuVertex, u32>,
+    /// The centre of the page in pixels, which the camera looks at.
+    origin: [f32; 2],
+}
+
+impl Tessellation {
+    fn frame(&mut self, frame: &Frame, transform: Transform) {
+        for (pos, item) in frame.items() {
+            let transform = transform.pre_concat(Transform::translate(pos.x, pos.y));
+            match item {
+                FrameItem::Group(group) => {
+                    self.frame(&group.frame, transform.pre_concat(group.transform))
+                }
+                FrameItem::Shape(shape, _) => self.shape(shape, transform),
+                FrameItem::Text(text) => self.text(text, transform),
+                _ => {}
+            }
+        }
+    }
+
+    fn shape(&mut self, shape: &Shape, transform: Transform) {
+        let mut path = PathWriter::new(transform);
+        match &shape.geometry {
+            Geometry::Line(to) => {
+                path.move_to(Point::zero());
+                path.line_to(*to);
+            }
+            Geometry::Rect(size) => {
+                path.move_to(Point::zero());
+                path.line_to(Point::with_x(size.x));
+                path.line_to(Point::new(size.x, size.y));
+                path.line_to(Point::with_y(size.y));
+                path.close();
+            }
+            Geometry::Curve(curve) => {
+                for item in curve.0.iter() {
+                    match *item {
+                        CurveItem::Move(to) => path.move_to(to),
+                        CurveItem::Line(to) => path.line_to(to),
+                        CurveItem::Cubic(a, b, to) => path.cubic_to(a, b, to),
+                        CurveItem::Close => path.close(),
+                    }
+                }
+            }
+        }
+        let path = path.build();
+        if let Some(color) = shape.fill.as_ref().and_then(solid) {
+            let rule = match shape.fill_rule {
+                FillRule::NonZero => LyonFillRule::NonZero,
+                FillRule::EvenOdd => LyonFillRule::EvenOdd,
+            };
+            self.fill(&path, rule, color);
+        }
+        if let Some(stroke) = &shape.stroke
+            && let Some(color) = solid(&stroke.paint)
+        {
+            self.stroke(&path, stroke, transform, color);
+        }
+    }
+
+    fn text(&mut self, text: &TextItem, transform: Transform) {
+        let Some(color) = solid(&text.fill) else {
+            return;
+        };
+        let scale = text.size.to_pt() / text.font.units_per_em();
+        let mut path = PathWriter::new(transform);
+        let (mut x, mut y) = (Abs::zero(), Abs::zero());
+        for glyph in &text.glyphs {
+            // Offsets and vertical advances are y up, as with typst-render.
+            path.glyph_origin = Point::new(
+                x + glyph.x_offset.at(text.size),
+                -(y + glyph.y_offset.at(text.size)),
+            );
+            path.glyph_scale = scale;
+            text.font
+                .ttf()
+                .outline_glyph(ttf_parser::GlyphId(glyph.id), &mut path);
+            x += glyph.x_advance.at(text.size);
+            y += glyph.y_advance.at(text.size);
+        }
+        self.fill(&path.build(), LyonFillRule::NonZero, color);
+    }
+
+    fn fill(&mut self, path: &Path, rule: LyonFillRule, color: [f32; 4]) {
+        let origin = self.origin;
+        let result = FillTessellator::new().tessellate_path(
+            path,
+            &FillOptions::tolerance(TOLERANCE).with_fill_rule(rule),
+            &mut BuffersBuilder::new(&mut self.buffers, |vertex: FillVertex| GpuVertex {
+                position: to_camera(origin, vertex.position()),
+                color,
+            }),
+        );
+        if let Err(error) = result {
+            typst_log!(
+                Render,
+                Warn,
+                "[TYPST GPU WARNING] Couldn't tessellate a fill: {:?}",
+                error
+            );
+        }
+    }
+
+    fn stroke(&mut self, path: &Path, stroke: &FixedStroke, transform: Transform, color: [f32; 4]) {
+        // The geometric mean of the transform's scale, as with text sizes in `legibility`.
+        let scale = (transform.sx.get() * transform.sy.get()
+            - transform.kx.get() * transform.ky.get())
+        .abs()
+        .sqrt();
+        let options = StrokeOptions::tolerance(TOLERANCE)
+            .with_line_width((stroke.thickness.to_pt() * scale) as f32)
+            .with_miter_limit((stroke.miter_limit.get() as f32).max(1.0))
+            .with_line_cap(match stroke.cap {
+                LineCap::Butt => LyonLineCap::Butt,
+                LineCap::Round => LyonLineCap::Round,
+                LineCap::Square => LyonLineCap::Square,
+            })
+            .with_line_join(match stroke.join {
+                LineJoin::Miter => LyonLineJoin::Miter,
+                LineJoin::Round => LyonLineJoin::Round,
+                LineJoin::Bevel => LyonLineJoin::Bevel,
+            });
+        let origin = self.origin;
+        let result = StrokeTessellator::new().tessellate_path(
+            path,
+            &options,
+            &mut BuffersBuilder::new(&mut self.buffers, |vertex: StrokeVertex| GpuVertex {
+                position: to_camera(origin, vertex.position()),
+                color,
+            }),
+        );
+        if let Err(error) = result {
+            typst_log!(
+                Render,
+                Warn,
+                "[TYPST GPU WARNING] Couldn't tessellate a stroke: {:?}",
+                error
+            );
+        }
+    }
+
+    fn into_mesh(self) -> Mesh {
+        let (positions, colors): (Vec<_>, Vec<_>) = self
+            .buffers
+            .vertices
+            .into_iter()
+            .map(|vertex| (vertex.position, vertex.color))
+            .unzip();
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+        .with_inserted_indices(Indices::U32(self.buffers.indices))
+    }
+}
+
+/// From page pixels (y down, from the top left) to the camera's space (y up, from the centre).
+fn to_camera(origin: [f32; 2], position: lyon_tessellation::math::Point) -> [f32; 3] {
+    [position.x - origin[0], origin[1] - position.y, 0.0]
+}
+
+/// The linear colour of a paint, if it's a solid colour.
+fn solid(paint: &Paint) -> Option<[f32; 4]> {
+    match paint {
+        Paint::Solid(color) => Some(color.to_space(ColorSpace::LinearRgb).to_vec4()),
+        _ => None,
+    }
+}
+
+/// Builds a lyon path in page pixels from typst curves and glyph outlines.
+struct PathWriter {
+    builder: Builder,
+    transform: Transform,
+    open: bool,
+    /// Where the glyph being outlined sits, in the text item's space.
+    glyph_origin: Point,
+    /// Font units to points for the glyph being outlined.
+    glyph_scale: f64,
+}
+
+impl PathWriter {
+    fn new(transform: Transform) -> Self {
+        Self {
+            builder: Path::builder(),
+            transform,
+            open: false,
+            glyph_origin: Point::zero(),
+            glyph_scale: 1.0,
+        }
+    }
+
+    fn point(&self, to: Point) -> lyon_tessellation::math::Point {
+        let to = to.transform(self.transform);
+        point(to.x.to_pt() as f32, to.y.to_pt() as f32)
+    }
+
+    /// A point of a glyph outline, which is in font units with y up.
+    fn glyph_point(&self, x: f32, y: f32) -> Point {
+        self.glyph_origin
+            + Point::new(
+                Abs::pt(x as f64 * self.glyph_scale),
+                Abs::pt(-y as f64 * self.glyph_scale),
+            )
+    }
+
+    fn move_to(&mut self, to: Point) {
+        if self.open {
+            self.builder.end(false);
+        }
+        let to = self.point(to);
+        self.builder.begin(to);
+        self.open = true;
+    }
+
+    fn line_to(&mut self, to: Point) {
+        let to = self.point(to);
+        self.builder.line_to(to);
+    }
+
+    fn cubic_to(&mut self, a: Point, b: Point, to: Point) {
+        let (a, b, to) = (self.point(a), self.point(b), self.point(to));
+        self.builder.cubic_bezier_to(a, b, to);
+    }
+
+    fn close(&mut self) {
+        if self.open {
+            self.builder.end(true);
+            self.open = false;
+        }
+    }
+
+    fn build(mut self) -> Path {
+        if self.open {
+            self.builder.end(false);
+        }
+        self.builder.build()
+    }
+}
+
+impl ttf_parser::OutlineBuilder for PathWriter {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let to = self.glyph_point(x, y);
+        PathWriter::move_to(self, to);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let to = self.glyph_point(x, y);
+        PathWriter::line_to(self, to);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (a, to) = (
+            self.point(self.glyph_point(x1, y1)),
+            self.point(self.glyph_point(x, y)),
+        );
+        self.builder.quadratic_bezier_to(a, to);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (a, b, to) = (
+            self.glyph_point(x1, y1),
+            self.glyph_point(x2, y2),
+            self.glyph_point(x, y),
+        );
+        PathWriter::cubic_to(self, a, b, to);
+    }
+
+    fn close(&mut self) {
+        PathWriter::close(self);
+    }
+}
+
+pub(crate) fn system_draw_typst_gpu_documents(
+    mut commands: Commands,
+    mut template_server: ResMut<TypstTextureServer>,
+    templates: Res<Assets<TypstTemplate>>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut documents: Query<(Entity, &mut TypstGpuDocument, Has<Camera>)>,
+) {
+    for (entity, mut document, has_camera) in &mut documents {
+        if !has_camera {
+            let layer = RenderLayers::layer(document.render_layer);
+            commands
+                .entity(entity)
+                .insert((
+                    Camera2d,
+                    Camera {
+                        // Draw before any camera that shows the render.
+                        order: -1,
+                        clear_color: ClearColorConfig::Custom(Color::NONE),
+                        ..Default::default()
+                    },
+                    RenderTarget::Image(document.image.clone().into()),
+                    layer.clone(),
+                ))
+                .with_child((
+                    Mesh2d(document.mesh.clone()),
+                    MeshMaterial2d(document.material.clone()),
+                    layer,
+                ));
+        }
+        let changed = document.is_changed();
+        let document = document.bypass_change_detection();
+        document.dirty |= changed;
+        if document.dirty {
+            document.redraw(&mut template_server, &templates, &mut images, &mut meshes);
+        }
+    }
+}
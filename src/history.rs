@@ -0,0 +1,215 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bevy_ecs::{
+    message::MessageReader,
+    resource::Resource,
+    system::{Res, ResMut},
+};
+use bevy_time::{Real, Time};
+use typst::foundations::{Dict, IntoValue};
+
+use crate::{
+    TypstJobId,
+    events::{TypstJobCompleted, TypstJobFailed},
+};
+
+/// Jobs whose inputs are kept while waiting for them to complete or fail, past which the oldest are dropped.
+const PENDING_INPUT_LIMIT: usize = 1024;
+
+/// How much of a job's input [`TypstJobHistory`] keeps.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum TypstHistoryInputs {
+    /// Don't keep inputs.
+    #[default]
+    Omit,
+    /// Keep the input's keys, with every value replaced by `"[redacted]"`.
+    Keys,
+    /// Keep the whole input, except the values of the listed top-level keys, which are replaced by
+    /// `"[redacted]"`.
+    Full { redact: Vec<String> },
+}
+
+impl TypstHistoryInputs {
+    fn record(&self, input: &Dict) -> Option<Dict> {
+        match self {
+            TypstHistoryInputs::Omit => None,
+            TypstHistoryInputs::Keys => Some(
+                input
+                    .iter()
+                    .map(|(key, _)| (key.clone(), "[redacted]".into_value()))
+                    .collect(),
+            ),
+            TypstHistoryInputs::Full { redact } => Some(
+                input
+                    .iter()
+                    .map(|(key, value)| {
+                        if redact
+                            .iter()
+                            .any(|redacted| redacted.as_str() == key.as_str())
+                        {
+                            (key.clone(), "[redacted]".into_value())
+                        } else {
+                            (key.clone(), value.clone())
+                        }
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// How a job in [`TypstJobHistory`] resolved.
+#[derive(Debug, Clone)]
+pub enum TypstJobOutcome {
+    Completed(TypstJobCompleted),
+    Failed(TypstJobFailed),
+}
+
+/// A job kept by [`TypstJobHistory`].
+#[derive(Debug, Clone)]
+pub struct TypstJobRecord {
+    pub outcome: TypstJobOutcome,
+    /// The job's input, as kept according to [`TypstJobHistory::inputs`].
+    pub input: Option<Dict>,
+    /// The real time since startup when the job resolved.
+    pub resolved_at: Duration,
+}
+
+impl TypstJobRecord {
+    pub fn job(&self) -> TypstJobId {
+        match &self.outcome {
+            TypstJobOutcome::Completed(completed) => completed.job,
+            TypstJobOutcome::Failed(failed) => failed.job,
+        }
+    }
+
+    /// The time from the job being picked up from the queue until its image was available, if it completed.
+    pub fn duration(&self) -> Option<Duration> {
+        match &self.outcome {
+            TypstJobOutcome::Completed(completed) => Some(completed.duration),
+            TypstJobOutcome::Failed(_) => None,
+        }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        matches!(self.outcome, TypstJobOutcome::Failed(_))
+    }
+}
+
+/// A ring buffer of the last jobs to complete or fail, with their timings and diagnostics, for looking into
+/// slow or broken renders from a debug UI after the log has scrolled by. Insert this resource to start
+/// recording, i.e. `app.insert_resource(TypstJobHistory::new(64))`.
+#[derive(Debug, Clone, Resource)]
+pub struct TypstJobHistory {
+    /// The number of jobs kept, dropping the oldest first.
+    pub capacity: usize,
+    /// How much of each job's input is kept. Defaults to [`TypstHistoryInputs::Omit`].
+    pub inputs: TypstHistoryInputs,
+    records: VecDeque<TypstJobRecord>,
+    pending_inputs: VecDeque<(TypstJobId, Dict)>,
+}
+
+impl Default for TypstJobHistory {
+    fn default() -> Self {
+        Self::new(64)
+    }
+}
+
+impl TypstJobHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inputs: TypstHistoryInputs::default(),
+            records: VecDeque::new(),
+            pending_inputs: VecDeque::new(),
+        }
+    }
+
+    pub fn with_inputs(mut self, inputs: TypstHistoryInputs) -> Self {
+        self.inputs = inputs;
+        self
+    }
+
+    /// The jobs kept, oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &TypstJobRecord> {
+        self.records.iter()
+    }
+
+    pub fn get(&self, job: TypstJobId) -> Option<&TypstJobRecord> {
+        self.records.iter().find(|record| record.job() == job)
+    }
+
+    /// The completed job that took the longest.
+    pub fn slowest(&self) -> Option<&TypstJobRecord> {
+        self.records
+            .iter()
+            .filter(|record| record.duration().is_some())
+            .max_by_key(|record| record.duration())
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &TypstJobRecord> {
+        self.records.iter().filter(|record| record.is_failure())
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// Keep a job's input, as it's picked up from the queue, until it resolves.
+    pub(crate) fn record_input(&mut self, job: TypstJobId, input: &Dict) {
+        let Some(input) = self.inputs.record(input) else {
+            return;
+        };
+        if self.pending_inputs.len() >= PENDING_INPUT_LIMIT {
+            self.pending_inputs.pop_front();
+        }
+        self.pending_inputs.push_back((job, input));
+    }
+
+    fn push(&mut self, outcome: TypstJobOutcome, resolved_at: Duration) {
+        let mut record = TypstJobRecord {
+            outcome,
+            input: None,
+            resolved_at,
+        };
+        let job = record.job();
+        if let Some(index) = self.pending_inputs.iter().position(|(id, _)| *id == job) {
+            record.input = self.pending_inputs.remove(index).map(|(_, input)| input);
+        }
+        if self.capacity == 0 {
+            return;
+        }
+        while self.records.len() >= self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+}
+
+pub(crate) fn system_record_job_history(
+    history: Option<ResMut<TypstJobHistory>>,
+    mut completed: MessageReader<TypstJobCompleted>,
+    mut failed: MessageReader<TypstJobFailed>,
+    time: Option<Res<Time<Real>>>,
+) {
+    let Some(mut history) = history else {
+        completed.clear();
+        failed.clear();
+        return;
+    };
+    let resolved_at = time.map(|time| time.elapsed()).unwrap_or_default();
+    for completed in completed.read() {
+        history.push(TypstJobOutcome::Completed(completed.clone()), resolved_at);
+    }
+    for failed in failed.read() {
+        history.push(TypstJobOutcome::Failed(failed.clone()), resolved_at);
+    }
+}
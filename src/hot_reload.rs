@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use bevy_asset::{AssetEvent, AssetId, Assets, Handle};
+use bevy_ecs::{
+    message::MessageReader,
+    system::{ResMut, SystemParam},
+};
+use bevy_image::Image;
+use typst::foundations::Dict;
+
+use crate::{
+    JobImageHandle, TypstJob, TypstJobId, TypstJobOptions, TypstRenderInfo, TypstTextureServer,
+    asset_loading::TypstTemplate,
+};
+
+/// How to re-run a job when its template is modified.
+#[derive(Debug)]
+pub(crate) struct RenderRecipe {
+    pub(crate) image: AssetId<Image>,
+    pub(crate) info: AssetId<TypstRenderInfo>,
+    pub(crate) input: Dict,
+    pub(crate) options: TypstJobOptions,
+}
+
+/// A re-run job whose outputs are written over the original job's assets once they arrive.
+#[derive(Debug)]
+pub(crate) struct PendingRerender {
+    image: AssetId<Image>,
+    info: AssetId<TypstRenderInfo>,
+    image_receiver: async_channel::Receiver<Image>,
+    info_receiver: async_channel::Receiver<TypstRenderInfo>,
+}
+
+/// The asset collections written by [`TypstTextureServer::system_hot_reload`].
+#[derive(SystemParam)]
+pub struct RerenderTargets<'w> {
+    templates: ResMut<'w, Assets<TypstTemplate>>,
+    images: ResMut<'w, Assets<Image>>,
+    infos: ResMut<'w, Assets<TypstRenderInfo>>,
+}
+
+impl TypstTextureServer {
+    /// Runs in `Last`. Writes finished renders over existing assets for jobs that target them, such as
+    /// [`crate::binding::TypstBinding`] renders. When [`TypstTextureServer::hot_reload_jobs`] is set, also re-queues
+    /// the jobs of modified templates to be written over the images and render info of the original jobs.
+    pub fn system_hot_reload(
+        mut template_server: ResMut<TypstTextureServer>,
+        mut asset_events: MessageReader<AssetEvent<TypstTemplate>>,
+        mut targets: RerenderTargets,
+    ) {
+        template_server.pending_rerenders.retain(|rerender| {
+            match rerender.image_receiver.try_recv() {
+                Ok(image) => {
+                    // Render info is always sent before the image.
+                    if let Ok(info) = rerender.info_receiver.try_recv() {
+                        let _ = targets.infos.insert(rerender.info, info);
+                    }
+                    let _ = targets.images.insert(rerender.image, image);
+                    false
+                }
+                Err(error) => !error.is_closed(),
+            }
+        });
+        for event in asset_events.read() {
+            let AssetEvent::Modified { id } = event else {
+                continue;
+            };
+            let Some(recipes) = template_server.render_recipes.remove(id) else {
+                continue;
+            };
+            let Some(template) = targets.templates.get_strong_handle(*id) else {
+                continue;
+            };
+            let recipes: Vec<RenderRecipe> = recipes
+                .into_iter()
+                .filter_map(|recipe| {
+                    let image = targets.images.get_strong_handle(recipe.image)?;
+                    template_server.enqueue_into(
+                        template.clone(),
+                        image,
+                        recipe.info,
+                        recipe.input.clone(),
+                        recipe.options.clone(),
+                    );
+                    Some(recipe)
+                })
+                .collect();
+            template_server.render_recipes.insert(*id, recipes);
+        }
+    }
+
+    /// Record how to re-run a job, if hot reloading jobs is enabled.
+    pub(crate) fn record_recipe(&mut self, template: &Handle<TypstTemplate>, recipe: RenderRecipe) {
+        if self.hot_reload_jobs {
+            self.render_recipes
+                .entry(template.id())
+                .or_default()
+                .push(recipe);
+        }
+    }
+
+    /// Queue a job whose render is written over existing image and render info assets once it's finished,
+    /// rather than into new assets.
+    pub(crate) fn enqueue_into(
+        &mut self,
+        template: Handle<TypstTemplate>,
+        image: Handle<Image>,
+        info: AssetId<TypstRenderInfo>,
+        input: Dict,
+        options: TypstJobOptions,
+    ) -> TypstJobId {
+        let (send_target, image_receiver) = async_channel::bounded(1);
+        let (info_target, info_receiver) = async_channel::bounded(1);
+        let id = TypstJobId(self.next_job_id);
+        self.next_job_id += 1;
+        self.pending_rerenders.push(PendingRerender {
+            image: image.id(),
+            info,
+            image_receiver,
+            info_receiver,
+        });
+        self.enqueue(TypstJob {
+            id,
+            use_template: template,
+            input,
+            send_target,
+            info_target,
+            job_options: options,
+            image: JobImageHandle::Strong(image),
+            shared_compile: None,
+            cancelled: Arc::default(),
+        });
+        id
+    }
+}
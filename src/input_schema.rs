@@ -0,0 +1,309 @@
+use std::{collections::BTreeMap, path::Path};
+
+use bevy_asset::Assets;
+use serde::{Deserialize, Serialize};
+use typst::foundations::{Dict, Value};
+
+use crate::{PathBufOrTemplate, TypstTextureServer, asset_loading::TypstTemplate};
+
+/// The expected inputs of a template, declared in the `[inputs]` table of its `package.toml` as
+/// key/type pairs. A `?` suffix marks a key as optional:
+///
+/// ```toml
+/// [inputs]
+/// title = "str"
+/// score = "int"
+/// subtitle = "str?"
+/// ```
+///
+/// Recognised types are `any`, `none`, `bool`, `int`, `float` (which also accepts integers), `str`, `bytes`,
+/// `array`, `dictionary`, and `content`. Keys not in the schema are allowed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(
+    try_from = "BTreeMap<String, toml::Value>",
+    into = "BTreeMap<String, toml::Value>"
+)]
+pub struct TypstInputSchema {
+    pub keys: BTreeMap<String, TypstInputSpec>,
+    /// Documentation for inputs, see [`TypstInputDoc`].
+    pub docs: BTreeMap<String, TypstInputDoc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypstInputSpec {
+    pub ty: TypstInputType,
+    pub optional: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypstInputType {
+    Any,
+    None,
+    Bool,
+    Int,
+    Float,
+    Str,
+    Bytes,
+    Array,
+    Dictionary,
+    Content,
+}
+
+impl TypstInputType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TypstInputType::Any => "any",
+            TypstInputType::None => "none",
+            TypstInputType::Bool => "bool",
+            TypstInputType::Int => "int",
+            TypstInputType::Float => "float",
+            TypstInputType::Str => "str",
+            TypstInputType::Bytes => "bytes",
+            TypstInputType::Array => "array",
+            TypstInputType::Dictionary => "dictionary",
+            TypstInputType::Content => "content",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "any" => TypstInputType::Any,
+            "none" => TypstInputType::None,
+            "bool" => TypstInputType::Bool,
+            "int" => TypstInputType::Int,
+            "float" => TypstInputType::Float,
+            "str" | "string" => TypstInputType::Str,
+            "bytes" => TypstInputType::Bytes,
+            "array" => TypstInputType::Array,
+            "dictionary" | "dict" => TypstInputType::Dictionary,
+            "content" => TypstInputType::Content,
+            _ => return None,
+        })
+    }
+
+    pub fn matches(&self, value: &Value) -> bool {
+        matches!(
+            (self, value),
+            (TypstInputType::Any, _)
+                | (TypstInputType::None, Value::None)
+                | (TypstInputType::Bool, Value::Bool(_))
+                | (TypstInputType::Int, Value::Int(_))
+                | (TypstInputType::Float, Value::Float(_) | Value::Int(_))
+                | (TypstInputType::Str, Value::Str(_))
+                | (TypstInputType::Bytes, Value::Bytes(_))
+                | (TypstInputType::Array, Value::Array(_))
+                | (TypstInputType::Dictionary, Value::Dict(_))
+                | (TypstInputType::Content, Value::Content(_))
+        )
+    }
+}
+
+impl TryFrom<BTreeMap<String, toml::Value>> for TypstInputSchema {
+    type Error = String;
+
+    fn try_from(mut value: BTreeMap<String, toml::Value>) -> Result<Self, Self::Error> {
+        let docs = match value.remove("docs") {
+            Some(toml::Value::Table(docs)) => docs
+                .into_iter()
+                .map(|(key, doc)| Ok((key, TypstInputDoc::try_from(doc)?)))
+                .collect::<Result<_, String>>()?,
+            // An input that happens to be called "docs".
+            Some(ty) => {
+                value.insert("docs".to_owned(), ty);
+                BTreeMap::new()
+            }
+            None => BTreeMap::new(),
+        };
+        let keys = value
+            .into_iter()
+            .map(|(key, ty)| {
+                let toml::Value::String(ty) = ty else {
+                    return Err(format!("input type for key '{key}' must be a string"));
+                };
+                let (name, optional) = match ty.strip_suffix('?') {
+                    Some(name) => (name, true),
+                    None => (ty.as_str(), false),
+                };
+                let ty = TypstInputType::from_name(name)
+                    .ok_or_else(|| format!("unknown input type '{ty}' for key '{key}'"))?;
+                Ok((key, TypstInputSpec { ty, optional }))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { keys, docs })
+    }
+}
+
+impl From<TypstInputSchema> for BTreeMap<String, toml::Value> {
+    fn from(value: TypstInputSchema) -> Self {
+        let mut map: BTreeMap<String, toml::Value> = value
+            .keys
+            .into_iter()
+            .map(|(key, spec)| {
+                let suffix = if spec.optional { "?" } else { "" };
+                (
+                    key,
+                    toml::Value::String(format!("{}{suffix}", spec.ty.name())),
+                )
+            })
+            .collect();
+        if !value.docs.is_empty() {
+            let docs = value
+                .docs
+                .into_iter()
+                .map(|(key, doc)| (key, doc.into()))
+                .collect();
+            map.insert("docs".to_owned(), toml::Value::Table(docs));
+        }
+        map
+    }
+}
+
+/// Documentation for an input, from the `[inputs.docs]` table of a template's `package.toml`. Each entry is either
+/// a description or a table with a `description` and an example or `default` value:
+///
+/// ```toml
+/// [inputs.docs]
+/// title = "The name shown at the top of the card"
+/// subtitle = { description = "Flavour text under the title", default = "" }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TypstInputDoc {
+    pub description: Option<String>,
+    /// The value the template uses when the input isn't given, as written in the manifest.
+    pub default: Option<toml::Value>,
+}
+
+impl TryFrom<toml::Value> for TypstInputDoc {
+    type Error = String;
+
+    fn try_from(value: toml::Value) -> Result<Self, Self::Error> {
+        match value {
+            toml::Value::String(description) => Ok(Self {
+                description: Some(description),
+                default: None,
+            }),
+            toml::Value::Table(mut table) => Ok(Self {
+                description: match table.remove("description") {
+                    Some(toml::Value::String(description)) => Some(description),
+                    Some(_) => return Err("input descriptions must be strings".to_owned()),
+                    None => None,
+                },
+                default: table.remove("default"),
+            }),
+            _ => Err("input docs must be a string or a table".to_owned()),
+        }
+    }
+}
+
+impl From<TypstInputDoc> for toml::Value {
+    fn from(value: TypstInputDoc) -> Self {
+        let mut table = toml::Table::new();
+        if let Some(description) = value.description {
+            table.insert("description".to_owned(), toml::Value::String(description));
+        }
+        if let Some(default) = value.default {
+            table.insert("default".to_owned(), default);
+        }
+        toml::Value::Table(table)
+    }
+}
+
+/// Everything known about one of a template's inputs, from [`TypstInputSchema::describe`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypstInputDescription {
+    pub key: String,
+    /// The declared type, if the input is in the schema rather than only documented.
+    pub spec: Option<TypstInputSpec>,
+    pub doc: TypstInputDoc,
+}
+
+impl TypstInputSchema {
+    /// Every declared or documented input, in key order, for showing in template browsers and other tooling.
+    pub fn describe(&self) -> Vec<TypstInputDescription> {
+        let keys: std::collections::BTreeSet<&String> =
+            self.keys.keys().chain(self.docs.keys()).collect();
+        keys.into_iter()
+            .map(|key| TypstInputDescription {
+                key: key.clone(),
+                spec: self.keys.get(key).copied(),
+                doc: self.docs.get(key).cloned().unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Check `input` against this schema, collecting every missing and mistyped key.
+    pub fn validate(&self, input: &Dict) -> Result<(), TypstInputSchemaError> {
+        let mut error = TypstInputSchemaError::default();
+        for (key, spec) in &self.keys {
+            match input.get(key.as_str()) {
+                Ok(value) if !spec.ty.matches(value) => error.mistyped.push(TypstMistypedInput {
+                    key: key.clone(),
+                    expected: spec.ty,
+                    found: value.ty().to_string(),
+                }),
+                Ok(_) => {}
+                Err(_) if spec.optional => {}
+                Err(_) => error.missing.push(key.clone()),
+            }
+        }
+        if error.missing.is_empty() && error.mistyped.is_empty() {
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+}
+
+impl TypstTextureServer {
+    /// The declared and documented inputs of a loaded template (or a registered name), for template browsers and
+    /// other tooling. `None` if the template hasn't loaded.
+    pub fn template_inputs(
+        &self,
+        templates: &Assets<TypstTemplate>,
+        template_path: impl AsRef<Path>,
+    ) -> Option<Vec<TypstInputDescription>> {
+        let handle = match self.resolve_alias(PathBufOrTemplate::PathBuf(
+            template_path.as_ref().to_path_buf(),
+        )) {
+            PathBufOrTemplate::PathBuf(path) => self.templates.get(&path)?.clone(),
+            PathBufOrTemplate::ExistingTemplate(handle) => handle,
+            PathBufOrTemplate::NewTemplate(_) | PathBufOrTemplate::Bytes { .. } => return None,
+        };
+        Some(templates.get(&handle)?.0.loaded_toml.inputs.describe())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TypstMistypedInput {
+    pub key: String,
+    pub expected: TypstInputType,
+    pub found: String,
+}
+
+/// Every way an input failed to match a [`TypstInputSchema`].
+#[derive(Debug, Clone, Default)]
+pub struct TypstInputSchemaError {
+    pub missing: Vec<String>,
+    pub mistyped: Vec<TypstMistypedInput>,
+}
+
+impl std::fmt::Display for TypstInputSchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TypstInputSchemaError:")?;
+        if !self.missing.is_empty() {
+            write!(f, " missing keys [{}]", self.missing.join(", "))?;
+        }
+        for mistyped in &self.mistyped {
+            write!(
+                f,
+                " key '{}' expected {} but got {};",
+                mistyped.key,
+                mistyped.expected.name(),
+                mistyped.found
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TypstInputSchemaError {}
@@ -0,0 +1,82 @@
+use bevy_app::{Last, Plugin};
+use bevy_diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy_ecs::{
+    message::MessageReader,
+    schedule::IntoScheduleConfigs,
+    system::{Res, ResMut},
+};
+use bevy_time::{Real, Time};
+
+use crate::{TypstTextureServer, events::TypstJobCompleted};
+
+/// Registers measurements of the [`TypstTextureServer`]'s work with bevy's `DiagnosticsStore`, for overlays
+/// and graphs alongside `FrameTimeDiagnosticsPlugin`. Add this after the [`crate::TypstTexturesPlugin`].
+///
+/// This takes the server's stats every frame, so [`TypstTextureServer::take_stats`] will only see what's
+/// happened since this last ran.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TypstJobDiagnosticsPlugin;
+
+impl TypstJobDiagnosticsPlugin {
+    /// Jobs waiting to be compiled.
+    pub const PENDING_JOBS: DiagnosticPath = DiagnosticPath::const_new("typst/pending_jobs");
+    /// Renders that reached the asset server per second.
+    pub const JOBS_COMPLETED_PER_SECOND: DiagnosticPath =
+        DiagnosticPath::const_new("typst/jobs_completed_per_second");
+    /// Average time to compile a job, in milliseconds.
+    pub const COMPILE_TIME: DiagnosticPath = DiagnosticPath::const_new("typst/compile_time");
+    /// Average time to rasterize a page, in milliseconds.
+    pub const RASTER_TIME: DiagnosticPath = DiagnosticPath::const_new("typst/raster_time");
+    /// Percentage of jobs that reused an engine built for an earlier job of their template that frame.
+    pub const ENGINE_CACHE_HIT_RATE: DiagnosticPath =
+        DiagnosticPath::const_new("typst/engine_cache_hit_rate");
+}
+
+impl Plugin for TypstJobDiagnosticsPlugin {
+    fn build(&self, app: &mut bevy_app::App) {
+        app.register_diagnostic(Diagnostic::new(Self::PENDING_JOBS))
+            .register_diagnostic(Diagnostic::new(Self::JOBS_COMPLETED_PER_SECOND))
+            .register_diagnostic(Diagnostic::new(Self::COMPILE_TIME).with_suffix("ms"))
+            .register_diagnostic(Diagnostic::new(Self::RASTER_TIME).with_suffix("ms"))
+            .register_diagnostic(Diagnostic::new(Self::ENGINE_CACHE_HIT_RATE).with_suffix("%"))
+            .add_systems(
+                Last,
+                system_measure_typst_jobs.after(TypstTextureServer::system_do_jobs),
+            );
+    }
+}
+
+fn system_measure_typst_jobs(
+    mut diagnostics: Diagnostics,
+    mut template_server: ResMut<TypstTextureServer>,
+    mut completed: MessageReader<TypstJobCompleted>,
+    time: Option<Res<Time<Real>>>,
+) {
+    let stats = template_server.take_stats();
+    let pending = template_server.queued();
+    diagnostics.add_measurement(&TypstJobDiagnosticsPlugin::PENDING_JOBS, || pending as f64);
+    let completed = completed.read().count();
+    if let Some(delta) = time.map(|time| time.delta_secs_f64())
+        && delta > 0.0
+    {
+        diagnostics.add_measurement(
+            &TypstJobDiagnosticsPlugin::JOBS_COMPLETED_PER_SECOND,
+            || completed as f64 / delta,
+        );
+    }
+    if stats.compiled > 0 {
+        diagnostics.add_measurement(&TypstJobDiagnosticsPlugin::COMPILE_TIME, || {
+            stats.compile_time.as_secs_f64() * 1000.0 / stats.compiled as f64
+        });
+    }
+    if stats.rasterized > 0 {
+        diagnostics.add_measurement(&TypstJobDiagnosticsPlugin::RASTER_TIME, || {
+            stats.raster_time.as_secs_f64() * 1000.0 / stats.rasterized as f64
+        });
+    }
+    if stats.engine_cache_lookups > 0 {
+        diagnostics.add_measurement(&TypstJobDiagnosticsPlugin::ENGINE_CACHE_HIT_RATE, || {
+            stats.engine_cache_hits as f64 * 100.0 / stats.engine_cache_lookups as f64
+        });
+    }
+}
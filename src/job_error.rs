@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use bevy_asset::AssetLoadError;
+use typst::{
+    diag::{Severity, SourceDiagnostic},
+    syntax::Span,
+};
+use typst_as_lib::TypstAsLibError;
+
+use crate::{
+    diagnostics::TypstDiagnostic, file_resolver::StructuredInMemoryTemplate,
+    input_schema::TypstInputSchemaError, logging::typst_log, serde_value::ValueSerializeError,
+};
+
+/// Why a typst job failed, by the stage of the pipeline it failed at.
+#[derive(Debug, Clone)]
+pub enum TypstJobError {
+    /// The job's template couldn't be loaded.
+    TemplateLoad(Arc<AssetLoadError>),
+    /// The job's input couldn't be converted into a typst dictionary, or doesn't match the template's inputs.
+    InputConversion(TypstInputError),
+    /// Typst couldn't compile the template. Errors that didn't come with source locations are given as
+    /// diagnostics with detached spans.
+    Compile(Vec<SourceDiagnostic>),
+    /// The compiled document had no page to render.
+    Render(String),
+    /// The finished image couldn't be handed over to the asset server.
+    Upload(String),
+    /// The job was cancelled before it ran.
+    Cancelled,
+}
+
+impl TypstJobError {
+    pub(crate) fn compile(error: TypstAsLibError) -> Self {
+        match error {
+            TypstAsLibError::TypstSource(diagnostics) => {
+                TypstJobError::Compile(diagnostics.into_iter().collect())
+            }
+            error => TypstJobError::Compile(vec![SourceDiagnostic::error(
+                Span::detached(),
+                error.to_string(),
+            )]),
+        }
+    }
+
+    /// This error as diagnostics, with compile errors resolved against the sources of `template` when given.
+    pub fn diagnostics(
+        &self,
+        template: Option<&StructuredInMemoryTemplate>,
+    ) -> Vec<TypstDiagnostic> {
+        match (self, template) {
+            (TypstJobError::Compile(diagnostics), Some(template)) => {
+                template.resolve_diagnostics(diagnostics)
+            }
+            (TypstJobError::Compile(diagnostics), None) => diagnostics
+                .iter()
+                .map(|diagnostic| {
+                    TypstDiagnostic::detached(diagnostic.severity, diagnostic.message.as_str())
+                })
+                .collect(),
+            _ => vec![TypstDiagnostic::detached(Severity::Error, self.to_string())],
+        }
+    }
+
+    /// Log this error for a job of `template`.
+    pub(crate) fn log(&self, template: &impl std::fmt::Debug) {
+        match self {
+            TypstJobError::InputConversion(_) => {
+                typst_log!(
+                    Input,
+                    Error,
+                    "[TYPST INPUT ERROR for {:?}] {}",
+                    template,
+                    self
+                )
+            }
+            TypstJobError::Compile(_) => {
+                typst_log!(
+                    Compile,
+                    Error,
+                    "[TYPST FATAL ERROR for {:?}] {}",
+                    template,
+                    self
+                )
+            }
+            _ => typst_log!(Job, Error, "[TYPST JOB ERROR for {:?}] {}", template, self),
+        }
+    }
+}
+
+impl std::fmt::Display for TypstJobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypstJobError::TemplateLoad(error) => write!(f, "TypstJobError::TemplateLoad: {error}"),
+            TypstJobError::InputConversion(error) => {
+                write!(f, "TypstJobError::InputConversion: {error}")
+            }
+            TypstJobError::Compile(diagnostics) => {
+                write!(f, "TypstJobError::Compile:")?;
+                for diagnostic in diagnostics {
+                    write!(f, " {};", diagnostic.message)?;
+                }
+                Ok(())
+            }
+            TypstJobError::Render(error) => write!(f, "TypstJobError::Render: {error}"),
+            TypstJobError::Upload(error) => write!(f, "TypstJobError::Upload: {error}"),
+            TypstJobError::Cancelled => write!(f, "TypstJobError::Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for TypstJobError {}
+
+impl From<TypstInputSchemaError> for TypstJobError {
+    fn from(error: TypstInputSchemaError) -> Self {
+        TypstJobError::InputConversion(TypstInputError::Schema(error))
+    }
+}
+
+impl From<ValueSerializeError> for TypstJobError {
+    fn from(error: ValueSerializeError) -> Self {
+        TypstJobError::InputConversion(TypstInputError::Serialize(error))
+    }
+}
+
+/// The ways a job's input can be unusable, see [`TypstJobError::InputConversion`].
+#[derive(Debug, Clone)]
+pub enum TypstInputError {
+    Serialize(ValueSerializeError),
+    Schema(TypstInputSchemaError),
+}
+
+impl std::fmt::Display for TypstInputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypstInputError::Serialize(error) => write!(f, "{error}"),
+            TypstInputError::Schema(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for TypstInputError {}
@@ -0,0 +1,177 @@
+use std::{path::PathBuf, time::Duration};
+
+use bevy_asset::Handle;
+use bevy_ecs::{
+    change_detection::{DetectChanges, DetectChangesMut},
+    component::Component,
+    system::{Query, Res, ResMut},
+};
+use bevy_image::Image;
+use bevy_time::{Real, Time};
+use typst::foundations::{Dict, IntoValue};
+
+use crate::{
+    PathBufOrTemplate, TypstJobId, TypstJobOptions, TypstRenderInfo, TypstTextureServer,
+    ambient::TypstAmbientInputs,
+};
+
+/// How often a [`TypstLabel`] is re-rendered while its text keeps changing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypstLabelUpdate {
+    /// Re-render once the text hasn't changed for this long.
+    Debounce(Duration),
+    /// Re-render at most once per this long, with a final render once the text stops changing.
+    Throttle(Duration),
+}
+
+impl Default for TypstLabelUpdate {
+    fn default() -> Self {
+        TypstLabelUpdate::Debounce(Duration::from_millis(150))
+    }
+}
+
+/// A line of frequently changing text, such as a chat bubble or name tag, rendered from a template into an image
+/// that stays the same between renders. Changes are batched according to `update` so typing or streaming text
+/// doesn't flood the job queue. The template receives the text under `input_key`. Labels also re-render when
+/// their ambient inputs change.
+///
+/// Without a `Time<Real>` resource, labels re-render in the frame they change.
+#[derive(Debug, Component)]
+pub struct TypstLabel {
+    pub template: PathBuf,
+    pub text: String,
+    /// The input key the text is passed under. Defaults to `"text"`.
+    pub input_key: String,
+    /// Input passed alongside the text, for styling and the like.
+    pub input: Dict,
+    pub options: TypstJobOptions,
+    pub update: TypstLabelUpdate,
+    image: Handle<Image>,
+    info: Handle<TypstRenderInfo>,
+    /// If the label has changed since the last render.
+    dirty: bool,
+    /// When the text last changed.
+    changed_at: Duration,
+    last_render: Option<Duration>,
+    job: Option<TypstJobId>,
+    /// The label's ambient inputs as of the last change to [`TypstAmbientInputs`].
+    ambient: Option<Dict>,
+}
+
+impl TypstLabel {
+    /// A label rendering into a new image, which shows the server's fallback image until the first render.
+    pub fn new(
+        template_server: &TypstTextureServer,
+        template: impl Into<PathBuf>,
+        text: impl Into<String>,
+    ) -> Self {
+        Self {
+            template: template.into(),
+            text: text.into(),
+            input_key: "text".to_owned(),
+            input: Dict::new(),
+            options: TypstJobOptions::default(),
+            update: TypstLabelUpdate::default(),
+            image: template_server
+                .asset_server
+                .add(template_server.fallback.clone()),
+            info: template_server.asset_server.add(TypstRenderInfo::default()),
+            dirty: false,
+            changed_at: Duration::ZERO,
+            last_render: None,
+            job: None,
+            ambient: None,
+        }
+    }
+
+    pub fn with_input(mut self, input: impl Into<Dict>) -> Self {
+        self.input = input.into();
+        self
+    }
+
+    pub fn with_options(mut self, options: TypstJobOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_update(mut self, update: TypstLabelUpdate) -> Self {
+        self.update = update;
+        self
+    }
+
+    /// The image this label renders into.
+    pub fn image(&self) -> Handle<Image> {
+        self.image.clone()
+    }
+
+    /// The render info of this label's latest render.
+    pub fn info(&self) -> Handle<TypstRenderInfo> {
+        self.info.clone()
+    }
+
+    /// If a render is due at `now`, as per `update`.
+    fn is_due(&self, now: Option<Duration>) -> bool {
+        let Some(now) = now else {
+            return true;
+        };
+        match self.update {
+            TypstLabelUpdate::Debounce(debounce) => now.saturating_sub(self.changed_at) >= debounce,
+            TypstLabelUpdate::Throttle(interval) => self
+                .last_render
+                .is_none_or(|last_render| now.saturating_sub(last_render) >= interval),
+        }
+    }
+
+    fn update(
+        &mut self,
+        changed: bool,
+        now: Option<Duration>,
+        template_server: &mut TypstTextureServer,
+    ) {
+        if changed {
+            self.dirty = true;
+            self.changed_at = now.unwrap_or_default();
+        }
+        if !self.dirty || !self.is_due(now) {
+            return;
+        }
+        self.dirty = false;
+        self.last_render = now;
+        if let Some(job) = self.job.take() {
+            template_server.cancel(job);
+        }
+        let mut input = self.input.clone();
+        input.insert(
+            self.input_key.as_str().into(),
+            self.text.as_str().into_value(),
+        );
+        let template =
+            template_server.resolve_template(PathBufOrTemplate::PathBuf(self.template.clone()));
+        self.job = Some(template_server.enqueue_into(
+            template,
+            self.image.clone(),
+            self.info.id(),
+            input,
+            self.options.clone(),
+        ));
+    }
+}
+
+pub(crate) fn system_update_typst_labels(
+    mut labels: Query<&mut TypstLabel>,
+    mut template_server: ResMut<TypstTextureServer>,
+    time: Option<Res<Time<Real>>>,
+    ambient_inputs: Option<Res<TypstAmbientInputs>>,
+) {
+    let now = time.map(|time| time.elapsed());
+    let ambient_inputs = ambient_inputs.filter(|ambient_inputs| ambient_inputs.is_changed());
+    for mut label in &mut labels {
+        let mut changed = label.is_changed();
+        let label = label.bypass_change_detection();
+        if let Some(ambient_inputs) = &ambient_inputs {
+            changed |= ambient_inputs
+                .resolved_changed(&label.options.ambient_overrides, &mut label.ambient);
+        }
+        label.update(changed, now, &mut template_server);
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use typst::foundations::Bytes;
+use zip::ZipArchive;
+
+use crate::{
+    asset_loading::{TypstArchiveLimits, TypstAssetError},
+    file_provider::TypstFileProvider,
+    file_resolver::{open_zip_entry, read_limited},
+    logging::typst_log,
+    shared_files::share_bytes,
+};
+
+/// The binary files of a zip archive that were left compressed while loading a template with
+/// [`crate::asset_loading::TypstTemplateSettings::lazy_members`], decompressed the first time typst asks for
+/// them and kept from then on.
+pub struct LazyZipMembers {
+    archive: Mutex<ZipArchive<Cursor<Arc<[u8]>>>>,
+    /// The index in the archive of each file, by its path relative to the template's root.
+    members: HashMap<PathBuf, usize>,
+    limits: TypstArchiveLimits,
+    password: Option<String>,
+    /// The bytes read while loading plus those decompressed since, counted against
+    /// [`TypstArchiveLimits::max_total_bytes`].
+    total_bytes: Mutex<u64>,
+    decompressed: Mutex<HashMap<usize, Bytes>>,
+}
+
+impl LazyZipMembers {
+    pub(crate) fn new(
+        bytes: Arc<[u8]>,
+        members: HashMap<PathBuf, usize>,
+        limits: &TypstArchiveLimits,
+        password: Option<String>,
+        total_bytes: u64,
+    ) -> Result<Self, TypstAssetError> {
+        let archive = ZipArchive::new(Cursor::new(bytes)).map_err(TypstAssetError::Zip)?;
+        Ok(Self {
+            archive: Mutex::new(archive),
+            members,
+            limits: limits.clone(),
+            password,
+            total_bytes: Mutex::new(total_bytes),
+            decompressed: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// How many of the files have been decompressed so far, out of how many there are.
+    pub fn decompressed_count(&self) -> (usize, usize) {
+        let decompressed = self
+            .decompressed
+            .lock()
+            .map_or(0, |decompressed| decompressed.len());
+        (decompressed, self.members.len())
+    }
+
+    /// Decompress a member, counting the bytes it really decompresses to rather than the size the archive
+    /// declares, so a member can't go over the limits by lying about its size.
+    fn decompress(&self, path: &Path, index: usize) -> Result<Bytes, TypstAssetError> {
+        let mut archive = self.archive.lock().map_err(|_| {
+            TypstAssetError::Io(std::io::Error::other("the archive's lock was poisoned"))
+        })?;
+        let mut total_bytes = self.total_bytes.lock().map_err(|_| {
+            TypstAssetError::Io(std::io::Error::other("the byte count's lock was poisoned"))
+        })?;
+        let mut file = open_zip_entry(&mut archive, index, self.password.as_deref())?;
+        // Only count the member once it's been read within the limits.
+        let mut total = *total_bytes;
+        let buf = read_limited(&mut file, path, &self.limits, &mut total)?;
+        *total_bytes = total;
+        Ok(share_bytes(buf))
+    }
+}
+
+impl TypstFileProvider for LazyZipMembers {
+    fn file(&self, path: &Path) -> Option<Bytes> {
+        let index = *self.members.get(path)?;
+        if let Some(bytes) = self
+            .decompressed
+            .lock()
+            .ok()
+            .and_then(|decompressed| decompressed.get(&index).cloned())
+        {
+            return Some(bytes);
+        }
+        match self.decompress(path, index) {
+            Ok(bytes) => {
+                if let Ok(mut decompressed) = self.decompressed.lock() {
+                    decompressed.insert(index, bytes.clone());
+                }
+                Some(bytes)
+            }
+            Err(error) => {
+                typst_log!(
+                    Template,
+                    Error,
+                    "[TYPST TEMPLATE ERROR] Couldn't decompress '{}': {error}",
+                    path.display()
+                );
+                None
+            }
+        }
+    }
+
+    fn shared_files(&self) -> Vec<Bytes> {
+        self.decompressed
+            .lock()
+            .map(|decompressed| decompressed.values().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl std::fmt::Debug for LazyZipMembers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (decompressed, members) = self.decompressed_count();
+        write!(
+            f,
+            "LazyZipMembers({decompressed} of {members} decompressed)"
+        )
+    }
+}
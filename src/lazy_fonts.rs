@@ -0,0 +1,60 @@
+use std::sync::{Arc, OnceLock};
+
+use typst::{
+    foundations::Bytes,
+    text::{Font, FontInfo},
+};
+
+/// A font of a template loaded with [`crate::asset_loading::TypstTemplateSettings::lazy_fonts`], of which only
+/// the metadata is read while loading. The font itself is parsed the first time an engine is built with it,
+/// and clones share the parsed font, so each is only parsed once however many engines the template builds.
+#[derive(Debug, Clone)]
+pub struct LazyFont {
+    data: Bytes,
+    index: u32,
+    info: FontInfo,
+    font: Arc<OnceLock<Option<Font>>>,
+}
+
+impl LazyFont {
+    /// Every font of a font file's contents, which may be a collection. Empty if typst can't read any of them.
+    pub fn read(data: Bytes) -> Vec<LazyFont> {
+        (0..)
+            .map_while(|index| {
+                let info = FontInfo::new(data.as_slice(), index)?;
+                Some(LazyFont {
+                    data: data.clone(),
+                    index,
+                    info,
+                    font: Arc::default(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn info(&self) -> &FontInfo {
+        &self.info
+    }
+
+    /// The contents of the font file this font is from.
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+
+    /// The index of this font within its file, for collections.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// If the font has been parsed yet.
+    pub fn is_parsed(&self) -> bool {
+        self.font.get().is_some()
+    }
+
+    /// The parsed font, parsing it if it hasn't been already. `None` if typst can't parse it.
+    pub fn font(&self) -> Option<Font> {
+        self.font
+            .get_or_init(|| Font::new(self.data.clone(), self.index))
+            .clone()
+    }
+}
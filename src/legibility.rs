@@ -0,0 +1,111 @@
+use typst::layout::{Frame, FrameItem, Transform};
+
+use crate::logging::typst_log;
+
+/// What to do with a render whose smallest text is below [`TypstMinTextSize::min_px`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TypstMinTextSizeAction {
+    /// Log a warning naming the template, and render as normal.
+    #[default]
+    Warn,
+    /// Raise the job's `pixels_per_pt` so the smallest text meets the minimum, up to `max_pixels_per_pt` if set,
+    /// warning if that still isn't enough. This makes the whole image larger.
+    ScaleUp { max_pixels_per_pt: Option<f32> },
+}
+
+/// A minimum size for rendered text, for legibility requirements such as console certification.
+/// Set through [`crate::TypstJobOptions::min_text_size`].
+///
+/// Text is measured by its font size (the em size) in output pixels, after any scaling or rotation applied to it
+/// in the template, so `min_px: 24.0` requires text that's at least 24 pixels per em in the image. How large that
+/// is on screen also depends on how the image is displayed, which this can't know.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TypstMinTextSize {
+    pub min_px: f32,
+    pub action: TypstMinTextSizeAction,
+}
+
+impl TypstMinTextSize {
+    pub fn warn(min_px: f32) -> Self {
+        Self {
+            min_px,
+            action: TypstMinTextSizeAction::Warn,
+        }
+    }
+
+    pub fn scale_up(min_px: f32) -> Self {
+        Self {
+            min_px,
+            action: TypstMinTextSizeAction::ScaleUp {
+                max_pixels_per_pt: None,
+            },
+        }
+    }
+
+    /// Check the text of a page about to be rendered, warning about text that's too small.
+    /// Returns the `pixels_per_pt` to render with.
+    pub(crate) fn check(
+        &self,
+        frame: &Frame,
+        pixels_per_pt: f32,
+        template: &impl std::fmt::Debug,
+    ) -> f32 {
+        let Some(smallest_pt) = smallest_text_pt(frame) else {
+            return pixels_per_pt;
+        };
+        let smallest_px = smallest_pt as f32 * pixels_per_pt;
+        if smallest_px >= self.min_px {
+            return pixels_per_pt;
+        }
+        let scaled = match self.action {
+            TypstMinTextSizeAction::Warn => pixels_per_pt,
+            TypstMinTextSizeAction::ScaleUp { max_pixels_per_pt } => {
+                let needed = self.min_px / smallest_pt as f32;
+                max_pixels_per_pt.map_or(needed, |max| needed.min(max.max(pixels_per_pt)))
+            }
+        };
+        let scaled_px = smallest_pt as f32 * scaled;
+        if scaled_px < self.min_px {
+            typst_log!(
+                Legibility,
+                Warn,
+                "[TYPST LEGIBILITY WARNING for {:?}] Smallest text is {:.1}px, below the minimum of {:.1}px",
+                template,
+                scaled_px,
+                self.min_px
+            );
+        }
+        scaled
+    }
+}
+
+/// The size in points of the smallest text in a frame, after the transforms of the groups it's in.
+pub fn smallest_text_pt(frame: &Frame) -> Option<f64> {
+    let mut smallest = None;
+    collect_smallest(frame, Transform::identity(), &mut smallest);
+    smallest
+}
+
+fn collect_smallest(frame: &Frame, transform: Transform, smallest: &mut Option<f64>) {
+    for (_, item) in frame.items() {
+        match item {
+            FrameItem::Group(group) => collect_smallest(
+                &group.frame,
+                transform.pre_concat(group.transform),
+                smallest,
+            ),
+            FrameItem::Text(text) => {
+                // The geometric mean of the transform's scale, which is exact for uniform scales and rotations.
+                let scale = (transform.sx.get() * transform.sy.get()
+                    - transform.kx.get() * transform.ky.get())
+                .abs()
+                .sqrt();
+                let size = text.size.to_pt() * scale;
+                if size > 0.0 && smallest.is_none_or(|smallest| size < smallest) {
+                    *smallest = Some(size);
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,34 +1,135 @@
 #![doc = include_str!("../README.md")]
 
 use bevy_app::{Last, Plugin, PreStartup};
-use bevy_asset::{AssetServer, Assets, Handle, RenderAssetUsages};
+use bevy_asset::{
+    Asset, AssetApp, AssetId, AssetLoadError, AssetServer, Assets, Handle, LoadState,
+    RenderAssetUsages, StrongHandle,
+};
 use bevy_ecs::{
+    change_detection::DetectChanges,
+    entity::Entity,
+    message::MessageWriter,
     resource::Resource,
+    schedule::IntoScheduleConfigs,
     system::{Commands, Res, ResMut},
 };
 use bevy_image::Image;
+use bevy_platform::time::Instant;
+use bevy_reflect::TypePath;
 use bevy_tasks::AsyncComputeTaskPool;
+use bevy_time::{Real, Time};
 use serde::Serialize;
-use serde_json::value::Serializer;
 use std::{
     collections::{HashMap, VecDeque},
     ffi::OsStr,
     path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::Duration,
 };
 use typst::{
-    diag::Severity,
+    diag::{Severity, SourceDiagnostic},
     foundations::{Dict, IntoValue},
     layout::PagedDocument,
+    syntax::Span,
+    text::{Font, FontVariant},
 };
 use wgpu_types::{Extent3d, TextureDimension, TextureFormat};
 
 use crate::{
-    asset_loading::{AssetPluginForTypstTextures, TypstTemplate},
+    ambient::TypstAmbientInputs,
+    asset_loading::{
+        AssetPluginForTypstTextures, TypstArchiveLimits, TypstArchivePasswords, TypstTemplate,
+    },
+    builder::TypstJobBuilder,
+    callbacks::TypstJobCallback,
+    data_files::TypstDataFile,
+    dependencies::TypstTemplateDependencies,
+    events::{PendingCompletion, TypstJobCompleted, TypstJobFailed, TypstTemplateReady},
     file_resolver::StructuredInMemoryTemplate,
+    font_config::TypstFontConfig,
+    font_library::{TypstEngineFonts, TypstFontLibrary, TypstJobFont, job_fonts},
+    history::TypstJobHistory,
+    hot_reload::{PendingRerender, RenderRecipe},
+    job_error::TypstJobError,
+    legibility::TypstMinTextSize,
+    logging::{TypstLogConfig, typst_log},
+    metadata::{TypstAutoShrink, TypstSliceBorder, TypstSliceSource},
+    missing_font::{TypstMissingFont, find_missing_fonts},
+    ownership::OwnedRender,
+    raster::{
+        RasterSettings, TypstOrientation, TypstOutputFormat, TypstPostProcess, TypstRgbaBuffer,
+    },
+    state_scope::TypstStateScope,
 };
 
+pub mod ambient;
 pub mod asset_loading;
+#[cfg(feature = "bevy-fonts")]
+pub mod bevy_fonts;
+pub mod binding;
+pub mod builder;
+pub mod callbacks;
+pub mod commands;
+pub mod compare;
+pub mod compose;
+#[cfg(feature = "basis-compression")]
+pub mod compression;
+pub mod data_files;
+pub mod dependencies;
+pub mod diagnostics;
+pub mod directory;
+#[cfg(feature = "embedded-projects")]
+pub mod embed;
+pub mod engine;
+mod error_card;
+pub mod events;
+pub mod file_provider;
 pub mod file_resolver;
+pub mod flipbook;
+pub mod font_config;
+pub mod font_library;
+#[cfg(feature = "font-subsetting")]
+mod font_subset;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod history;
+pub mod hot_reload;
+pub mod input_schema;
+#[cfg(feature = "job-diagnostics")]
+pub mod job_diagnostics;
+pub mod job_error;
+pub mod label;
+pub mod lazy_archive;
+pub mod lazy_fonts;
+pub mod legibility;
+#[cfg(feature = "typst-live-edit")]
+pub mod live_edit;
+pub mod logging;
+#[cfg(feature = "pbr")]
+pub mod material;
+pub mod measure;
+pub mod metadata;
+pub mod missing_font;
+pub mod ownership;
+pub mod prelude;
+pub mod processed;
+pub mod progress;
+pub mod raster;
+pub mod scene;
+pub mod serde_value;
+pub mod shared_files;
+#[cfg(feature = "sprite")]
+pub mod sprite;
+pub mod state_scope;
+pub mod static_document;
+#[cfg(feature = "tar-archives")]
+pub mod tar_archive;
+#[cfg(feature = "ui")]
+pub mod ui;
+pub mod validation;
 
 /// This crate's core plugin. Add this to your app to enable typst-related asset loading, the TypstTextureServer resource, and typst compilation/rasterisation system.
 #[derive(Debug, Clone, Resource, Default)]
@@ -36,25 +137,273 @@ pub struct TypstTexturesPlugin {
     /// Optional limit on the number of rasterization jobs to process every frame.
     /// This can also be modified on the [`TypstTextureServer`] resource itself.
     pub jobs_per_frame: Option<u32>,
+    /// Optional frame time threshold, above which no jobs are processed that frame, other than one job after
+    /// [`TypstTextureServer::max_skipped_frames`] frames in a row were skipped.
+    /// This can also be modified on the [`TypstTextureServer`] resource itself.
+    pub skip_jobs_over_frame_time: Option<Duration>,
+    /// Limits on how much zipped templates may decompress to when loaded.
+    pub archive_limits: TypstArchiveLimits,
+    /// Panic as soon as a template fails to load or compile, to catch broken templates before release.
+    /// See [`TypstTextureServer::fail_fast`].
+    pub fail_fast: bool,
+    /// Which fonts are given to typst and in what order, inserted as a resource that can be changed later.
+    pub font_config: TypstFontConfig,
 }
 
 impl Plugin for TypstTexturesPlugin {
     fn build(&self, app: &mut bevy_app::App) {
-        app.add_plugins(AssetPluginForTypstTextures);
+        app.add_plugins(AssetPluginForTypstTextures {
+            archive_limits: self.archive_limits.clone(),
+        });
+        app.init_asset::<TypstRenderInfo>();
+        app.add_message::<TypstTemplateReady>();
+        app.add_message::<TypstJobCompleted>();
+        app.add_message::<TypstJobFailed>();
+        app.add_message::<TypstMissingFont>();
         app.insert_resource(self.clone());
+        app.init_resource::<TypstRunState>();
+        app.init_resource::<TypstAmbientInputs>();
+        app.init_resource::<TypstLogConfig>();
+        app.init_resource::<TypstFontLibrary>();
+        app.insert_resource(self.font_config.clone());
         app.add_systems(PreStartup, TypstTextureServer::system_insert_to_world)
-            .add_systems(Last, TypstTextureServer::system_do_jobs);
+            .add_systems(
+                bevy_app::First,
+                (
+                    logging::system_apply_log_config,
+                    font_library::system_share_engine_fonts,
+                ),
+            )
+            .add_systems(
+                Last,
+                (
+                    events::system_emit_template_ready,
+                    TypstTextureServer::system_hot_reload,
+                    TypstTextureServer::system_release_owned
+                        .before(TypstTextureServer::system_do_jobs),
+                    TypstTextureServer::system_do_jobs,
+                    TypstTextureServer::system_upload_images,
+                    events::system_emit_job_completed,
+                    callbacks::system_run_job_callbacks.after(events::system_emit_job_completed),
+                    history::system_record_job_history.after(events::system_emit_job_completed),
+                    TypstTextureServer::system_evict_templates,
+                    TypstTextureServer::system_forget_measured_fonts,
+                ),
+            )
+            .add_systems(
+                bevy_app::Update,
+                (
+                    label::system_update_typst_labels,
+                    validation::system_fail_fast_on_broken_templates,
+                    (
+                        flipbook::system_queue_typst_flipbooks,
+                        flipbook::system_queue_typst_flipbook_pages,
+                        flipbook::system_play_typst_flipbooks,
+                    )
+                        .chain(),
+                ),
+            );
+        #[cfg(feature = "sprite")]
+        app.add_systems(
+            bevy_app::Update,
+            flipbook::system_show_typst_flipbook_sprites
+                .after(flipbook::system_play_typst_flipbooks),
+        );
+        #[cfg(feature = "pbr")]
+        app.add_systems(
+            bevy_app::Update,
+            flipbook::system_show_typst_flipbook_materials
+                .after(flipbook::system_play_typst_flipbooks),
+        );
+        #[cfg(feature = "sprite")]
+        app.add_systems(
+            bevy_app::Update,
+            (
+                sprite::system_rescale_dpi_aware_sprites.before(sprite::system_queue_typst_sprites),
+                sprite::system_queue_typst_sprites,
+                sprite::system_update_typst_sprites,
+            ),
+        );
+        #[cfg(feature = "ui")]
+        app.add_systems(
+            bevy_app::Update,
+            (
+                ui::system_queue_typst_ui_nodes,
+                ui::system_resize_typst_ui_nodes,
+                ui::system_update_typst_ui_nodes,
+            ),
+        );
+        #[cfg(feature = "gpu")]
+        app.add_systems(
+            Last,
+            gpu::system_draw_typst_gpu_documents.after(TypstTextureServer::system_do_jobs),
+        );
+        #[cfg(feature = "bevy-fonts")]
+        app.add_systems(bevy_app::Update, bevy_fonts::system_register_bevy_fonts);
+        #[cfg(feature = "pbr")]
+        app.add_systems(
+            bevy_app::Update,
+            (
+                material::system_queue_typst_material_textures,
+                material::system_update_typst_material_textures,
+            ),
+        );
     }
 }
 
 /// The data needed to complete a Typst job.
 #[derive(Debug)]
 pub struct TypstJob {
+    pub id: TypstJobId,
     pub use_template: Handle<TypstTemplate>,
     pub input: Dict,
     pub send_target: async_channel::Sender<bevy_image::Image>,
+    pub info_target: async_channel::Sender<TypstRenderInfo>,
     pub job_options: TypstJobOptions,
-    _handle: Handle<Image>,
+    image: JobImageHandle,
+    /// Shared with the other jobs rendering the same document, so it's only compiled once.
+    shared_compile: Option<SharedCompile>,
+    /// Set when the job is cancelled, so its image closing without a render isn't reported as an error.
+    cancelled: Arc<AtomicBool>,
+}
+
+/// A document compiled by the first of several jobs that render it, such as those of
+/// [`TypstTextureServer::submit_scaled_with_dict_input`] or the pages of a [`flipbook::TypstFlipbook`], for the rest to
+/// rasterize without compiling again.
+#[derive(Clone, Default)]
+struct SharedCompile(Arc<Mutex<Option<CompiledDocument>>>);
+
+#[derive(Clone)]
+struct CompiledDocument {
+    document: PagedDocument,
+    warnings: Vec<SourceDiagnostic>,
+    fitted_scale: Option<f64>,
+}
+
+impl SharedCompile {
+    fn get(&self) -> Option<CompiledDocument> {
+        self.0.lock().ok()?.clone()
+    }
+
+    fn set(&self, compiled: CompiledDocument) {
+        if let Ok(mut shared) = self.0.lock() {
+            *shared = Some(compiled);
+        }
+    }
+}
+
+impl std::fmt::Debug for SharedCompile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let compiled = self.0.lock().is_ok_and(|shared| shared.is_some());
+        write!(f, "SharedCompile(compiled: {compiled})")
+    }
+}
+
+/// The last job submitted with a [`TypstJobOptions::content_key`], with its render info once it has completed.
+#[derive(Debug)]
+struct ContentKeyedJob {
+    hash: u64,
+    target: TypstRenderTarget,
+    info: Option<TypstRenderInfo>,
+}
+
+/// The job's own reference to its image. Strong unless [`TypstJobOptions::weak_handle`] is set.
+#[derive(Debug)]
+enum JobImageHandle {
+    Strong(Handle<Image>),
+    Weak(std::sync::Weak<StrongHandle>, AssetId<Image>),
+}
+
+impl JobImageHandle {
+    fn new(handle: &Handle<Image>, weak: bool) -> Self {
+        match handle {
+            Handle::Strong(strong) if weak => Self::Weak(Arc::downgrade(strong), handle.id()),
+            _ => Self::Strong(handle.clone()),
+        }
+    }
+
+    fn id(&self) -> AssetId<Image> {
+        match self {
+            JobImageHandle::Strong(handle) => handle.id(),
+            JobImageHandle::Weak(_, id) => *id,
+        }
+    }
+
+    /// If every handle to the image outside of the server has been dropped.
+    fn is_dropped(&self) -> bool {
+        matches!(self, JobImageHandle::Weak(weak, _) if weak.strong_count() == 0)
+    }
+}
+
+/// Identifies a job submitted to a [`TypstTextureServer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TypstJobId(pub u64);
+
+/// Everything produced by a job submitted through the `submit_*` methods of [`TypstTextureServer`].
+#[derive(Debug, Clone)]
+pub struct TypstRenderTarget {
+    /// The rendered image, loaded once the job has completed.
+    pub image: Handle<Image>,
+    /// Metadata about the render, loaded alongside the image.
+    pub info: Handle<TypstRenderInfo>,
+    /// The job's id, usable with [`TypstTextureServer::cancel`].
+    pub job: TypstJobId,
+}
+
+/// Renders of the same job at several scales, from [`TypstTextureServer::submit_scaled_with_dict_input`].
+#[derive(Debug, Clone)]
+pub struct TypstScaledRenders {
+    /// Each requested scale with its render, in the order the scales were given.
+    pub renders: Vec<(f32, TypstRenderTarget)>,
+}
+
+impl TypstScaledRenders {
+    /// The render for exactly this scale.
+    pub fn get(&self, scale: f32) -> Option<&TypstRenderTarget> {
+        self.renders
+            .iter()
+            .find(|(render_scale, _)| *render_scale == scale)
+            .map(|(_, target)| target)
+    }
+
+    /// The render with the scale closest to `scale`, i.e. the window's scale factor.
+    pub fn closest(&self, scale: f32) -> Option<&TypstRenderTarget> {
+        self.renders
+            .iter()
+            .min_by(|(a, _), (b, _)| (a - scale).abs().total_cmp(&(b - scale).abs()))
+            .map(|(_, target)| target)
+    }
+}
+
+/// Metadata about a finished render, available as a companion asset to the rendered image.
+/// See [`TypstTextureServer::add_job_with_render_info`].
+#[derive(Debug, Clone, Default, Asset, TypePath)]
+pub struct TypstRenderInfo {
+    /// Total number of pages in the compiled document.
+    pub page_count: usize,
+    /// The index of the page that was rendered, after clamping.
+    pub page_index: usize,
+    /// Width and height of the rendered page in typst `pt`.
+    pub page_size_pt: (f64, f64),
+    /// Width and height of the output image in pixels, including any padding for compressed output.
+    pub size_px: (u32, u32),
+    /// Width and height of the rendered page within the output image in pixels, after orientation. This is
+    /// `size_px` without the padding for compressed output, which is on the right and bottom.
+    pub content_size_px: (u32, u32),
+    /// The `pixels_per_pt` the page was rendered with.
+    pub pixels_per_pt: f32,
+    /// The final scale input used when [`TypstJobOptions::auto_shrink`] is set.
+    pub fitted_scale: Option<f64>,
+    /// 9-slice border insets in output pixels, when [`TypstJobOptions::slice`] is set and could be resolved.
+    pub slice_border: Option<TypstSliceBorder>,
+}
+
+impl TypstRenderInfo {
+    /// Width divided by height of the rendered page as it appears in the output, so rotated by a quarter turn
+    /// if [`TypstJobOptions::orientation`] rotates it.
+    pub fn aspect_ratio(&self) -> f64 {
+        self.content_size_px.0 as f64 / self.content_size_px.1 as f64
+    }
 }
 
 /// Strategies for unifying keys when calling [`TypstTextureServer::add_job_with_dict_and_serde_input`].
@@ -70,6 +419,29 @@ pub enum InputUnifyMode {
     },
 }
 
+/// Queue priority for a typst job. Jobs whose templates haven't loaded yet are moved to the back of the queue regardless of priority.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TypstJobPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// What to do with the warnings typst reports while compiling a job.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TypstWarningPolicy {
+    /// Log warnings, and include them in the job's [`TypstJobCompleted`] message.
+    #[default]
+    Log,
+    /// Drop warnings without logging them or including them in [`TypstJobCompleted`].
+    Ignore,
+    /// Fail the job if there are any warnings, as for a compile error.
+    PromoteToError,
+    /// Include warnings in the job's [`TypstJobCompleted`] message without logging them.
+    Collect,
+}
+
 /// Options for the typst job.
 #[derive(Debug, Clone)]
 pub struct TypstJobOptions {
@@ -77,9 +449,74 @@ pub struct TypstJobOptions {
     pub pixels_per_pt: f32,
     /// Which page to render, defaults to the first page when not specified, and is clamped by the total number of pages in the document.
     pub specific_page: Option<usize>,
+    /// Render the page containing the element with this label, i.e. `"card-back"` for `<card-back>`, instead of `specific_page`.
+    /// If no element has the label then `specific_page` is used as a fallback.
+    pub page_label: Option<String>,
     /// Options to pass to [`Image::asset_usage`], defaults to RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD.
     pub asset_usage: RenderAssetUsages,
     pub input_unify_mode: InputUnifyMode,
+    /// Rotation and flipping applied to the rasterized output. Defaults to no change.
+    pub orientation: TypstOrientation,
+    /// Pixel format of the output image. Defaults to [`TypstOutputFormat::Rgba8`], see [`TypstOutputFormat::Rgba16Float`] for HDR output.
+    pub output_format: TypstOutputFormat,
+    /// What color is multiplied by for [`TypstOutputFormat::Rgba16Float`] output. Intensities above 1 push the
+    /// output past the SDR range, i.e. for bloom. Defaults to `1.`
+    pub hdr_intensity: f32,
+    /// Where the job is placed in the queue, ahead of any queued jobs with a lower priority. Defaults to [`TypstJobPriority::Normal`].
+    pub priority: TypstJobPriority,
+    /// Post-processing applied to the output before it's sent to the asset server. Defaults to `None`.
+    pub post_process: Option<TypstPostProcess>,
+    /// Keep a CPU-side copy of the output's pixels, readable with [`TypstTextureServer::read_pixels`]
+    /// until released with [`TypstTextureServer::release_pixels`]. Defaults to `false`.
+    pub retain_pixels: bool,
+    /// Recompile with progressively smaller scale inputs while the template reports that it overflows. Defaults to `None`.
+    pub auto_shrink: Option<TypstAutoShrink>,
+    /// Compute 9-slice border insets for the output, reported in [`TypstRenderInfo::slice_border`]. Defaults to `None`.
+    pub slice: Option<TypstSliceSource>,
+    /// Ambient inputs for this job only, shadowing the global [`TypstAmbientInputs`] resource.
+    pub ambient_overrides: TypstAmbientInputs,
+    /// Extra files available to this job only, such as `data.json` built from game state with [`TypstDataFile::json`].
+    /// Jobs with data files get their own engine rather than sharing one with other jobs of the same template.
+    pub data_files: Vec<TypstDataFile>,
+    /// Experimental typst compiler features to enable for this job on top of those in the template's `package.toml`,
+    /// see [`file_resolver::BevyTypstDotToml::compiler_features`]. Like `data_files`, this gives the job its own engine.
+    pub compiler_features: Vec<String>,
+    /// Compile this typst file of the template instead of its main file, by its path relative to the template's
+    /// root, as per [`StructuredInMemoryTemplate::with_entry`]. Jobs of the same template and entry share an engine
+    /// within a frame, as jobs of the main file do. Defaults to `None`.
+    pub entry: Option<PathBuf>,
+    /// Extra fonts available to this job only, used over the template's own fonts of the same family and style.
+    /// Like `data_files`, this gives the job its own engine.
+    pub fonts: Vec<TypstJobFont>,
+    /// Don't keep the job's image handle alive from within the server, and cancel the job if every handle to its image
+    /// has been dropped by the time it would be compiled, so textures nobody will see aren't rendered.
+    /// The render target of a job with a `content_key` is still retained. Defaults to `false`.
+    pub weak_handle: bool,
+    /// Marks jobs as re-renders of the same texture, such as one updated from a polled data source. When a job's
+    /// template, input, ambient inputs, and every option that changes its output hash the same as the last job
+    /// submitted with this key, it isn't queued and the previous job's render target is returned instead, unless
+    /// that job failed or was cancelled. Changes to the global [`TypstAmbientInputs`] are compared from the frame
+    /// after they're made. Defaults to `None`.
+    pub content_key: Option<String>,
+    /// The named queue this job runs in, see [`TypstTextureServer::queues`]. Jobs without a queue, or with
+    /// a queue name the server doesn't know, run in the main queue. Defaults to `None`.
+    pub queue: Option<String>,
+    /// Cancel or park this job when a bevy state is exited, as per [`TypstStateScope`]. Defaults to `None`.
+    pub state_scope: Option<TypstStateScope>,
+    /// Warn about, or scale up, text rendered smaller than a minimum size. Defaults to `None`.
+    pub min_text_size: Option<TypstMinTextSize>,
+    /// Cancel this job if the entity has despawned before the job is compiled, so that renders for short-lived
+    /// entities aren't wasted. See [`TypstTextureServer::release_with_entity`] for releasing the render itself.
+    /// Defaults to `None`.
+    pub owner: Option<Entity>,
+    /// If the job fails, render a card listing its errors into its image instead of leaving the fallback image
+    /// in place, so broken templates are visible in-scene while iterating on them. Defaults to `false`.
+    pub error_texture: bool,
+    /// What to do with typst's warnings for this job. Defaults to [`TypstWarningPolicy::Log`].
+    pub warning_policy: TypstWarningPolicy,
+    /// Transcode the rasterized output into a GPU-compressed format before creating the [`Image`]. Defaults to `None`.
+    #[cfg(feature = "basis-compression")]
+    pub compression: Option<compression::TypstTextureCompression>,
 }
 
 impl Default for TypstJobOptions {
@@ -87,20 +524,209 @@ impl Default for TypstJobOptions {
         Self {
             pixels_per_pt: 1.0,
             specific_page: None,
+            page_label: None,
             asset_usage: RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD,
             input_unify_mode: InputUnifyMode::default(),
+            orientation: TypstOrientation::default(),
+            output_format: TypstOutputFormat::default(),
+            hdr_intensity: 1.0,
+            priority: TypstJobPriority::default(),
+            post_process: None,
+            retain_pixels: false,
+            auto_shrink: None,
+            slice: None,
+            ambient_overrides: TypstAmbientInputs::default(),
+            data_files: Vec::new(),
+            compiler_features: Vec::new(),
+            entry: None,
+            fonts: Vec::new(),
+            weak_handle: false,
+            content_key: None,
+            queue: None,
+            state_scope: None,
+            min_text_size: None,
+            owner: None,
+            error_texture: false,
+            warning_policy: TypstWarningPolicy::default(),
+            #[cfg(feature = "basis-compression")]
+            compression: None,
         }
     }
 }
 
+/// Global run state for typst job processing. While [`TypstRunState::Paused`], no queued jobs are compiled or
+/// rasterized, but they stay in the queue and are processed once the state is back to [`TypstRunState::Running`].
+/// This can be used alongside [`TypstTextureServer::pause`], jobs only run when neither is paused.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Resource)]
+pub enum TypstRunState {
+    #[default]
+    Running,
+    Paused,
+}
+
+/// A template, lowercase family and variant a font was resolved for by [`TypstTextureServer::measure_font_text`].
+type MeasuredFontKey = (AssetId<TypstTemplate>, String, FontVariant);
+
 /// Resource to access in systems under `ResMut<TypstTextureServer>` to queue typst jobs. [`TypstTextureServer::add_job`] and [`TypstTextureServer::add_job_with_serde_input`].
 #[derive(Debug, Resource)]
 pub struct TypstTextureServer {
     asset_server: AssetServer,
     pub fallback: Image,
     pub templates: HashMap<PathBuf, Handle<TypstTemplate>>,
+    /// Logical template names, from [`TypstTextureServer::register`].
+    aliases: HashMap<String, PathBufOrTemplate>,
     pub jobs: VecDeque<TypstJob>,
+    /// Jobs held back while the state they're scoped to is inactive, see [`TypstJobOptions::state_scope`].
+    parked_jobs: Vec<TypstJob>,
     pub jobs_per_frame: Option<u32>,
+    /// Limit on the number of rasterized jobs being converted and uploaded on the async compute pool at once.
+    /// While at the limit, queued jobs wait in the queue rather than being compiled. This is separate from
+    /// `jobs_per_frame`, which doesn't account for tasks started on earlier frames that are still running.
+    pub max_in_flight: Option<usize>,
+    /// Tasks currently running on the async compute pool.
+    in_flight: Arc<AtomicUsize>,
+    /// Named queues throttled independently of the main queue and each other, so that i.e. UI text isn't held up
+    /// by background decal generation. Jobs are assigned a queue through [`TypstJobOptions::queue`].
+    pub queues: HashMap<String, TypstJobQueue>,
+    /// When true, queued jobs are held until [`TypstTextureServer::resume`] is called.
+    pub paused: bool,
+    /// When the previous frame took longer than this, skip processing jobs for the current frame
+    /// so that typst work doesn't compound an existing frame time spike.
+    pub skip_jobs_over_frame_time: Option<Duration>,
+    /// After this many frames in a row have skipped queued jobs for being over `skip_jobs_over_frame_time`, one
+    /// job is run anyway, so that a game that's always over the threshold still makes progress on its queue.
+    /// Defaults to 30.
+    pub max_skipped_frames: u32,
+    /// Frames in a row that have skipped queued jobs for being over `skip_jobs_over_frame_time`.
+    skipped_frames: u32,
+    /// Options used by [`TypstTextureServer::add_job_default`], and returned by [`TypstTextureServer::default_options`]
+    /// for use as a base for per-job options.
+    pub default_options: TypstJobOptions,
+    /// Optional policy for automatically unloading templates that haven't been used recently.
+    pub eviction: Option<TypstTemplateEviction>,
+    /// Panic when a template fails to load, or fails to compile once it's loaded. Each template is compiled
+    /// once as it loads (or reloads) with no input besides the ambient inputs, or only has its syntax checked if
+    /// its input schema has required keys. [`TypstTextureServer::preload`] templates at startup so they're all
+    /// checked before they're needed.
+    pub fail_fast: bool,
+    /// When each template last had a job processed, in real time since startup.
+    template_last_used: HashMap<AssetId<TypstTemplate>, Duration>,
+    /// Limit on the bytes of image data handed to the asset server (and so uploaded to the GPU) per frame.
+    /// When `None`, images are handed over as soon as they're rasterized.
+    pub upload_bytes_per_frame: Option<usize>,
+    /// Images waiting for upload when `upload_bytes_per_frame` is set, written from the async task pool.
+    pending_uploads: Arc<Mutex<Vec<PendingUpload>>>,
+    /// Pixels kept for jobs with [`TypstJobOptions::retain_pixels`] set, written from the async task pool.
+    retained_pixels: Arc<Mutex<HashMap<AssetId<Image>, TypstRgbaBuffer>>>,
+    /// What each template used during its last compile.
+    template_dependencies: HashMap<AssetId<TypstTemplate>, TypstTemplateDependencies>,
+    /// When set, jobs are re-run when their template is modified, such as by bevy's asset hot reloading, with the new
+    /// renders written over the original images. Jobs of templates that haven't been modified yet are kept in memory
+    /// for as long as the template is, so this is best left off in release builds. Defaults to `false`.
+    pub hot_reload_jobs: bool,
+    /// How to re-run each template's jobs, kept when `hot_reload_jobs` is set.
+    render_recipes: HashMap<AssetId<TypstTemplate>, Vec<RenderRecipe>>,
+    /// Re-run jobs waiting for their renders, written from the async task pool.
+    pending_rerenders: Vec<PendingRerender>,
+    /// Shared with the template loaders, see [`TypstTextureServer::set_archive_password`].
+    archive_passwords: TypstArchivePasswords,
+    /// Fonts for compiling templates outside of jobs, shared with the static document loader.
+    engine_fonts: TypstEngineFonts,
+    /// Bevy fonts waiting to load before they're added to the [`TypstFontLibrary`].
+    #[cfg(feature = "bevy-fonts")]
+    pending_bevy_fonts: Vec<Handle<bevy_text::Font>>,
+    /// The last job submitted with each [`TypstJobOptions::content_key`].
+    content_hashes: HashMap<String, ContentKeyedJob>,
+    /// A copy of the global [`TypstAmbientInputs`] as of the last [`TypstTextureServer::system_do_jobs`], for the
+    /// content hashes of jobs submitted outside of it.
+    ambient_inputs: TypstAmbientInputs,
+    /// Fonts resolved by [`TypstTextureServer::measure_font_text`], until their template changes.
+    measured_fonts: Mutex<HashMap<MeasuredFontKey, Option<Font>>>,
+    stats: TypstJobStats,
+    /// Jobs run this frame on the main queue and on each named queue, see [`TypstTextureServer::take_job_slot`].
+    jobs_done: u32,
+    queue_jobs_done: HashMap<String, u32>,
+    /// If jobs are held this frame, because processing is paused or the last frame was over
+    /// [`TypstTextureServer::skip_jobs_over_frame_time`].
+    jobs_held: bool,
+    /// Jobs left to run this frame across every queue, when only one is let through despite the frame time.
+    jobs_left: Option<u32>,
+    /// Callbacks waiting on their jobs, from [`TypstTextureServer::on_resolved`].
+    callbacks: HashMap<TypstJobId, TypstJobCallback>,
+    /// Callbacks of jobs that resolved outside of the usual messages, such as cancelled jobs, to be run with
+    /// their outcome.
+    resolved_callbacks: Vec<(TypstJobCallback, Result<TypstRenderInfo, TypstJobError>)>,
+    /// Jobs whose images are on their way to the asset server, for [`TypstJobCompleted`] messages.
+    completing: HashMap<AssetId<Image>, PendingCompletion>,
+    /// Renders to release when their entity despawns, from [`TypstTextureServer::release_with_entity`].
+    owned_renders: HashMap<Entity, Vec<OwnedRender>>,
+    next_job_id: u64,
+}
+
+/// The loading state of a template, from [`TypstTextureServer::template_state`].
+#[derive(Debug, Clone)]
+pub enum TypstTemplateState {
+    Loading,
+    Ready,
+    Failed { error: Arc<AssetLoadError> },
+}
+
+/// Counts a task towards [`TypstTextureServer::max_in_flight`] until it's dropped at the end of the task.
+struct InFlightTask(Arc<AtomicUsize>);
+
+impl InFlightTask {
+    fn start(in_flight: &Arc<AtomicUsize>) -> Self {
+        in_flight.fetch_add(1, Ordering::AcqRel);
+        Self(in_flight.clone())
+    }
+}
+
+impl Drop for InFlightTask {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Totals of the work done by [`TypstTextureServer::system_do_jobs`], since they were last taken with
+/// [`TypstTextureServer::take_stats`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TypstJobStats {
+    /// Jobs compiled, whether or not compilation succeeded.
+    pub compiled: u32,
+    pub compile_time: Duration,
+    /// Pages rasterized, not counting conversion to the output format off-thread.
+    pub rasterized: u32,
+    pub raster_time: Duration,
+    /// Jobs that could share an engine with other jobs of the same template and entry point that frame.
+    pub engine_cache_lookups: u32,
+    /// Jobs that found an engine already built for their template that frame.
+    pub engine_cache_hits: u32,
+}
+
+/// A rasterized image waiting to be handed to the asset server.
+#[derive(Debug)]
+struct PendingUpload {
+    priority: TypstJobPriority,
+    image: Image,
+    sender: async_channel::Sender<Image>,
+}
+
+/// A named job queue of a [`TypstTextureServer`], with its own per-frame job limit and default options.
+#[derive(Debug, Clone, Default)]
+pub struct TypstJobQueue {
+    pub jobs_per_frame: Option<u32>,
+    /// Options returned by [`TypstTextureServer::queue_options`] for this queue.
+    pub default_options: TypstJobOptions,
+}
+
+/// Policy for automatically evicting templates from a [`TypstTextureServer`]. Templates with queued jobs
+/// are never evicted, and evicted templates are only unloaded once no other handles to them exist.
+#[derive(Debug, Clone, Default)]
+pub struct TypstTemplateEviction {
+    /// Evict templates that haven't had a job processed for this long.
+    pub max_idle: Option<Duration>,
+    /// Keep at most this many templates, evicting the least recently used first.
+    pub max_templates: Option<usize>,
 }
 
 impl TypstTextureServer {
@@ -108,80 +734,529 @@ impl TypstTextureServer {
         mut commands: Commands,
         asset_server: Res<AssetServer>,
         plugin_settings: Res<TypstTexturesPlugin>,
+        archive_passwords: Option<Res<TypstArchivePasswords>>,
+        engine_fonts: Option<Res<TypstEngineFonts>>,
     ) {
         let mut typst_template_server = Self::new(asset_server.clone());
+        if let Some(archive_passwords) = archive_passwords {
+            typst_template_server.archive_passwords = archive_passwords.clone();
+        }
+        if let Some(engine_fonts) = engine_fonts {
+            typst_template_server.engine_fonts = engine_fonts.clone();
+        }
         typst_template_server.jobs_per_frame = plugin_settings.jobs_per_frame;
+        typst_template_server.skip_jobs_over_frame_time = plugin_settings.skip_jobs_over_frame_time;
+        typst_template_server.fail_fast = plugin_settings.fail_fast;
         commands.remove_resource::<TypstTexturesPlugin>();
         commands.insert_resource(typst_template_server);
     }
 
     /// Runs in `Last`. Exposed here to allow for specific scheduling on the user's part.
+    #[allow(clippy::too_many_arguments)]
     pub fn system_do_jobs(
         mut template_server: ResMut<TypstTextureServer>,
         templates: Res<Assets<TypstTemplate>>,
+        run_state: Option<Res<TypstRunState>>,
+        time: Option<Res<Time<Real>>>,
+        ambient_inputs: Option<Res<TypstAmbientInputs>>,
+        mut failed: MessageWriter<TypstJobFailed>,
+        mut history: Option<ResMut<TypstJobHistory>>,
+        font_library: Option<Res<TypstFontLibrary>>,
+        font_config: Option<Res<TypstFontConfig>>,
+        mut missing_fonts: MessageWriter<TypstMissingFont>,
     ) {
-        let max_jobs = template_server
-            .jobs_per_frame
-            .unwrap_or(template_server.jobs.len() as u32);
-        let mut jobs_done = 0;
+        if let Some(ambient_inputs) = &ambient_inputs
+            && ambient_inputs.is_changed()
+        {
+            template_server.ambient_inputs = (**ambient_inputs).clone();
+        }
+        template_server.jobs_done = 0;
+        template_server.queue_jobs_done.clear();
+        template_server.jobs_left = None;
+        let paused = template_server.paused
+            || run_state.is_some_and(|run_state| *run_state == TypstRunState::Paused);
+        let over_frame_time = template_server
+            .skip_jobs_over_frame_time
+            .is_some_and(|threshold| time.as_ref().is_some_and(|time| time.delta() > threshold));
+        if paused || !over_frame_time || template_server.jobs.is_empty() {
+            template_server.skipped_frames = 0;
+        } else if template_server.skipped_frames >= template_server.max_skipped_frames {
+            template_server.skipped_frames = 0;
+            template_server.jobs_left = Some(1);
+        } else {
+            template_server.skipped_frames += 1;
+        }
+        template_server.jobs_held =
+            paused || (over_frame_time && template_server.jobs_left.is_none());
+        if template_server.jobs_held {
+            return;
+        }
+        let mut over_budget = VecDeque::new();
         let mut compiled_map = HashMap::new();
-        while jobs_done < max_jobs
-            && let Some(job) = template_server.jobs.pop_front()
+        let engine_fonts = template_server.engine_fonts.clone();
+        let library_fonts = font_library
+            .as_ref()
+            .map_or(&[][..], |library| library.fonts());
+        let default_font_config = TypstFontConfig::default();
+        let font_config = font_config.as_deref().unwrap_or(&default_font_config);
+        let mut remaining = template_server.jobs.len();
+        while remaining > 0
+            && let Some(mut job) = template_server.jobs.pop_front()
         {
+            remaining -= 1;
+            if job.image.is_dropped() {
+                template_server.job_cancelled(job);
+                continue;
+            }
+            if template_server
+                .max_in_flight
+                .is_some_and(|max| template_server.in_flight.load(Ordering::Acquire) >= max)
+            {
+                over_budget.push_back(job);
+                continue;
+            }
+            if !template_server.take_job_slot(job.job_options.queue.as_deref()) {
+                over_budget.push_back(job);
+                continue;
+            }
             if template_server.asset_server.is_loaded(&job.use_template)
                 && let Some(template) = templates.get(&job.use_template)
             {
-                let (engine, _) = compiled_map
-                    .entry(job.use_template.clone())
-                    .or_insert_with(|| template.0.clone().to_engine());
-                let compiled = engine.compile_with_input::<_, PagedDocument>(job.input);
-                let path = job.use_template.path();
-                let Ok(page) = compiled.output else {
-                    bevy_log::error!(
-                        "[TYPST FATAL ERROR for {:?}] {}",
-                        path,
-                        compiled.output.unwrap_err()
+                let started = Instant::now();
+                if let Some(history) = &mut history {
+                    history.record_input(job.id, &job.input);
+                }
+                if let Some(time) = &time {
+                    template_server
+                        .template_last_used
+                        .insert(job.use_template.id(), time.elapsed());
+                }
+                if let Err(error) = template.0.loaded_toml.inputs.validate(&job.input) {
+                    let error = TypstJobError::from(error);
+                    job.fail(
+                        &mut failed,
+                        &engine_fonts,
+                        job.use_template.path(),
+                        error,
+                        Some(&template.0),
                     );
                     continue;
+                }
+                let shared = job.shared_compile.as_ref().and_then(SharedCompile::get);
+                let (page, mut warnings, fitted_scale, compile_time) = match shared {
+                    Some(compiled) => (
+                        compiled.document,
+                        compiled.warnings,
+                        compiled.fitted_scale,
+                        Duration::ZERO,
+                    ),
+                    None => {
+                        let job_engine;
+                        let engine = if job.job_options.data_files.is_empty()
+                            && job.job_options.compiler_features.is_empty()
+                            && job.job_options.fonts.is_empty()
+                        {
+                            template_server.stats.engine_cache_lookups += 1;
+                            let key = (job.use_template.clone(), job.job_options.entry.clone());
+                            if compiled_map.contains_key(&key) {
+                                template_server.stats.engine_cache_hits += 1;
+                            }
+                            compiled_map
+                                .entry(key)
+                                .or_insert_with(|| {
+                                    let _span = bevy_log::info_span!(
+                                        "typst_engine_build",
+                                        job = job.id.0,
+                                        template = ?job.use_template.path()
+                                    )
+                                    .entered();
+                                    with_job_entry(&template.0, job.job_options.entry.as_deref())
+                                        .map(|job_template| {
+                                            job_template
+                                                .to_engine_with_fonts(font_config, library_fonts)
+                                                .0
+                                        })
+                                })
+                                .as_ref()
+                        } else {
+                            let _span = bevy_log::info_span!(
+                                "typst_engine_build",
+                                job = job.id.0,
+                                template = ?job.use_template.path()
+                            )
+                            .entered();
+                            job_engine = with_job_entry(
+                                &template.0,
+                                job.job_options.entry.as_deref(),
+                            )
+                            .map(|mut job_template| {
+                                if !job.job_options.fonts.is_empty() {
+                                    let mut fonts =
+                                        job_fonts(&job.job_options.fonts, library_fonts);
+                                    fonts.append(&mut job_template.loaded_fonts);
+                                    job_template.loaded_fonts = fonts;
+                                }
+                                job_template
+                                    .with_data_files(&job.job_options.data_files)
+                                    .with_compiler_features(&job.job_options.compiler_features)
+                                    .to_engine_with_fonts(font_config, library_fonts)
+                                    .0
+                            });
+                            job_engine.as_ref()
+                        };
+                        let Some(engine) = engine else {
+                            let entry = job.job_options.entry.as_deref().unwrap_or(Path::new(""));
+                            let error = TypstJobError::Compile(vec![SourceDiagnostic::error(
+                                Span::detached(),
+                                format!("the template has no entry point '{}'", entry.display()),
+                            )]);
+                            job.fail(
+                                &mut failed,
+                                &engine_fonts,
+                                job.use_template.path(),
+                                error,
+                                Some(&template.0),
+                            );
+                            continue;
+                        };
+                        template.0.file_accesses.clear();
+                        let mut input = std::mem::take(&mut job.input);
+                        let ambient = match &ambient_inputs {
+                            Some(global) => global.shadowed_by(&job.job_options.ambient_overrides),
+                            None => job.job_options.ambient_overrides.clone(),
+                        };
+                        ambient.insert_into(&mut input);
+                        let compile_started = Instant::now();
+                        let compile_span = bevy_log::info_span!(
+                            "typst_compile",
+                            job = job.id.0,
+                            template = ?job.use_template.path()
+                        )
+                        .entered();
+                        let (compiled, fitted_scale) = match &job.job_options.auto_shrink {
+                            Some(auto_shrink) => {
+                                let (compiled, scale) = auto_shrink.compile_fitted(engine, input);
+                                (compiled, Some(scale))
+                            }
+                            None => (engine.compile_with_input::<_, PagedDocument>(input), None),
+                        };
+                        drop(compile_span);
+                        let compile_time = compile_started.elapsed();
+                        template_server.stats.compiled += 1;
+                        template_server.stats.compile_time += compile_time;
+                        let page = match compiled.output {
+                            Ok(page) => page,
+                            Err(error) => {
+                                let error = TypstJobError::compile(error);
+                                job.fail(
+                                    &mut failed,
+                                    &engine_fonts,
+                                    job.use_template.path(),
+                                    error,
+                                    Some(&template.0),
+                                );
+                                continue;
+                            }
+                        };
+                        let dependencies = TypstTemplateDependencies::collect(
+                            &template.0.file_accesses.accessed(),
+                            &page,
+                        );
+                        template_server
+                            .template_dependencies
+                            .insert(job.use_template.id(), dependencies);
+                        let warnings = compiled.warnings.to_vec();
+                        if let Some(shared) = &job.shared_compile {
+                            shared.set(CompiledDocument {
+                                document: page.clone(),
+                                warnings: warnings.clone(),
+                                fitted_scale,
+                            });
+                        }
+                        (page, warnings, fitted_scale, compile_time)
+                    }
                 };
-                for warning in compiled.warnings {
-                    if warning.severity == Severity::Error {
-                        bevy_log::error!("[TYPST ERROR for {:?}] {}", path, warning.message);
-                    } else {
-                        bevy_log::warn!("[TYPST WARNING for {:?}] {}", path, warning.message);
+                let path = job.use_template.path();
+                for (family, missing_chars) in find_missing_fonts(&warnings, &page) {
+                    if !missing_chars.is_empty() {
+                        typst_log!(
+                            Warning,
+                            Warn,
+                            "[TYPST WARNING for {:?}] No font has glyphs for {:?}, drawn with {}",
+                            path,
+                            missing_chars.iter().collect::<String>(),
+                            family
+                        );
                     }
+                    missing_fonts.write(TypstMissingFont {
+                        job: job.id,
+                        template: path.map(|path| path.clone_owned()),
+                        family,
+                        missing_chars,
+                    });
                 }
-                let rendered = typst_render::render(
-                    &page.pages[job
+                match job.job_options.warning_policy {
+                    TypstWarningPolicy::Log => {
+                        for warning in &warnings {
+                            if warning.severity == Severity::Error {
+                                typst_log!(
+                                    Warning,
+                                    Error,
+                                    "[TYPST ERROR for {:?}] {}",
+                                    path,
+                                    warning.message
+                                );
+                            } else {
+                                typst_log!(
+                                    Warning,
+                                    Warn,
+                                    "[TYPST WARNING for {:?}] {}",
+                                    path,
+                                    warning.message
+                                );
+                            }
+                        }
+                    }
+                    TypstWarningPolicy::Ignore => warnings.clear(),
+                    TypstWarningPolicy::PromoteToError if !warnings.is_empty() => {
+                        let error = TypstJobError::Compile(warnings);
+                        job.fail(&mut failed, &engine_fonts, path, error, Some(&template.0));
+                        continue;
+                    }
+                    TypstWarningPolicy::PromoteToError | TypstWarningPolicy::Collect => {}
+                }
+                let labelled_page = job.job_options.page_label.as_ref().and_then(|label| {
+                    let labelled_page = metadata::page_of_label(&page, label);
+                    if labelled_page.is_none() {
+                        typst_log!(
+                            Job,
+                            Warn,
+                            "[TYPST WARNING for {:?}] No page contains the label <{}>",
+                            path,
+                            label
+                        );
+                    }
+                    labelled_page
+                });
+                let page_index = labelled_page
+                    .or(job.job_options.specific_page)
+                    .map(|page_num| (page.pages.len().saturating_sub(1)).min(page_num))
+                    .unwrap_or(0);
+                let Some(rendered_page) = page.pages.get(page_index) else {
+                    let error = TypstJobError::Render("the document has no pages".to_owned());
+                    job.fail(&mut failed, &engine_fonts, path, error, Some(&template.0));
+                    continue;
+                };
+                let pixels_per_pt = match &job.job_options.min_text_size {
+                    Some(min_text_size) => min_text_size.check(
+                        &rendered_page.frame,
+                        job.job_options.pixels_per_pt,
+                        &path,
+                    ),
+                    None => job.job_options.pixels_per_pt,
+                };
+                let raster_started = Instant::now();
+                let raster_span = bevy_log::info_span!(
+                    "typst_rasterize",
+                    job = job.id.0,
+                    template = ?path
+                )
+                .entered();
+                let rendered = typst_render::render(rendered_page, pixels_per_pt);
+                drop(raster_span);
+                let raster_time = raster_started.elapsed();
+                template_server.stats.rasterized += 1;
+                template_server.stats.raster_time += raster_time;
+                let orientation = job.job_options.orientation;
+                let settings = RasterSettings::from_options(&job.job_options);
+                let page_size = rendered_page.frame.size();
+                let size_px = settings.output_size(rendered.width(), rendered.height());
+                let (page_width, page_height) =
+                    orientation.output_size(rendered.width(), rendered.height());
+                let info = TypstRenderInfo {
+                    page_count: page.pages.len(),
+                    page_index,
+                    page_size_pt: (page_size.x.to_pt(), page_size.y.to_pt()),
+                    size_px,
+                    content_size_px: (page_width, page_height),
+                    pixels_per_pt,
+                    fitted_scale,
+                    slice_border: job
                         .job_options
-                        .specific_page
-                        .map(|page_num| (page.pages.len().saturating_sub(1)).min(page_num))
-                        .unwrap_or(0)],
-                    job.job_options.pixels_per_pt,
+                        .slice
+                        .as_ref()
+                        .and_then(|slice| slice.resolve(&page, pixels_per_pt, orientation))
+                        .map(|border| TypstSliceBorder {
+                            // Padding for compression is on the right and bottom, outside of the page.
+                            right: border.right + (size_px.0 - page_width) as f32,
+                            bottom: border.bottom + (size_px.1 - page_height) as f32,
+                            ..border
+                        }),
+                };
+                template_server.completing.insert(
+                    job.image.id(),
+                    PendingCompletion {
+                        job: job.id,
+                        template: path.map(|path| path.clone_owned()),
+                        started,
+                        compile_time,
+                        raster_time,
+                        info: info.clone(),
+                        warnings,
+                    },
                 );
-                let asset_usage = job.job_options.asset_usage;
                 let sender = job.send_target.clone();
+                let info_sender = job.info_target.clone();
+                let retained_pixels = template_server.retained_pixels.clone();
+                let image_id = job.image.id();
+                let convert_span = bevy_log::info_span!(
+                    "typst_convert",
+                    job = job.id.0,
+                    template = ?path
+                );
+                let pending_uploads = template_server
+                    .upload_bytes_per_frame
+                    .is_some()
+                    .then(|| template_server.pending_uploads.clone());
+                let priority = job.job_options.priority;
+                let in_flight = InFlightTask::start(&template_server.in_flight);
                 AsyncComputeTaskPool::get()
                     .spawn(async move {
-                        sender
-                            .send(bevy_image::Image::new(
-                                Extent3d {
-                                    width: rendered.width(),
-                                    height: rendered.height(),
-                                    depth_or_array_layers: 1,
-                                },
-                                TextureDimension::D2,
-                                rendered.data().to_vec(),
-                                TextureFormat::Rgba8UnormSrgb,
-                                asset_usage,
-                            ))
-                            .await
+                        let _in_flight = in_flight;
+                        let _ = info_sender.send(info).await;
+                        let (image, retained) =
+                            convert_span.in_scope(|| settings.build_image(rendered));
+                        if let Some(retained) = retained
+                            && let Ok(mut retained_pixels) = retained_pixels.lock()
+                        {
+                            retained_pixels.insert(image_id, retained);
+                        }
+                        if let Some(pending_uploads) = pending_uploads
+                            && let Ok(mut pending_uploads) = pending_uploads.lock()
+                        {
+                            pending_uploads.push(PendingUpload {
+                                priority,
+                                image,
+                                sender,
+                            });
+                            return Ok(());
+                        }
+                        sender.send(image).await
                     })
                     .detach();
+            } else if let Some(LoadState::Failed(error)) = template_server
+                .asset_server
+                .get_load_state(&job.use_template)
+            {
+                if let Some(history) = &mut history {
+                    history.record_input(job.id, &job.input);
+                }
+                let error = TypstJobError::TemplateLoad(error);
+                job.fail(
+                    &mut failed,
+                    &engine_fonts,
+                    job.use_template.path(),
+                    error,
+                    None,
+                );
             } else {
                 template_server.jobs.push_back(job);
             }
-            jobs_done += 1;
+        }
+        over_budget.append(&mut template_server.jobs);
+        template_server.jobs = over_budget;
+    }
+
+    /// Count a job towards this frame's limit of its queue, or of the main queue when `queue` is `None` or not
+    /// one of [`TypstTextureServer::queues`]. `false` if jobs are held this frame or the limit has been reached,
+    /// in which case the job should wait for a later frame.
+    pub(crate) fn take_job_slot(&mut self, queue: Option<&str>) -> bool {
+        if self.jobs_held || self.jobs_left == Some(0) {
+            return false;
+        }
+        let (done, limit) = match queue.and_then(|name| Some((name, self.queues.get(name)?))) {
+            Some((name, queue)) => (
+                self.queue_jobs_done.entry(name.to_owned()).or_default(),
+                queue.jobs_per_frame,
+            ),
+            None => (&mut self.jobs_done, self.jobs_per_frame),
+        };
+        if limit.is_some_and(|limit| *done >= limit) {
+            return false;
+        }
+        *done += 1;
+        if let Some(jobs_left) = &mut self.jobs_left {
+            *jobs_left -= 1;
+        }
+        true
+    }
+
+    /// Runs in `Last`, handing finished images to the asset server within the [`TypstTextureServer::upload_bytes_per_frame`]
+    /// budget, highest priority first. At least one image is handed over each frame, however large it is.
+    pub fn system_upload_images(template_server: Res<TypstTextureServer>) {
+        let Some(budget) = template_server.upload_bytes_per_frame else {
+            return;
+        };
+        let Ok(mut pending_uploads) = template_server.pending_uploads.lock() else {
+            return;
+        };
+        // Stable sort, so uploads of equal priority stay in the order they finished.
+        pending_uploads.sort_by_key(|upload| std::cmp::Reverse(upload.priority));
+        let mut uploaded_bytes = 0;
+        let mut uploaded = 0;
+        for upload in pending_uploads.iter() {
+            let size = upload.image.data.as_ref().map_or(0, Vec::len);
+            if uploaded > 0 && uploaded_bytes + size > budget {
+                break;
+            }
+            uploaded_bytes += size;
+            uploaded += 1;
+        }
+        for upload in pending_uploads.drain(..uploaded) {
+            let _ = upload.sender.try_send(upload.image);
+        }
+    }
+
+    /// Runs in `Last`, applying the [`TypstTextureServer::eviction`] policy if there is one.
+    pub fn system_evict_templates(
+        mut template_server: ResMut<TypstTextureServer>,
+        time: Option<Res<Time<Real>>>,
+    ) {
+        let Some(eviction) = template_server.eviction.clone() else {
+            return;
+        };
+        let now = time.map(|time| time.elapsed()).unwrap_or_default();
+        let mut candidates: Vec<(PathBuf, Duration)> = template_server
+            .templates
+            .iter()
+            .filter(|(_, handle)| !template_server.has_queued_jobs(handle))
+            .map(|(path, handle)| {
+                let last_used = template_server
+                    .template_last_used
+                    .get(&handle.id())
+                    .copied()
+                    .unwrap_or(now);
+                (path.clone(), last_used)
+            })
+            .collect();
+        candidates.sort_by_key(|(_, last_used)| *last_used);
+        let mut over_limit = eviction
+            .max_templates
+            .map(|max_templates| {
+                template_server
+                    .templates
+                    .len()
+                    .saturating_sub(max_templates)
+            })
+            .unwrap_or(0);
+        for (path, last_used) in candidates {
+            let idle = eviction
+                .max_idle
+                .is_some_and(|max_idle| now.saturating_sub(last_used) > max_idle);
+            if over_limit > 0 || idle {
+                over_limit = over_limit.saturating_sub(1);
+                template_server.unload_template(&path);
+            }
         }
     }
 
@@ -209,8 +1284,45 @@ impl TypstTextureServer {
             asset_server,
             fallback,
             templates: HashMap::new(),
+            aliases: HashMap::new(),
             jobs: VecDeque::new(),
+            parked_jobs: Vec::new(),
             jobs_per_frame: None,
+            max_in_flight: None,
+            in_flight: Arc::default(),
+            queues: HashMap::new(),
+            paused: false,
+            skip_jobs_over_frame_time: None,
+            max_skipped_frames: 30,
+            skipped_frames: 0,
+            default_options: TypstJobOptions::default(),
+            eviction: None,
+            fail_fast: false,
+            template_last_used: HashMap::new(),
+            template_dependencies: HashMap::new(),
+            retained_pixels: Arc::default(),
+            upload_bytes_per_frame: None,
+            pending_uploads: Arc::default(),
+            hot_reload_jobs: false,
+            render_recipes: HashMap::new(),
+            pending_rerenders: Vec::new(),
+            archive_passwords: TypstArchivePasswords::default(),
+            engine_fonts: TypstEngineFonts::default(),
+            #[cfg(feature = "bevy-fonts")]
+            pending_bevy_fonts: Vec::new(),
+            content_hashes: HashMap::new(),
+            ambient_inputs: TypstAmbientInputs::default(),
+            measured_fonts: Mutex::default(),
+            owned_renders: HashMap::new(),
+            completing: HashMap::new(),
+            stats: TypstJobStats::default(),
+            jobs_done: 0,
+            queue_jobs_done: HashMap::new(),
+            jobs_held: false,
+            jobs_left: None,
+            callbacks: HashMap::new(),
+            resolved_callbacks: Vec::new(),
+            next_job_id: 0,
         }
     }
 
@@ -223,7 +1335,28 @@ impl TypstTextureServer {
         path: impl Into<PathBufOrTemplate>,
         options: TypstJobOptions,
     ) -> Handle<Image> {
-        self.add_job_with_dict_input(path, Dict::default(), options)
+        self.submit(path, options).image
+    }
+
+    /// Start building a job for the given template, starting from this server's default options.
+    /// Finish with [`TypstJobBuilder::spawn`] to queue it.
+    pub fn job(&mut self, path: impl Into<PathBufOrTemplate>) -> TypstJobBuilder<'_> {
+        TypstJobBuilder::new(self, path.into())
+    }
+
+    /// Add a typst job to the queue, as per [`TypstTextureServer::add_job`], using this server's default options.
+    pub fn add_job_default(&mut self, path: impl Into<PathBufOrTemplate>) -> Handle<Image> {
+        self.add_job(path, self.default_options())
+    }
+
+    /// Set the options used by [`TypstTextureServer::add_job_default`] and returned by [`TypstTextureServer::default_options`].
+    pub fn set_default_options(&mut self, options: TypstJobOptions) {
+        self.default_options = options;
+    }
+
+    /// A copy of this server's default job options, i.e. for `TypstJobOptions { specific_page: Some(1), ..server.default_options() }`.
+    pub fn default_options(&self) -> TypstJobOptions {
+        self.default_options.clone()
     }
 
     /// Add a typst job to the queue, as per [`TypstTextureServer::add_job`], but with a dictionary as input,
@@ -234,97 +1367,206 @@ impl TypstTextureServer {
         input: impl Into<Dict>,
         options: TypstJobOptions,
     ) -> Handle<Image> {
-        let asset_server = self.asset_server.clone();
-        let path_or_template: PathBufOrTemplate = path.into();
-        let template = match path_or_template {
-            PathBufOrTemplate::PathBuf(path_buf) => self
-                .templates
-                .entry(path_buf.clone())
-                .or_insert_with(|| asset_server.load(path_buf))
-                .clone(),
-            PathBufOrTemplate::NewTemplate(structured_in_memory_template) => self
-                .templates
-                .entry(structured_in_memory_template.path_given.clone())
-                .insert_entry(asset_server.add(TypstTemplate(structured_in_memory_template)))
-                .get()
-                .clone(),
-            PathBufOrTemplate::ExistingTemplate(handle) => handle,
-        };
+        self.submit_with_dict_input(path, input, options).image
+    }
+
+    /// Add a typst job to the queue, as per [`TypstTextureServer::add_job_with_dict_input`], but also
+    /// returning a [`Handle<TypstRenderInfo>`] that resolves alongside the image with the page count and sizes.
+    pub fn add_job_with_render_info(
+        &mut self,
+        path: impl Into<PathBufOrTemplate>,
+        input: impl Into<Dict>,
+        options: TypstJobOptions,
+    ) -> (Handle<Image>, Handle<TypstRenderInfo>) {
+        let target = self.submit_with_dict_input(path, input, options);
+        (target.image, target.info)
+    }
+
+    /// Add a typst job to the queue, with both a Serde and Dict input type, unified together as a single dict.
+    /// If your inputs share any keys, be sure to understand which [`InputUnifyMode`] is relevant to what you want, the default being [`InputUnifyMode::SerdeOverridesDict`].
+    pub fn add_job_with_dict_and_serde_input(
+        &mut self,
+        path: impl Into<PathBufOrTemplate>,
+        input_serde: impl Serialize,
+        input_dict: impl Into<Dict>,
+        options: TypstJobOptions,
+    ) -> Handle<Image> {
+        self.submit_with_dict_and_serde_input(path, input_serde, input_dict, options)
+            .image
+    }
+
+    /// Add a typst job to the queue with input, as per [`TypstTextureServer::add_job_with_dict_input`],
+    /// but with the input generated from a serde serializable type.
+    /// This method overrides the `input_unify_mode` of your job options, setting it to [`InputUnifyMode::SerdeOverridesDict`].
+    pub fn add_job_with_serde_input(
+        &mut self,
+        path: impl Into<PathBufOrTemplate>,
+        input: impl Serialize,
+        options: TypstJobOptions,
+    ) -> Handle<Image> {
+        self.submit_with_serde_input(path, input, options).image
+    }
+
+    /// Add a typst job compiled directly from a typst source string, registered as a template under `name`.
+    /// Calling this again with the same `name` replaces the previously registered template.
+    pub fn add_job_from_source(
+        &mut self,
+        name: &str,
+        source: impl Into<String>,
+        options: TypstJobOptions,
+    ) -> Handle<Image> {
+        self.add_job(
+            StructuredInMemoryTemplate::from_source(name, source),
+            options,
+        )
+    }
+
+    /// Submit a typst job to the queue, as per [`TypstTextureServer::add_job`], returning a
+    /// [`TypstRenderTarget`] with the image, its render info, and the job's id.
+    pub fn submit(
+        &mut self,
+        path: impl Into<PathBufOrTemplate>,
+        options: TypstJobOptions,
+    ) -> TypstRenderTarget {
+        self.submit_with_dict_input(path, Dict::default(), options)
+    }
+
+    /// Submit a typst job with a dictionary as input, as per [`TypstTextureServer::add_job_with_dict_input`].
+    pub fn submit_with_dict_input(
+        &mut self,
+        path: impl Into<PathBufOrTemplate>,
+        input: impl Into<Dict>,
+        options: TypstJobOptions,
+    ) -> TypstRenderTarget {
+        let template = self.resolve_template(path.into());
+        self.submit_shared(template, input.into(), options, None)
+    }
+
+    /// Submit a job, compiling its document with any other jobs given the same [`SharedCompile`].
+    fn submit_shared(
+        &mut self,
+        template: Handle<TypstTemplate>,
+        input: Dict,
+        options: TypstJobOptions,
+        shared_compile: Option<SharedCompile>,
+    ) -> TypstRenderTarget {
+        let content_hash = options.content_key.as_ref().map(|key| {
+            let ambient = self.ambient_inputs.shadowed_by(&options.ambient_overrides);
+            (
+                key.clone(),
+                content_hash(&template, &input, &ambient, &options),
+            )
+        });
+        if let Some((key, hash)) = &content_hash
+            && let Some(last) = self.content_hashes.get(key)
+            && last.hash == *hash
+        {
+            return last.target.clone();
+        }
         let (sender, receiver) = async_channel::unbounded::<bevy_image::Image>();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let job_cancelled = cancelled.clone();
         let handle: Handle<Image> = self.asset_server.add_async(async move {
             let res = receiver.recv().await;
-            if let Err(res) = &res {
-                bevy_log::error!("[TYPST ASYNC JOB ERROR] {res}")
+            if let Err(error) = &res
+                && !job_cancelled.load(Ordering::Acquire)
+            {
+                typst_log!(
+                    Job,
+                    Error,
+                    "[TYPST ASYNC JOB ERROR] {}",
+                    TypstJobError::Upload(error.to_string())
+                )
             }
             res
         });
-        self.jobs.push_back(TypstJob {
+        let (info_sender, info_receiver) = async_channel::bounded::<TypstRenderInfo>(1);
+        let info_handle: Handle<TypstRenderInfo> = self
+            .asset_server
+            .add_async(async move { info_receiver.recv().await });
+        let id = TypstJobId(self.next_job_id);
+        self.next_job_id += 1;
+        self.record_recipe(
+            &template,
+            RenderRecipe {
+                image: handle.id(),
+                info: info_handle.id(),
+                input: input.clone(),
+                options: options.clone(),
+            },
+        );
+        let image = JobImageHandle::new(&handle, options.weak_handle);
+        self.enqueue(TypstJob {
+            id,
             use_template: template.clone(),
-            input: input.into(),
+            input,
             send_target: sender,
+            info_target: info_sender,
             job_options: options,
-            _handle: handle.clone(),
+            image,
+            shared_compile,
+            cancelled,
         });
-        handle
+        let target = TypstRenderTarget {
+            image: handle,
+            info: info_handle,
+            job: id,
+        };
+        if let Some((key, hash)) = content_hash {
+            self.content_hashes.insert(
+                key,
+                ContentKeyedJob {
+                    hash,
+                    target: target.clone(),
+                    info: None,
+                },
+            );
+        }
+        target
     }
 
-    /// Add a typst job to the queue, with both a Serde and Dict input type, unified together as a single dict.
-    /// If your inputs share any keys, be sure to understand which [`InputUnifyMode`] is relevant to what you want, the default being [`InputUnifyMode::SerdeOverridesDict`].
-    pub fn add_job_with_dict_and_serde_input(
+    /// Forget the last job submitted with a [`TypstJobOptions::content_key`], so the next job with that key is
+    /// always queued and the previous render can be dropped.
+    pub fn forget_content_key(&mut self, key: &str) -> Option<TypstRenderTarget> {
+        self.content_hashes.remove(key).map(|last| last.target)
+    }
+
+    /// Forget the content key of a job that failed or was cancelled, so a later job with the same content is queued
+    /// instead of being handed the unfinished render.
+    pub(crate) fn forget_job_content_key(&mut self, job: TypstJobId) {
+        self.content_hashes.retain(|_, last| last.target.job != job);
+    }
+
+    /// Keep the render info of a completed job with a content key, for callbacks attached to it through jobs
+    /// deduplicated onto it later.
+    pub(crate) fn record_content_key_info(&mut self, job: TypstJobId, info: &TypstRenderInfo) {
+        for last in self.content_hashes.values_mut() {
+            if last.target.job == job {
+                last.info = Some(info.clone());
+            }
+        }
+    }
+
+    /// Submit a typst job with both a Serde and Dict input, as per [`TypstTextureServer::add_job_with_dict_and_serde_input`].
+    pub fn submit_with_dict_and_serde_input(
         &mut self,
         path: impl Into<PathBufOrTemplate>,
         input_serde: impl Serialize,
         input_dict: impl Into<Dict>,
         options: TypstJobOptions,
-    ) -> Handle<Image> {
-        let Ok(serde_input): Result<serde_json::Value, _> = input_serde.serialize(Serializer)
-        else {
-            bevy_log::error!(
-                "[TYPST INPUT ERROR] Could not transform value into a serde json as interim for Dict."
-            );
-            return self.add_job_with_dict_input(path, input_dict, options);
-        };
-        let Ok(mut input_serde_dict): Result<Dict, _> = serde_json::from_value(serde_input) else {
-            bevy_log::error!("[TYPST INPUT ERROR] Could not get Dict from interim serde json.");
-            return self.add_job_with_dict_input(path, input_dict, options);
-        };
-        let mut input_dict: Dict = input_dict.into();
-        let input_unified = match options.input_unify_mode.clone() {
-            InputUnifyMode::SerdeOverridesDict => {
-                for (key, value) in input_serde_dict {
-                    input_dict.insert(key, value);
-                }
-                input_dict
-            }
-            InputUnifyMode::DictOverridesSerde => {
-                for (key, value) in input_dict {
-                    input_serde_dict.insert(key, value);
-                }
-                input_serde_dict
-            }
-            InputUnifyMode::SeparateKeys {
-                serde_key,
-                dict_key,
-            } => {
-                let mut dict = Dict::new();
-                dict.insert(serde_key.into(), input_serde_dict.into_value());
-                dict.insert(dict_key.into(), input_dict.into_value());
-                dict
-            }
-        };
-        self.add_job_with_dict_input(path, input_unified, options)
+    ) -> TypstRenderTarget {
+        let input = unify_inputs(input_serde, input_dict.into(), &options.input_unify_mode);
+        self.submit_with_dict_input(path, input, options)
     }
 
-    /// Add a typst job to the queue with input, as per [`TypstTextureServer::add_job_with_dict_input`],
-    /// but with the input generated from a serde serializable type.
-    /// This method overrides the `input_unify_mode` of your job options, setting it to [`InputUnifyMode::SerdeOverridesDict`].
-    pub fn add_job_with_serde_input(
+    /// Submit a typst job with a serde serializable input, as per [`TypstTextureServer::add_job_with_serde_input`].
+    pub fn submit_with_serde_input(
         &mut self,
         path: impl Into<PathBufOrTemplate>,
         input: impl Serialize,
         options: TypstJobOptions,
-    ) -> Handle<Image> {
-        self.add_job_with_dict_and_serde_input(
+    ) -> TypstRenderTarget {
+        self.submit_with_dict_and_serde_input(
             path,
             input,
             Dict::default(),
@@ -335,6 +1577,203 @@ impl TypstTextureServer {
         )
     }
 
+    /// Submit a typst job as per [`TypstTextureServer::submit_with_dict_input`], first checking the input against the
+    /// template's declared [`input_schema::TypstInputSchema`]. Templates that haven't loaded yet can't be checked here, so their
+    /// jobs are checked just before compiling instead, with failures logged and the job dropped.
+    pub fn try_submit_with_dict_input(
+        &mut self,
+        templates: &Assets<TypstTemplate>,
+        path: impl Into<PathBufOrTemplate>,
+        input: impl Into<Dict>,
+        options: TypstJobOptions,
+    ) -> Result<TypstRenderTarget, TypstJobError> {
+        let path = self.resolve_alias(path.into());
+        let input = input.into();
+        let schema = match &path {
+            PathBufOrTemplate::PathBuf(path_buf) => self
+                .templates
+                .get(path_buf)
+                .and_then(|handle| templates.get(handle))
+                .map(|template| &template.0.loaded_toml.inputs),
+            PathBufOrTemplate::NewTemplate(template) => Some(&template.loaded_toml.inputs),
+            PathBufOrTemplate::ExistingTemplate(handle) => templates
+                .get(handle)
+                .map(|template| &template.0.loaded_toml.inputs),
+            PathBufOrTemplate::Bytes { .. } => None,
+        };
+        if let Some(schema) = schema {
+            schema.validate(&input)?;
+        }
+        Ok(self.submit_with_dict_input(path, input, options))
+    }
+
+    /// Submit one job per scale, each rendered with `pixels_per_pt` multiplied by that scale, i.e. `[1., 2.]`
+    /// for @1x and @2x renders of the same document. The document is compiled once, by whichever of the jobs
+    /// runs first, and rasterized at each scale.
+    pub fn submit_scaled_with_dict_input(
+        &mut self,
+        path: impl Into<PathBufOrTemplate>,
+        input: impl Into<Dict>,
+        scales: impl IntoIterator<Item = f32>,
+        options: TypstJobOptions,
+    ) -> TypstScaledRenders {
+        let template = self.resolve_template(path.into());
+        let input: Dict = input.into();
+        let shared_compile = SharedCompile::default();
+        let renders = scales
+            .into_iter()
+            .map(|scale| {
+                let target = self.submit_shared(
+                    template.clone(),
+                    input.clone(),
+                    TypstJobOptions {
+                        pixels_per_pt: options.pixels_per_pt * scale,
+                        ..options.clone()
+                    },
+                    Some(shared_compile.clone()),
+                );
+                (scale, target)
+            })
+            .collect();
+        TypstScaledRenders { renders }
+    }
+
+    /// Start loading a template without queueing a job, i.e. during a loading screen. The returned handle
+    /// can be checked with [`TypstTextureServer::template_state`] and passed to the `add_job`/`submit` methods.
+    pub fn preload(&mut self, path: impl Into<PathBufOrTemplate>) -> Handle<TypstTemplate> {
+        self.resolve_template(path.into())
+    }
+
+    /// The loading state of a template. Templates that haven't started loading are reported as loading.
+    pub fn template_state(&self, template: &Handle<TypstTemplate>) -> TypstTemplateState {
+        match self.asset_server.get_load_state(template) {
+            Some(LoadState::Loaded) => TypstTemplateState::Ready,
+            Some(LoadState::Failed(error)) => TypstTemplateState::Failed { error },
+            Some(LoadState::NotLoaded | LoadState::Loading) | None => TypstTemplateState::Loading,
+        }
+    }
+
+    /// Register a logical name for a template, so jobs can be added with i.e. `add_job("card_front", ..)` while the
+    /// template's path is configured in one place. Registering a name again points it at the new template, which
+    /// allows templates to be swapped for skinning or modding. Names take priority over asset paths.
+    pub fn register(&mut self, name: &str, path_or_template: impl Into<PathBufOrTemplate>) {
+        let target = match path_or_template.into() {
+            target @ (PathBufOrTemplate::NewTemplate(_) | PathBufOrTemplate::Bytes { .. }) => {
+                PathBufOrTemplate::ExistingTemplate(self.resolve_template(target))
+            }
+            target => target,
+        };
+        self.aliases.insert(name.to_owned(), target);
+    }
+
+    /// Set the password of an encrypted zip archive by its asset path, i.e. `"cards.zip"`, for keeping it out of
+    /// the archive's `.meta` file. This has to be called before the archive is first loaded.
+    pub fn set_archive_password(&self, path: impl Into<PathBuf>, password: impl Into<String>) {
+        self.archive_passwords.insert(path, password);
+    }
+
+    /// Remove a name registered with [`TypstTextureServer::register`]. Returns false if it wasn't registered.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.aliases.remove(name).is_some()
+    }
+
+    /// The registered template for a path that's actually a name, or the path itself.
+    fn resolve_alias(&self, path_or_template: PathBufOrTemplate) -> PathBufOrTemplate {
+        let PathBufOrTemplate::PathBuf(path_buf) = path_or_template else {
+            return path_or_template;
+        };
+        match path_buf.to_str().and_then(|name| self.aliases.get(name)) {
+            Some(PathBufOrTemplate::PathBuf(target)) => PathBufOrTemplate::PathBuf(target.clone()),
+            Some(PathBufOrTemplate::ExistingTemplate(handle)) => {
+                PathBufOrTemplate::ExistingTemplate(handle.clone())
+            }
+            _ => PathBufOrTemplate::PathBuf(path_buf),
+        }
+    }
+
+    /// Get the handle for a template, loading or registering it with this server if needed.
+    fn resolve_template(&mut self, path_or_template: PathBufOrTemplate) -> Handle<TypstTemplate> {
+        let asset_server = self.asset_server.clone();
+        match self.resolve_alias(path_or_template) {
+            PathBufOrTemplate::PathBuf(path_buf) => self
+                .templates
+                .entry(path_buf.clone())
+                .or_insert_with(|| asset_server.load(path_buf))
+                .clone(),
+            PathBufOrTemplate::NewTemplate(structured_in_memory_template) => self
+                .templates
+                .entry(structured_in_memory_template.path_given.clone())
+                .insert_entry(asset_server.add(TypstTemplate(structured_in_memory_template)))
+                .get()
+                .clone(),
+            PathBufOrTemplate::ExistingTemplate(handle) => handle,
+            PathBufOrTemplate::Bytes { name, zip_bytes } => {
+                let template = StructuredInMemoryTemplate::from_zip_bytes(name.clone(), zip_bytes)
+                    .unwrap_or_else(|error| {
+                        typst_log!(
+                            Template,
+                            Error,
+                            "[TYPST TEMPLATE ERROR] Couldn't read template {}: {error}",
+                            name.display()
+                        );
+                        StructuredInMemoryTemplate::from_source(
+                            name,
+                            format!("#panic({:?})", error.to_string()),
+                        )
+                    });
+                self.resolve_template(PathBufOrTemplate::NewTemplate(template))
+            }
+        }
+    }
+
+    /// Remove a job from the queue before it's processed. Returns false if the job isn't queued,
+    /// either because it has already been processed or was never submitted to this server.
+    /// The job's image and render info handles will fail to load.
+    pub fn cancel(&mut self, job: TypstJobId) -> bool {
+        let cancelled = match self.jobs.iter().position(|queued_job| queued_job.id == job) {
+            Some(index) => self.jobs.remove(index),
+            None => self
+                .parked_jobs
+                .iter()
+                .position(|parked_job| parked_job.id == job)
+                .map(|index| self.parked_jobs.remove(index)),
+        };
+        let Some(cancelled) = cancelled else {
+            return false;
+        };
+        self.job_cancelled(cancelled);
+        true
+    }
+
+    /// Resolve a job that's been taken out of the queue without running, calling back with
+    /// [`TypstJobError::Cancelled`].
+    pub(crate) fn job_cancelled(&mut self, job: TypstJob) {
+        job.cancelled.store(true, Ordering::Release);
+        let job = job.id;
+        self.forget_job_content_key(job);
+        if let Some(callback) = self.callbacks.remove(&job) {
+            self.resolved_callbacks
+                .push((callback, Err(TypstJobError::Cancelled)));
+        }
+    }
+
+    /// Add a named queue, replacing any existing queue of the same name.
+    pub fn with_queue(mut self, name: impl Into<String>, queue: TypstJobQueue) -> Self {
+        self.queues.insert(name.into(), queue);
+        self
+    }
+
+    /// The default options of the named queue with [`TypstJobOptions::queue`] set to it, as a base for jobs
+    /// submitted to that queue. Falls back to the server's default options if there's no such queue.
+    pub fn queue_options(&self, name: &str) -> TypstJobOptions {
+        let mut options = self.queues.get(name).map_or_else(
+            || self.default_options(),
+            |queue| queue.default_options.clone(),
+        );
+        options.queue = Some(name.to_owned());
+        options
+    }
+
     pub fn limit_jobs(mut self, limit: u32) -> Self {
         self.jobs_per_frame = Some(limit);
         self
@@ -344,10 +1783,231 @@ impl TypstTextureServer {
         self.jobs_per_frame = None;
         self
     }
+
+    pub fn limit_in_flight(mut self, limit: usize) -> Self {
+        self.max_in_flight = Some(limit);
+        self
+    }
+
+    /// The number of jobs waiting to be compiled, not counting jobs parked by [`TypstStateScope`].
+    pub fn queued(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// Take the totals of work done since the last call, resetting them. These are also taken every frame by
+    /// the `TypstJobDiagnosticsPlugin` when the `job-diagnostics` feature is enabled.
+    pub fn take_stats(&mut self) -> TypstJobStats {
+        std::mem::take(&mut self.stats)
+    }
+
+    /// The number of jobs currently being converted or uploaded on the async compute pool.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// Skip processing jobs on any frame where the previous frame took longer than `threshold`, as per
+    /// [`TypstTextureServer::skip_jobs_over_frame_time`].
+    pub fn skip_jobs_over_frame_time(mut self, threshold: Duration) -> Self {
+        self.skip_jobs_over_frame_time = Some(threshold);
+        self
+    }
+
+    /// Stop processing queued jobs. Jobs added while paused are still queued.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resume processing queued jobs after [`TypstTextureServer::pause`].
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Stop tracking the template at `path`, returning its handle if it was tracked. The template
+    /// asset is unloaded once all other handles to it, including those held by queued jobs, are dropped.
+    pub fn unload_template(&mut self, path: impl AsRef<Path>) -> Option<Handle<TypstTemplate>> {
+        let handle = self.templates.remove(path.as_ref())?;
+        self.template_last_used.remove(&handle.id());
+        self.render_recipes.remove(&handle.id());
+        self.template_dependencies.remove(&handle.id());
+        Some(handle)
+    }
+
+    /// Unload every template that has no queued jobs and no handles to it outside of this server.
+    pub fn clear_unused_templates(&mut self) {
+        let unused: Vec<PathBuf> = self
+            .templates
+            .iter()
+            .filter(|(_, handle)| {
+                !self.has_queued_jobs(handle)
+                    && matches!(handle, Handle::Strong(handle) if Arc::strong_count(handle) <= 1)
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in unused {
+            self.unload_template(path);
+        }
+    }
+
+    /// Spread handing finished images to the asset server across frames, with at most `bytes` of image data per frame.
+    pub fn limit_upload_bytes(mut self, bytes: usize) -> Self {
+        self.upload_bytes_per_frame = Some(bytes);
+        self
+    }
+
+    /// Set a policy for automatically evicting unused templates.
+    pub fn with_eviction(mut self, eviction: TypstTemplateEviction) -> Self {
+        self.eviction = Some(eviction);
+        self
+    }
+
+    /// The files, fonts, and packages a template used during its last compile, if it has been compiled.
+    pub fn template_dependencies(
+        &self,
+        template: &Handle<TypstTemplate>,
+    ) -> Option<&TypstTemplateDependencies> {
+        self.template_dependencies.get(&template.id())
+    }
+
+    /// A copy of the pixels of a finished render made with [`TypstJobOptions::retain_pixels`] set.
+    pub fn read_pixels(&self, image: impl Into<AssetId<Image>>) -> Option<TypstRgbaBuffer> {
+        self.retained_pixels
+            .lock()
+            .ok()?
+            .get(&image.into())
+            .cloned()
+    }
+
+    /// Stop retaining the pixels of a render, returning them if they were retained.
+    pub fn release_pixels(&mut self, image: impl Into<AssetId<Image>>) -> Option<TypstRgbaBuffer> {
+        self.retained_pixels.lock().ok()?.remove(&image.into())
+    }
+
+    /// Queue a job after any jobs of the same or higher priority.
+    fn enqueue(&mut self, job: TypstJob) {
+        let priority = job.job_options.priority;
+        let position = self
+            .jobs
+            .iter()
+            .position(|queued_job| queued_job.job_options.priority < priority)
+            .unwrap_or(self.jobs.len());
+        self.jobs.insert(position, job);
+    }
+
+    fn has_queued_jobs(&self, template: &Handle<TypstTemplate>) -> bool {
+        self.jobs
+            .iter()
+            .chain(&self.parked_jobs)
+            .any(|job| job.use_template == *template)
+    }
+}
+
+/// Hash of everything a [`TypstJobOptions::content_key`] compares between jobs, which is every option that changes
+/// the output along with the job's resolved ambient inputs.
+fn content_hash(
+    template: &Handle<TypstTemplate>,
+    input: &Dict,
+    ambient: &TypstAmbientInputs,
+    options: &TypstJobOptions,
+) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::hash::DefaultHasher::new();
+    template.id().hash(&mut hasher);
+    input.hash(&mut hasher);
+    ambient.to_dict().hash(&mut hasher);
+    for data_file in &options.data_files {
+        data_file.path.hash(&mut hasher);
+        data_file.bytes.hash(&mut hasher);
+    }
+    options.compiler_features.hash(&mut hasher);
+    options.entry.hash(&mut hasher);
+    options.fonts.hash(&mut hasher);
+    options.pixels_per_pt.to_bits().hash(&mut hasher);
+    options.specific_page.hash(&mut hasher);
+    options.page_label.hash(&mut hasher);
+    options.asset_usage.bits().hash(&mut hasher);
+    // The rest don't implement `Hash`, but their debug output covers every field.
+    format!("{:?}", options.orientation).hash(&mut hasher);
+    format!("{:?}", options.output_format).hash(&mut hasher);
+    options.hdr_intensity.to_bits().hash(&mut hasher);
+    format!("{:?}", options.auto_shrink).hash(&mut hasher);
+    format!("{:?}", options.slice).hash(&mut hasher);
+    format!("{:?}", options.min_text_size).hash(&mut hasher);
+    options.error_texture.hash(&mut hasher);
+    #[cfg(feature = "basis-compression")]
+    format!("{:?}", options.compression).hash(&mut hasher);
+    match &options.post_process {
+        // Custom post-processing is compared by the closure it runs.
+        Some(TypstPostProcess::Custom(custom)) => {
+            Arc::as_ptr(custom).cast::<()>().hash(&mut hasher)
+        }
+        post_process => format!("{post_process:?}").hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+/// A copy of the template to build a job's engine from, compiling `entry` in place of its main file if given.
+/// `None` if the template has no such entry point.
+fn with_job_entry(
+    template: &StructuredInMemoryTemplate,
+    entry: Option<&Path>,
+) -> Option<StructuredInMemoryTemplate> {
+    match entry {
+        Some(entry) => template.clone().with_entry(entry),
+        None => Some(template.clone()),
+    }
+}
+
+/// Unify a serde and dict input into a single dict according to the given [`InputUnifyMode`].
+/// Falls back to only the dict input if the serde input can't be converted.
+pub(crate) fn unify_inputs(
+    input_serde: impl Serialize,
+    mut input_dict: Dict,
+    mode: &InputUnifyMode,
+) -> Dict {
+    let mut input_serde_dict = match serde_value::to_dict(&input_serde) {
+        Ok(dict) => dict,
+        Err(error) => {
+            typst_log!(
+                Input,
+                Error,
+                "[TYPST INPUT ERROR] {}",
+                TypstJobError::from(error)
+            );
+            return input_dict;
+        }
+    };
+    match mode.clone() {
+        InputUnifyMode::SerdeOverridesDict => {
+            for (key, value) in input_serde_dict {
+                input_dict.insert(key, value);
+            }
+            input_dict
+        }
+        InputUnifyMode::DictOverridesSerde => {
+            for (key, value) in input_dict {
+                input_serde_dict.insert(key, value);
+            }
+            input_serde_dict
+        }
+        InputUnifyMode::SeparateKeys {
+            serde_key,
+            dict_key,
+        } => {
+            let mut dict = Dict::new();
+            dict.insert(serde_key.into(), input_serde_dict.into_value());
+            dict.insert(dict_key.into(), input_dict.into_value());
+            dict
+        }
+    }
 }
 
 /// The typst asset reference type for the various `add_job` methods of [`TypstTextureServer`].
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum PathBufOrTemplate {
     /// A path to an on-disk typst asset.
     PathBuf(PathBuf),
@@ -358,6 +2018,10 @@ pub enum PathBufOrTemplate {
     NewTemplate(StructuredInMemoryTemplate),
     /// An already existing template.
     ExistingTemplate(Handle<TypstTemplate>),
+    /// A zipped template that isn't in the asset folder, such as one downloaded from a server or embedded with
+    /// `include_bytes!`, registered under `name` as per [`StructuredInMemoryTemplate::from_zip_bytes`]. If the
+    /// archive can't be read, the error is logged and jobs of the template fail to compile with it.
+    Bytes { name: PathBuf, zip_bytes: Arc<[u8]> },
 }
 
 impl From<PathBuf> for PathBufOrTemplate {
@@ -0,0 +1,264 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+use bevy_ecs::{change_detection::DetectChanges, resource::Resource, system::Res};
+use bevy_platform::time::Instant;
+
+/// The kinds of message this crate logs, for [`TypstLogConfig::levels`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TypstLogCategory {
+    /// Templates that failed to compile.
+    Compile,
+    /// Warnings reported by typst while compiling, see also [`crate::TypstJobOptions::warning_policy`].
+    Warning,
+    /// Job inputs that couldn't be converted, or don't match a template's inputs.
+    Input,
+    /// Other problems with jobs, such as templates that failed to load or renders that couldn't be uploaded.
+    Job,
+    /// Recoverable problems found while loading templates.
+    Template,
+    /// Text rendered below [`crate::TypstJobOptions::min_text_size`].
+    Legibility,
+    /// Problems turning documents into images, i.e. with compression or the GPU path.
+    Render,
+    /// Problems with how the crate is set up, such as missing features or resources.
+    Setup,
+}
+
+/// The level a message is logged at, or `Off` to drop it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TypstLogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// The `tracing` target messages are forwarded to `bevy_log` under, for filtering them with i.e. `RUST_LOG`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TypstLogTarget {
+    /// `bevy_typst_textures`, so every message can be filtered at once.
+    #[default]
+    Crate,
+    /// A target per [`TypstLogCategory`], i.e. `bevy_typst_textures::compile`, so each can be filtered separately.
+    Category,
+}
+
+/// A message logged by this crate, as passed to a [`TypstLogConfig::sink`].
+#[derive(Debug, Clone, Copy)]
+pub struct TypstLogRecord<'a> {
+    pub category: TypstLogCategory,
+    pub level: TypstLogLevel,
+    /// The message, including its `[TYPST ...]` prefix.
+    pub message: &'a str,
+}
+
+/// Receives every message logged by this crate that isn't dropped, i.e. for an in-game console.
+pub type TypstLogSink = Arc<dyn Fn(&TypstLogRecord) + Send + Sync>;
+
+/// Controls how this crate's messages are logged. Changes to this resource take effect from the next frame,
+/// including for messages logged while loading templates, and removing it restores the defaults. The config in
+/// effect is process-wide, so with several apps in one process the one that changed it last applies to all.
+#[derive(Clone, Resource)]
+pub struct TypstLogConfig {
+    /// Log messages of a category at a different level than they're normally logged at, or drop them with
+    /// [`TypstLogLevel::Off`].
+    pub levels: HashMap<TypstLogCategory, TypstLogLevel>,
+    /// Drop messages identical to one logged less than this long ago, such as the same error for a job that's
+    /// re-queued every frame. Defaults to `None`.
+    pub rate_limit: Option<Duration>,
+    /// Called with every message that isn't dropped. Defaults to `None`.
+    pub sink: Option<TypstLogSink>,
+    /// Log messages through `bevy_log` as well as passing them to `sink`. Defaults to `true`.
+    pub forward_to_bevy_log: bool,
+    /// The target messages are forwarded to `bevy_log` under. Defaults to [`TypstLogTarget::Crate`].
+    pub target: TypstLogTarget,
+}
+
+impl Default for TypstLogConfig {
+    fn default() -> Self {
+        Self {
+            levels: HashMap::new(),
+            rate_limit: None,
+            sink: None,
+            forward_to_bevy_log: true,
+            target: TypstLogTarget::default(),
+        }
+    }
+}
+
+impl std::fmt::Debug for TypstLogConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TypstLogConfig")
+            .field("levels", &self.levels)
+            .field("rate_limit", &self.rate_limit)
+            .field("sink", &self.sink.as_ref().map(|_| ".."))
+            .field("forward_to_bevy_log", &self.forward_to_bevy_log)
+            .field("target", &self.target)
+            .finish()
+    }
+}
+
+impl TypstLogConfig {
+    pub fn with_level(mut self, category: TypstLogCategory, level: TypstLogLevel) -> Self {
+        self.levels.insert(category, level);
+        self
+    }
+
+    pub fn with_rate_limit(mut self, rate_limit: Duration) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    pub fn with_target(mut self, target: TypstLogTarget) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn with_sink(mut self, sink: impl Fn(&TypstLogRecord) + Send + Sync + 'static) -> Self {
+        self.sink = Some(Arc::new(sink));
+        self
+    }
+}
+
+/// The config in effect, copied from the resource so messages can be logged from outside of systems.
+static ACTIVE_CONFIG: RwLock<Option<TypstLogConfig>> = RwLock::new(None);
+
+/// When each recent message was last logged, for [`TypstLogConfig::rate_limit`].
+static RECENT: Mutex<Option<HashMap<(TypstLogCategory, String), Instant>>> = Mutex::new(None);
+
+/// Past this many remembered messages, ones outside of the rate limit window are forgotten.
+const RECENT_CAPACITY: usize = 256;
+
+/// Log a message through the active [`TypstLogConfig`], as per [`typst_log`].
+pub(crate) fn emit(category: TypstLogCategory, level: TypstLogLevel, args: std::fmt::Arguments) {
+    let config = ACTIVE_CONFIG.read().ok();
+    let config = config.as_ref().and_then(|config| config.as_ref());
+    let level = config
+        .and_then(|config| config.levels.get(&category).copied())
+        .unwrap_or(level);
+    if level == TypstLogLevel::Off {
+        return;
+    }
+    let message = args.to_string();
+    if let Some(window) = config.and_then(|config| config.rate_limit)
+        && is_rate_limited(category, &message, window)
+    {
+        return;
+    }
+    if let Some(sink) = config.and_then(|config| config.sink.as_ref()) {
+        sink(&TypstLogRecord {
+            category,
+            level,
+            message: &message,
+        });
+    }
+    if config.is_none_or(|config| config.forward_to_bevy_log) {
+        let target = config.map_or(TypstLogTarget::default(), |config| config.target);
+        forward(category, level, target, &message);
+    }
+}
+
+/// Log through `bevy_log` with a target of `$target`, which has to be a literal for `tracing`.
+macro_rules! forward_to {
+    ($target:literal, $level:expr, $message:expr) => {
+        match $level {
+            TypstLogLevel::Off => {}
+            TypstLogLevel::Error => bevy_log::error!(target: $target, "{}", $message),
+            TypstLogLevel::Warn => bevy_log::warn!(target: $target, "{}", $message),
+            TypstLogLevel::Info => bevy_log::info!(target: $target, "{}", $message),
+            TypstLogLevel::Debug => bevy_log::debug!(target: $target, "{}", $message),
+            TypstLogLevel::Trace => bevy_log::trace!(target: $target, "{}", $message),
+        }
+    };
+}
+
+fn forward(
+    category: TypstLogCategory,
+    level: TypstLogLevel,
+    target: TypstLogTarget,
+    message: &str,
+) {
+    use TypstLogCategory::*;
+    match (target, category) {
+        (TypstLogTarget::Crate, _) => forward_to!("bevy_typst_textures", level, message),
+        (TypstLogTarget::Category, Compile) => {
+            forward_to!("bevy_typst_textures::compile", level, message)
+        }
+        (TypstLogTarget::Category, Warning) => {
+            forward_to!("bevy_typst_textures::warning", level, message)
+        }
+        (TypstLogTarget::Category, Input) => {
+            forward_to!("bevy_typst_textures::input", level, message)
+        }
+        (TypstLogTarget::Category, Job) => forward_to!("bevy_typst_textures::job", level, message),
+        (TypstLogTarget::Category, Template) => {
+            forward_to!("bevy_typst_textures::template", level, message)
+        }
+        (TypstLogTarget::Category, Legibility) => {
+            forward_to!("bevy_typst_textures::legibility", level, message)
+        }
+        (TypstLogTarget::Category, Render) => {
+            forward_to!("bevy_typst_textures::render", level, message)
+        }
+        (TypstLogTarget::Category, Setup) => {
+            forward_to!("bevy_typst_textures::setup", level, message)
+        }
+    }
+}
+
+fn is_rate_limited(category: TypstLogCategory, message: &str, window: Duration) -> bool {
+    let Ok(mut recent) = RECENT.lock() else {
+        return false;
+    };
+    let recent = recent.get_or_insert_with(HashMap::new);
+    let now = Instant::now();
+    let key = (category, message.to_owned());
+    if recent
+        .get(&key)
+        .is_some_and(|logged| now.duration_since(*logged) < window)
+    {
+        return true;
+    }
+    if recent.len() >= RECENT_CAPACITY {
+        recent.retain(|_, logged| now.duration_since(*logged) < window);
+    }
+    recent.insert(key, now);
+    false
+}
+
+/// Log a message with a [`TypstLogCategory`] and the [`TypstLogLevel`] it's normally logged at, i.e.
+/// `typst_log!(Job, Error, "[TYPST JOB ERROR] {error}")`.
+macro_rules! typst_log {
+    ($category:ident, $level:ident, $($arg:tt)+) => {
+        $crate::logging::emit(
+            $crate::logging::TypstLogCategory::$category,
+            $crate::logging::TypstLogLevel::$level,
+            format_args!($($arg)+),
+        )
+    };
+}
+
+pub(crate) use typst_log;
+
+pub(crate) fn system_apply_log_config(config: Option<Res<TypstLogConfig>>) {
+    let Some(config) = config else {
+        // The resource was removed, so go back to the defaults.
+        if ACTIVE_CONFIG.read().is_ok_and(|active| active.is_some())
+            && let Ok(mut active) = ACTIVE_CONFIG.write()
+        {
+            *active = None;
+        }
+        return;
+    };
+    if config.is_changed()
+        && let Ok(mut active) = ACTIVE_CONFIG.write()
+    {
+        *active = Some(config.clone());
+    }
+}
@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use bevy_asset::Assets;
+use bevy_color::LinearRgba;
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    query::Changed,
+    system::{Commands, Query, Res, ResMut},
+};
+use bevy_image::Image;
+use bevy_pbr::{MeshMaterial3d, StandardMaterial};
+use typst::foundations::Dict;
+
+use crate::{TypstJobOptions, TypstRenderTarget, TypstTextureServer};
+
+/// A texture for an entity's [`StandardMaterial`], rendered from a typst template. A job is queued whenever this
+/// component is added or changed, and once the render has loaded it's set as the `base_color_texture` (and
+/// `emissive_texture`, if `emissive` is set) of the entity's material. A material is created for entities
+/// without a [`MeshMaterial3d<StandardMaterial>`].
+///
+/// The material is edited in place, so entities sharing a material handle will all show the latest render.
+#[derive(Debug, Clone, Component)]
+#[require(TypstMaterialTextureRender)]
+pub struct TypstMaterialTexture {
+    pub template: PathBuf,
+    pub input: Dict,
+    pub options: TypstJobOptions,
+    /// Also use the render as the material's emissive texture, with a white emissive colour so it shows as-is.
+    pub emissive: bool,
+}
+
+impl TypstMaterialTexture {
+    pub fn new(template: impl Into<PathBuf>, input: impl Into<Dict>) -> Self {
+        Self {
+            template: template.into(),
+            input: input.into(),
+            options: TypstJobOptions::default(),
+            emissive: false,
+        }
+    }
+
+    pub fn with_options(mut self, options: TypstJobOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_emissive(mut self, emissive: bool) -> Self {
+        self.emissive = emissive;
+        self
+    }
+}
+
+/// The renders of a [`TypstMaterialTexture`], managed by this crate.
+#[derive(Debug, Clone, Default, Component)]
+pub struct TypstMaterialTextureRender {
+    /// The render currently used by the material.
+    pub current: Option<TypstRenderTarget>,
+    /// A newer render that hasn't finished loading yet.
+    pub pending: Option<TypstRenderTarget>,
+}
+
+pub(crate) fn system_queue_typst_material_textures(
+    mut template_server: ResMut<TypstTextureServer>,
+    mut textures: Query<
+        (&TypstMaterialTexture, &mut TypstMaterialTextureRender),
+        Changed<TypstMaterialTexture>,
+    >,
+) {
+    for (texture, mut render) in &mut textures {
+        if let Some(pending) = render.pending.take() {
+            template_server.cancel(pending.job);
+        }
+        render.pending = Some(template_server.submit_with_dict_input(
+            texture.template.clone(),
+            texture.input.clone(),
+            texture.options.clone(),
+        ));
+    }
+}
+
+pub(crate) fn system_update_typst_material_textures(
+    mut commands: Commands,
+    images: Res<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut textures: Query<(
+        Entity,
+        &TypstMaterialTexture,
+        &mut TypstMaterialTextureRender,
+        Option<&MeshMaterial3d<StandardMaterial>>,
+    )>,
+) {
+    for (entity, texture, mut render, material_handle) in &mut textures {
+        let Some(pending) = &render.pending else {
+            continue;
+        };
+        if !images.contains(&pending.image) {
+            continue;
+        }
+        let image = pending.image.clone();
+        let apply = |material: &mut StandardMaterial| {
+            material.base_color_texture = Some(image.clone());
+            if texture.emissive {
+                material.emissive = LinearRgba::WHITE;
+                material.emissive_texture = Some(image.clone());
+            }
+        };
+        match material_handle.and_then(|handle| materials.get_mut(&handle.0)) {
+            Some(material) => apply(material),
+            None => {
+                let mut material = StandardMaterial::default();
+                apply(&mut material);
+                commands
+                    .entity(entity)
+                    .insert(MeshMaterial3d(materials.add(material)));
+            }
+        }
+        render.current = render.pending.take();
+    }
+}
@@ -0,0 +1,281 @@
+use std::{collections::BTreeSet, path::Path};
+
+use bevy_asset::{AssetEvent, Assets};
+use bevy_ecs::{message::MessageReader, system::Res};
+use typst::{
+    foundations::{Dict, IntoValue},
+    layout::{Frame, FrameItem, PagedDocument},
+    text::{Font, FontBook, FontVariant},
+};
+use typst_as_lib::TypstAsLibError;
+
+use crate::{
+    TypstTextureServer,
+    asset_loading::TypstTemplate,
+    file_resolver::StructuredInMemoryTemplate,
+    font_library::{TypstEngineFonts, TypstFontLibrary},
+    lazy_fonts::LazyFont,
+};
+
+/// The document used to measure text, in place of a template's main file. The page shrinks to fit the text,
+/// wrapping at `width` if given.
+const MEASURE_SOURCE: &str = r#"#let width = sys.inputs.at("width", default: none)
+#set page(width: if width == none { auto } else { width * 1pt }, height: auto, margin: 0pt, fill: none)
+#set text(..sys.inputs.at("style", default: (:)))
+#sys.inputs.text
+"#;
+
+/// The laid out size of a string, from [`TypstTextureServer::measure_text`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TypstTextMetrics {
+    pub width_pt: f64,
+    pub height_pt: f64,
+    pub line_count: usize,
+}
+
+/// The size of a string set in a single font, from [`font_text_metrics`]. Unlike [`TypstTextMetrics`], this
+/// doesn't lay the text out, so kerning, ligatures and fallback fonts aren't accounted for, but once the font
+/// is resolved it's cheap enough to call every frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TypstFontTextMetrics {
+    /// The advance of the widest line.
+    pub width_pt: f64,
+    /// The distance between the baselines of consecutive lines, from the font's ascender, descender and line gap.
+    pub line_height_pt: f64,
+    pub ascender_pt: f64,
+    /// How far below the baseline the font reaches, as a positive distance.
+    pub descender_pt: f64,
+    /// The number of lines, split at line breaks.
+    pub line_count: usize,
+}
+
+impl TypstFontTextMetrics {
+    /// The height of every line together.
+    pub fn height_pt(&self) -> f64 {
+        self.line_height_pt * self.line_count as f64
+    }
+}
+
+#[derive(Debug)]
+pub enum TypstMeasureError {
+    /// The template isn't known to the server, or hasn't finished loading.
+    TemplateNotLoaded,
+    Compile(TypstAsLibError),
+    /// The template has no font of the given family.
+    UnknownFamily(String),
+}
+
+impl std::fmt::Display for TypstMeasureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypstMeasureError::TemplateNotLoaded => {
+                write!(f, "TypstMeasureError::TemplateNotLoaded")
+            }
+            TypstMeasureError::Compile(error) => write!(f, "TypstMeasureError::Compile: {error}"),
+            TypstMeasureError::UnknownFamily(family) => {
+                write!(
+                    f,
+                    "TypstMeasureError::UnknownFamily: No font of the family '{family}'"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for TypstMeasureError {}
+
+impl StructuredInMemoryTemplate {
+    /// Lay out `text` with this template's fonts, without rendering it. `style` is spread into typst's `text`
+    /// function as named arguments, i.e. `font`, `size`, and `weight`. When `max_width_pt` is set the text wraps
+    /// at that width, otherwise it's laid out on as few lines as its line breaks allow.
+    ///
+    /// This compiles a small document synchronously, so it's best used sparingly rather than every frame.
+    pub fn measure_text(
+        &self,
+        style: &Dict,
+        text: &str,
+        max_width_pt: Option<f64>,
+    ) -> Result<TypstTextMetrics, TypstMeasureError> {
+        self.measure_text_with_fonts(style, text, max_width_pt, &TypstEngineFonts::default())
+    }
+
+    /// Lay out `text` as per [`StructuredInMemoryTemplate::measure_text`], with the font config and library fonts
+    /// of `fonts`.
+    pub fn measure_text_with_fonts(
+        &self,
+        style: &Dict,
+        text: &str,
+        max_width_pt: Option<f64>,
+        fonts: &TypstEngineFonts,
+    ) -> Result<TypstTextMetrics, TypstMeasureError> {
+        let mut measurer = self.clone();
+        measurer.loaded_main = MEASURE_SOURCE.to_owned();
+        let (engine, _) = fonts.to_engine(measurer);
+        let mut input = Dict::new();
+        input.insert("text".into(), text.into_value());
+        input.insert("style".into(), style.clone().into_value());
+        input.insert("width".into(), max_width_pt.into_value());
+        let document = engine
+            .compile_with_input::<_, PagedDocument>(input)
+            .output
+            .map_err(TypstMeasureError::Compile)?;
+        let Some(page) = document.pages.first() else {
+            return Ok(TypstTextMetrics {
+                width_pt: 0.0,
+                height_pt: 0.0,
+                line_count: 0,
+            });
+        };
+        let mut baselines = BTreeSet::new();
+        collect_baselines(&page.frame, 0.0, &mut baselines);
+        let size = page.frame.size();
+        Ok(TypstTextMetrics {
+            width_pt: size.x.to_pt(),
+            height_pt: size.y.to_pt(),
+            line_count: baselines.len(),
+        })
+    }
+
+    /// The font of this template that typst would pick for a family and variant, parsing it if it's one of the
+    /// template's lazy fonts. Families are compared case-insensitively. This goes through all of the template's
+    /// fonts each time, see [`TypstTextureServer::measure_font_text`] for a cached lookup.
+    pub fn font(&self, family: &str, variant: FontVariant) -> Option<Font> {
+        let mut fonts = self.loaded_fonts.clone();
+        let family = family.to_lowercase();
+        fonts.extend(
+            self.lazy_fonts
+                .iter()
+                .filter(|font| font.info().family.to_lowercase() == family)
+                .filter_map(LazyFont::font),
+        );
+        let book = FontBook::from_fonts(&fonts);
+        let index = book.select(&family, variant)?;
+        fonts.into_iter().nth(index)
+    }
+}
+
+/// The size of `text` set in `font` at `size_pt`, from the advances of the font's glyphs alone. Characters the
+/// font has no glyph for are measured as its missing glyph box.
+pub fn font_text_metrics(font: &Font, text: &str, size_pt: f64) -> TypstFontTextMetrics {
+    let ttf = font.ttf();
+    let width_pt = text
+        .lines()
+        .map(|line| {
+            line.chars()
+                .filter(|c| !c.is_control())
+                .map(|c| {
+                    let glyph = ttf.glyph_index(c).map_or(0, |glyph| glyph.0);
+                    font.x_advance(glyph)
+                        .map_or(0.0, |advance| advance.get() * size_pt)
+                })
+                .sum::<f64>()
+        })
+        .fold(0.0, f64::max);
+    let metrics = font.metrics();
+    let ascender_pt = metrics.ascender.get() * size_pt;
+    let descender_pt = -metrics.descender.get() * size_pt;
+    TypstFontTextMetrics {
+        width_pt,
+        line_height_pt: ascender_pt
+            + descender_pt
+            + ttf.line_gap() as f64 / font.units_per_em() * size_pt,
+        ascender_pt,
+        descender_pt,
+        line_count: text.lines().count().max(1),
+    }
+}
+
+/// The distinct baselines of text in a frame, in hundredths of a point.
+fn collect_baselines(frame: &Frame, offset_pt: f64, baselines: &mut BTreeSet<i64>) {
+    for (position, item) in frame.items() {
+        let y = offset_pt + position.y.to_pt();
+        match item {
+            FrameItem::Group(group) => collect_baselines(&group.frame, y, baselines),
+            FrameItem::Text(_) => {
+                baselines.insert((y * 100.0).round() as i64);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl TypstTextureServer {
+    /// Measure text in the style of a loaded template known to this server, as per [`StructuredInMemoryTemplate::measure_text`].
+    pub fn measure_text(
+        &self,
+        templates: &Assets<TypstTemplate>,
+        template_path: impl AsRef<Path>,
+        style: &Dict,
+        text: &str,
+        max_width_pt: Option<f64>,
+    ) -> Result<TypstTextMetrics, TypstMeasureError> {
+        self.templates
+            .get(template_path.as_ref())
+            .and_then(|handle| templates.get(handle))
+            .ok_or(TypstMeasureError::TemplateNotLoaded)?
+            .0
+            .measure_text_with_fonts(style, text, max_width_pt, &self.engine_fonts)
+    }
+
+    /// Measure text set in a font of a loaded template known to this server, as per [`font_text_metrics`], i.e. to
+    /// size speech bubbles before queuing their render. The template's own fonts are used over the library's,
+    /// as they are for jobs by default. Fonts are resolved once per template, family and variant, until the
+    /// template is reloaded.
+    pub fn measure_font_text(
+        &self,
+        templates: &Assets<TypstTemplate>,
+        library: &TypstFontLibrary,
+        template_path: impl AsRef<Path>,
+        family: &str,
+        text: &str,
+        size_pt: f64,
+    ) -> Result<TypstFontTextMetrics, TypstMeasureError> {
+        let handle = self
+            .templates
+            .get(template_path.as_ref())
+            .ok_or(TypstMeasureError::TemplateNotLoaded)?;
+        let template = &templates
+            .get(handle)
+            .ok_or(TypstMeasureError::TemplateNotLoaded)?
+            .0;
+        let variant = FontVariant::default();
+        let key = (handle.id(), family.to_lowercase(), variant);
+        let cached = self
+            .measured_fonts
+            .lock()
+            .ok()
+            .and_then(|fonts| fonts.get(&key).cloned());
+        let font = match cached {
+            Some(font) => font,
+            None => {
+                let font = template.font(family, variant);
+                if let Ok(mut fonts) = self.measured_fonts.lock() {
+                    fonts.insert(key, font.clone());
+                }
+                font
+            }
+        };
+        let font = font
+            .or_else(|| library.font(family, variant))
+            .ok_or_else(|| TypstMeasureError::UnknownFamily(family.to_owned()))?;
+        Ok(font_text_metrics(&font, text, size_pt))
+    }
+
+    /// Forget the fonts resolved for [`TypstTextureServer::measure_font_text`] from templates that have changed.
+    pub(crate) fn system_forget_measured_fonts(
+        server: Res<TypstTextureServer>,
+        mut asset_events: MessageReader<AssetEvent<TypstTemplate>>,
+    ) {
+        for event in asset_events.read() {
+            let (AssetEvent::Modified { id }
+            | AssetEvent::Removed { id }
+            | AssetEvent::Unused { id }) = event
+            else {
+                continue;
+            };
+            if let Ok(mut fonts) = server.measured_fonts.lock() {
+                fonts.retain(|(template, _, _), _| template != id);
+            }
+        }
+    }
+}
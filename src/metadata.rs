@@ -0,0 +1,232 @@
+use typst::{
+    diag::Warned,
+    foundations::{Dict, IntoValue, Label, Selector, Value},
+    introspection::MetadataElem,
+    layout::PagedDocument,
+    utils::PicoStr,
+};
+use typst_as_lib::TypstAsLibError;
+
+use crate::{
+    engine::TypstTemplateEngine,
+    raster::{TypstOrientation, TypstRotation},
+};
+
+/// All values of `metadata` elements in the document with the given label, in document order.
+/// `#metadata(true) <overflowed>` in typst can be read with `query_metadata(document, "overflowed")`.
+pub fn query_metadata(document: &PagedDocument, label: &str) -> Vec<Value> {
+    let Some(label) = Label::new(PicoStr::intern(label)) else {
+        return vec![];
+    };
+    document
+        .introspector
+        .query(&Selector::Label(label))
+        .iter()
+        .filter_map(|content| content.to_packed::<MetadataElem>())
+        .map(|metadata| metadata.value.clone())
+        .collect()
+}
+
+/// The zero-based index of the page containing the first element with the given label, i.e. `<card-back>`.
+pub fn page_of_label(document: &PagedDocument, label: &str) -> Option<usize> {
+    let label = Label::new(PicoStr::intern(label))?;
+    let location = document
+        .introspector
+        .query(&Selector::Label(label))
+        .iter()
+        .find_map(|content| content.location())?;
+    Some(document.introspector.page(location).get() - 1)
+}
+
+/// Settings for automatically shrinking content that doesn't fit its fixed-size page.
+///
+/// The template is expected to read a scale factor from `sys.inputs` under `input_key` and to
+/// export a boolean `metadata` with the `overflow_label` label, i.e. `#metadata(true) <overflowed>`,
+/// when its content doesn't fit. While that flag is true, the job is recompiled with the scale
+/// multiplied by `step`, until it fits, `min_scale` is reached, or `max_attempts` recompiles have happened.
+#[derive(Debug, Clone)]
+pub struct TypstAutoShrink {
+    pub input_key: String,
+    pub overflow_label: String,
+    pub step: f64,
+    pub min_scale: f64,
+    pub max_attempts: u32,
+}
+
+impl Default for TypstAutoShrink {
+    fn default() -> Self {
+        Self {
+            input_key: "scale".to_owned(),
+            overflow_label: "overflowed".to_owned(),
+            step: 0.9,
+            min_scale: 0.25,
+            max_attempts: 8,
+        }
+    }
+}
+
+impl TypstAutoShrink {
+    /// Whether the document reported itself as overflowing.
+    pub fn overflowed(&self, document: &PagedDocument) -> bool {
+        query_metadata(document, &self.overflow_label)
+            .iter()
+            .any(|value| *value == Value::Bool(true))
+    }
+
+    /// Compile with progressively smaller scales until the document fits, returning the final
+    /// compilation and the scale it was compiled with.
+    pub(crate) fn compile_fitted(
+        &self,
+        engine: &TypstTemplateEngine,
+        mut input: Dict,
+    ) -> (Warned<Result<PagedDocument, TypstAsLibError>>, f64) {
+        let mut scale = match input.get(&self.input_key) {
+            Ok(Value::Float(scale)) => *scale,
+            Ok(Value::Int(scale)) => *scale as f64,
+            _ => 1.0,
+        };
+        let mut compiled = engine.compile_with_input::<_, PagedDocument>(input.clone());
+        let mut attempts = 0;
+        while let Ok(document) = &compiled.output
+            && self.overflowed(document)
+            && attempts < self.max_attempts
+            && scale * self.step >= self.min_scale
+        {
+            scale *= self.step;
+            attempts += 1;
+            input.insert(self.input_key.as_str().into(), scale.into_value());
+            compiled = engine.compile_with_input::<_, PagedDocument>(input.clone());
+        }
+        (compiled, scale)
+    }
+}
+
+/// Border insets for 9-slice scaling. Convert into bevy's `BorderRect` for use with a `TextureSlicer`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TypstSliceBorder {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl TypstSliceBorder {
+    pub fn all(inset: f32) -> Self {
+        Self {
+            left: inset,
+            right: inset,
+            top: inset,
+            bottom: inset,
+        }
+    }
+
+    fn scaled(self, factor: f32) -> Self {
+        Self {
+            left: self.left * factor,
+            right: self.right * factor,
+            top: self.top * factor,
+            bottom: self.bottom * factor,
+        }
+    }
+
+    /// Move the insets to the sides they end up on after the output has been reoriented.
+    fn oriented(self, orientation: TypstOrientation) -> Self {
+        let Self {
+            left,
+            right,
+            top,
+            bottom,
+        } = self;
+        let mut border = match orientation.rotation {
+            TypstRotation::None => self,
+            TypstRotation::Cw90 => Self {
+                left: bottom,
+                right: top,
+                top: left,
+                bottom: right,
+            },
+            TypstRotation::Cw180 => Self {
+                left: right,
+                right: left,
+                top: bottom,
+                bottom: top,
+            },
+            TypstRotation::Cw270 => Self {
+                left: top,
+                right: bottom,
+                top: right,
+                bottom: left,
+            },
+        };
+        if orientation.flip_x {
+            std::mem::swap(&mut border.left, &mut border.right);
+        }
+        if orientation.flip_y {
+            std::mem::swap(&mut border.top, &mut border.bottom);
+        }
+        border
+    }
+
+    /// Read insets from a typst value: either a single length for all sides, or a dictionary with
+    /// any of `left`, `right`, `top`, `bottom`, `x`, `y`, and `rest` as lengths. Plain numbers are taken as `pt`.
+    fn from_value(value: &Value) -> Option<Self> {
+        fn to_pt(value: &Value) -> Option<f32> {
+            match value {
+                Value::Length(length) => Some(length.abs.to_pt() as f32),
+                Value::Float(float) => Some(*float as f32),
+                Value::Int(int) => Some(*int as f32),
+                _ => None,
+            }
+        }
+        if let Value::Dict(dict) = value {
+            let side = |keys: &[&str]| {
+                keys.iter()
+                    .find_map(|key| dict.get(key).ok().and_then(to_pt))
+                    .unwrap_or(0.)
+            };
+            Some(Self {
+                left: side(&["left", "x", "rest"]),
+                right: side(&["right", "x", "rest"]),
+                top: side(&["top", "y", "rest"]),
+                bottom: side(&["bottom", "y", "rest"]),
+            })
+        } else {
+            to_pt(value).map(Self::all)
+        }
+    }
+}
+
+/// Where to get 9-slice border insets from for [`crate::TypstJobOptions::slice`].
+#[derive(Debug, Clone)]
+pub enum TypstSliceSource {
+    /// Read insets in `pt` from the first `metadata` with this label, i.e. `#metadata((x: 12pt, y: 8pt)) <slice>`.
+    Metadata { label: String },
+    /// Fixed insets in `pt`.
+    Insets(TypstSliceBorder),
+}
+
+impl Default for TypstSliceSource {
+    fn default() -> Self {
+        Self::Metadata {
+            label: "slice".to_owned(),
+        }
+    }
+}
+
+impl TypstSliceSource {
+    /// Compute the insets in output pixels for the given document and render settings.
+    pub(crate) fn resolve(
+        &self,
+        document: &PagedDocument,
+        pixels_per_pt: f32,
+        orientation: TypstOrientation,
+    ) -> Option<TypstSliceBorder> {
+        let border = match self {
+            TypstSliceSource::Metadata { label } => query_metadata(document, label)
+                .first()
+                .and_then(TypstSliceBorder::from_value)?,
+            TypstSliceSource::Insets(border) => *border,
+        };
+        Some(border.scaled(pixels_per_pt).oriented(orientation))
+    }
+}
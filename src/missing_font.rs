@@ -0,0 +1,70 @@
+use bevy_asset::AssetPath;
+use bevy_ecs::message::Message;
+use typst::{
+    diag::SourceDiagnostic,
+    layout::{Frame, FrameItem, PagedDocument},
+};
+
+use crate::TypstJobId;
+
+/// Sent when a job's template asks for a font family that isn't available, or draws characters that none of its
+/// fonts have glyphs for, which typst renders as boxes. Sent whatever the job's warning policy.
+#[derive(Debug, Clone, Message)]
+pub struct TypstMissingFont {
+    pub job: TypstJobId,
+    /// The asset path of the job's template, if it was loaded from one.
+    pub template: Option<AssetPath<'static>>,
+    /// The family that was asked for, or for characters without glyphs, the family of the font they were drawn
+    /// with instead.
+    pub family: String,
+    /// Characters drawn without a glyph, in the order they first appear. Empty if the family wasn't found but
+    /// typst fell back to another font for all of its text.
+    pub missing_chars: Vec<char>,
+}
+
+/// Families typst reported as unknown, and the families of fonts that drew characters without glyphs anywhere in
+/// the document, along with those characters.
+pub(crate) fn find_missing_fonts(
+    warnings: &[SourceDiagnostic],
+    document: &PagedDocument,
+) -> Vec<(String, Vec<char>)> {
+    let mut missing: Vec<(String, Vec<char>)> = vec![];
+    for warning in warnings {
+        if let Some(family) = warning.message.strip_prefix("unknown font family: ")
+            && !missing.iter().any(|(existing, _)| existing == family)
+        {
+            missing.push((family.to_owned(), vec![]));
+        }
+    }
+    for page in &document.pages {
+        collect_missing_glyphs(&page.frame, &mut missing);
+    }
+    missing
+}
+
+fn collect_missing_glyphs(frame: &Frame, missing: &mut Vec<(String, Vec<char>)>) {
+    for (_, item) in frame.items() {
+        match item {
+            FrameItem::Group(group) => collect_missing_glyphs(&group.frame, missing),
+            FrameItem::Text(text) => {
+                for glyph in text.glyphs.iter().filter(|glyph| glyph.id == 0) {
+                    let family = &text.font.info().family;
+                    let index = match missing.iter().position(|(existing, _)| existing == family) {
+                        Some(index) => index,
+                        None => {
+                            missing.push((family.clone(), vec![]));
+                            missing.len() - 1
+                        }
+                    };
+                    let chars = &mut missing[index].1;
+                    for c in text.text[glyph.range()].chars() {
+                        if !c.is_whitespace() && !chars.contains(&c) {
+                            chars.push(c);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use bevy_asset::{AssetId, Assets};
+use bevy_ecs::{
+    entity::{Entities, Entity},
+    system::ResMut,
+};
+use bevy_image::Image;
+
+use crate::{TypstJobId, TypstRenderInfo, TypstRenderTarget, TypstTextureServer};
+
+/// A render released when its owning entity despawns, from [`TypstTextureServer::release_with_entity`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct OwnedRender {
+    job: TypstJobId,
+    image: AssetId<Image>,
+    info: AssetId<TypstRenderInfo>,
+}
+
+impl TypstTextureServer {
+    /// Cancel `target`'s job and remove its image and render info from their assets once `entity` despawns,
+    /// such as for the texture of a short-lived UI element. Handles to the image held elsewhere will then
+    /// point to a removed asset.
+    pub fn release_with_entity(&mut self, entity: Entity, target: &TypstRenderTarget) {
+        self.owned_renders
+            .entry(entity)
+            .or_default()
+            .push(OwnedRender {
+                job: target.job,
+                image: target.image.id(),
+                info: target.info.id(),
+            });
+    }
+
+    /// Runs in `Last` before [`TypstTextureServer::system_do_jobs`], dropping queued jobs whose
+    /// [`crate::TypstJobOptions::owner`] has despawned and releasing the renders of despawned entities.
+    pub fn system_release_owned(
+        mut template_server: ResMut<TypstTextureServer>,
+        entities: &Entities,
+        mut images: ResMut<Assets<Image>>,
+        mut infos: ResMut<Assets<TypstRenderInfo>>,
+    ) {
+        let is_alive = |owner: Option<Entity>| owner.is_none_or(|owner| entities.contains(owner));
+        let orphaned: Vec<TypstJobId> = template_server
+            .jobs
+            .iter()
+            .chain(&template_server.parked_jobs)
+            .filter(|job| !is_alive(job.job_options.owner))
+            .map(|job| job.id)
+            .collect();
+        for job in orphaned {
+            template_server.cancel(job);
+        }
+        if template_server.owned_renders.is_empty() {
+            return;
+        }
+        let despawned: Vec<Entity> = template_server
+            .owned_renders
+            .keys()
+            .copied()
+            .filter(|entity| !entities.contains(*entity))
+            .collect();
+        for entity in despawned {
+            for render in template_server
+                .owned_renders
+                .remove(&entity)
+                .unwrap_or_default()
+            {
+                template_server.cancel(render.job);
+                images.remove(render.image);
+                infos.remove(render.info);
+            }
+        }
+    }
+}
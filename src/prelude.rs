@@ -0,0 +1,17 @@
+use typst::syntax::{FileId, Source, VirtualPath};
+
+/// Where the prelude of typst helpers is made available to every template, i.e.
+/// `#import "/bevy_prelude.typ": plural, format-number`. A template's own file at this path takes priority.
+///
+/// The prelude provides `plural`, `plural-category`, `format-number`, and `format-percent`, formatting for
+/// the locale of the injected [`crate::ambient::TypstAmbientInputs`] unless given a `locale` argument.
+pub const PRELUDE_PATH: &str = "/bevy_prelude.typ";
+
+const PRELUDE_SOURCE: &str = include_str!("prelude.typ");
+
+pub(crate) fn prelude_source() -> Source {
+    Source::new(
+        FileId::new(None, VirtualPath::new(PRELUDE_PATH)),
+        PRELUDE_SOURCE.to_owned(),
+    )
+}
@@ -0,0 +1,81 @@
+// Helpers available to every template with `#import "/bevy_prelude.typ": *`.
+// The locale defaults to the ambient locale injected by bevy_typst_textures, falling back to English.
+
+#let locale = {
+  let ambient = sys.inputs.at("ambient", default: (:))
+  let locale = ambient.at("locale", default: none)
+  if locale == none { "en" } else { locale }
+}
+
+#let _language(locale) = lower(locale.split(regex("[-_]")).first())
+
+// The CLDR plural category of `n`: "zero", "one", "two", "few", "many", or "other".
+// Covers the cardinal rules of common languages, other languages use the English rules.
+#let plural-category(n, locale: locale) = {
+  let lang = _language(locale)
+  if type(n) != int { return "other" }
+  let i = calc.abs(n)
+  let m10 = calc.rem(i, 10)
+  let m100 = calc.rem(i, 100)
+  if lang in ("ja", "zh", "ko", "vi", "th", "id", "ms", "tr") {
+    "other"
+  } else if lang in ("fr", "pt") {
+    if i < 2 { "one" } else { "other" }
+  } else if lang in ("ru", "uk", "be") {
+    if m10 == 1 and m100 != 11 { "one" } else if m10 >= 2 and m10 <= 4 and (m100 < 12 or m100 > 14) { "few" } else { "many" }
+  } else if lang == "pl" {
+    if i == 1 { "one" } else if m10 >= 2 and m10 <= 4 and (m100 < 12 or m100 > 14) { "few" } else { "many" }
+  } else if lang in ("cs", "sk") {
+    if i == 1 { "one" } else if i >= 2 and i <= 4 { "few" } else { "other" }
+  } else if lang == "ar" {
+    if i == 0 { "zero" } else if i == 1 { "one" } else if i == 2 { "two" } else if m100 >= 3 and m100 <= 10 { "few" } else if m100 >= 11 { "many" } else { "other" }
+  } else {
+    if i == 1 { "one" } else { "other" }
+  }
+}
+
+#let _separators(locale) = {
+  let lang = _language(locale)
+  if lang in ("de", "nl", "it", "es", "pt", "id", "tr", "da") {
+    (group: ".", decimal: ",", percent: "\u{00A0}%")
+  } else if lang == "fr" {
+    (group: "\u{202F}", decimal: ",", percent: "\u{202F}%")
+  } else if lang in ("ru", "uk", "be", "pl", "cs", "sk", "sv", "nb", "no", "fi") {
+    (group: "\u{00A0}", decimal: ",", percent: "\u{00A0}%")
+  } else {
+    (group: ",", decimal: ".", percent: "%")
+  }
+}
+
+// Format a number with the locale's digit grouping and decimal separator,
+// rounded and zero-padded to `decimals` places if given.
+#let format-number(n, decimals: none, locale: locale) = {
+  let separators = _separators(locale)
+  let value = if decimals == none { n } else { calc.round(n, digits: decimals) }
+  let parts = str(calc.abs(value)).split(".")
+  let fraction = parts.at(1, default: "")
+  if decimals != none {
+    fraction = fraction.slice(0, calc.min(fraction.len(), decimals))
+    while fraction.len() < decimals { fraction += "0" }
+  }
+  let grouped = ""
+  for (index, digit) in parts.first().clusters().rev().enumerate() {
+    if index > 0 and calc.rem(index, 3) == 0 { grouped = separators.group + grouped }
+    grouped = digit + grouped
+  }
+  let sign = if value < 0 { "\u{2212}" } else { "" }
+  sign + grouped + (if fraction == "" { "" } else { separators.decimal + fraction })
+}
+
+// Format a ratio such as `0.25` or `25%` as a percentage in the locale's style.
+#let format-percent(value, decimals: 0, locale: locale) = {
+  let fraction = if type(value) == ratio { value / 100% } else { value }
+  format-number(fraction * 100, decimals: decimals, locale: locale) + _separators(locale).percent
+}
+
+// Pick the form for `n` from a dictionary keyed by plural category, replacing `#` with the formatted number:
+// `plural(3, (one: "# item", other: "# items"))` gives "3 items".
+#let plural(n, forms, locale: locale) = {
+  let form = forms.at(plural-category(n, locale: locale), default: forms.at("other"))
+  form.replace("#", format-number(n, locale: locale))
+}
@@ -0,0 +1,337 @@
+use std::{borrow::Cow, path::PathBuf};
+
+use bevy_asset::{
+    AssetLoader, AsyncWriteExt,
+    processor::LoadTransformAndSave,
+    saver::{AssetSaver, SavedAsset},
+    transformer::IdentityAssetTransformer,
+};
+use bevy_reflect::TypePath;
+use serde::{Deserialize, Serialize};
+use typst::syntax::{FileId, Source, VirtualPath};
+
+use crate::{
+    asset_loading::{TypstAssetError, TypstTemplate},
+    file_resolver::{BevyTypstDotToml, StructuredInMemoryTemplate},
+    logging::typst_log,
+    shared_files::{SharedFilesLoad, share_bytes, share_fonts},
+};
+
+/// The start of every processed template, followed by the format version.
+const MAGIC: &[u8; 8] = b"TYPSTTPL";
+const VERSION: u32 = 2;
+
+/// Loads templates saved with [`StructuredInMemoryTemplate::save_processed`], for shipped builds to skip
+/// decompressing archives, decoding text and finding entry points at runtime. Processed templates use the
+/// `.typbin` extension.
+#[derive(Debug, Default, TypePath)]
+pub struct TypstProcessedLoader;
+
+impl AssetLoader for TypstProcessedLoader {
+    type Asset = TypstTemplate;
+
+    type Settings = ();
+
+    type Error = TypstAssetError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn bevy_asset::io::Reader,
+        _settings: &Self::Settings,
+        load_context: &mut bevy_asset::LoadContext<'_>,
+    ) -> std::result::Result<Self::Asset, Self::Error> {
+        let _sharing = SharedFilesLoad::start();
+        let mut buffer = Vec::new();
+        reader
+            .read_to_end(&mut buffer)
+            .await
+            .map_err(TypstAssetError::Io)?;
+        let _span =
+            bevy_log::info_span!("typst_template_load", path = %load_context.path()).entered();
+        StructuredInMemoryTemplate::from_processed(&buffer).map(TypstTemplate)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["typbin"]
+    }
+}
+
+/// Saves templates in the format read by [`TypstProcessedLoader`]. With bevy's asset processing enabled, this
+/// packs template directories, as loaded by [`crate::directory::TypstDirectoryLoader`], into processed templates at
+/// process time, see [`TypstTemplateProcessor`].
+#[derive(Debug, Default, TypePath)]
+pub struct TypstProcessedSaver;
+
+/// Settings for [`TypstProcessedSaver`], set in the `.meta` file of a template directory that's processed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TypstProcessedSettings {
+    /// Cut the template's fonts down to the glyphs of printable ASCII, the characters in its typst files and
+    /// `keep_characters`, so shipped templates only carry the glyphs they use. Needs the `font-subsetting`
+    /// feature. Defaults to `false`.
+    pub subset_fonts: bool,
+    /// Characters to keep the glyphs of when subsetting fonts, for text given to the template as inputs.
+    pub keep_characters: String,
+}
+
+impl AssetSaver for TypstProcessedSaver {
+    type Asset = TypstTemplate;
+
+    type Settings = TypstProcessedSettings;
+
+    type OutputLoader = TypstProcessedLoader;
+
+    type Error = TypstAssetError;
+
+    async fn save(
+        &self,
+        writer: &mut bevy_asset::io::Writer,
+        asset: SavedAsset<'_, Self::Asset>,
+        settings: &Self::Settings,
+    ) -> Result<(), Self::Error> {
+        writer
+            .write_all(&asset.0.save_processed_with_settings(settings))
+            .await
+            .map_err(TypstAssetError::Io)
+    }
+}
+
+/// The asset processor for `.typdir` template directories, which replaces them with processed templates in the
+/// imported assets so shipped builds don't need the project's files zipped by hand. It's the default processor
+/// for the `typdir` extension when the [`crate::asset_loading::AssetPluginForTypstTextures`] is added to an app
+/// with asset processing enabled.
+pub type TypstTemplateProcessor = LoadTransformAndSave<
+    crate::directory::TypstDirectoryLoader,
+    IdentityAssetTransformer<TypstTemplate>,
+    TypstProcessedSaver,
+>;
+
+/// Everything of a processed template but the contents of its binary files, which follow it in order.
+#[derive(Serialize, Deserialize)]
+struct ProcessedHeader {
+    toml: BevyTypstDotToml,
+    main: String,
+    path_given: PathBuf,
+    main_path: Option<PathBuf>,
+    sources: Vec<(PathBuf, String)>,
+    /// The path of each file and the length of its contents.
+    files: Vec<(PathBuf, u64)>,
+    /// The length of each font file, which follow the files. Collections are saved once for all of their fonts.
+    font_files: Vec<u64>,
+    /// The font file of each font and the font's index within it.
+    fonts: Vec<(usize, u32)>,
+}
+
+impl StructuredInMemoryTemplate {
+    /// Save this template in the format read by [`TypstProcessedLoader`] and
+    /// [`StructuredInMemoryTemplate::from_processed`]. Files from a template's [`crate::file_provider::TypstFileProvider`]s
+    /// aren't part of it, so templates loaded with [`crate::asset_loading::TypstTemplateSettings::lazy_members`]
+    /// should be loaded without it to be saved. Load diagnostics aren't saved either. Fonts read with
+    /// [`crate::asset_loading::TypstTemplateSettings::lazy_fonts`] are saved, but parsed when loaded.
+    pub fn save_processed(&self) -> Vec<u8> {
+        self.save_processed_with_settings(&TypstProcessedSettings::default())
+    }
+
+    /// As per [`StructuredInMemoryTemplate::save_processed`], subsetting fonts if the settings ask for it.
+    pub fn save_processed_with_settings(&self, settings: &TypstProcessedSettings) -> Vec<u8> {
+        #[cfg(feature = "font-subsetting")]
+        let subset_chars = self.subset_chars(settings);
+        #[cfg(not(feature = "font-subsetting"))]
+        if settings.subset_fonts {
+            typst_log!(
+                Setup,
+                Warn,
+                "[TYPST PROCESSING] Fonts of {:?} can't be subset without the `font-subsetting` feature, keeping them whole",
+                self.path_given
+            );
+        }
+        let mut blobs: Vec<Cow<[u8]>> = vec![];
+        let files = self
+            .file_resolver
+            .iter()
+            .filter(|(id, _)| id.package().is_none())
+            .map(|(id, bytes)| {
+                blobs.push(Cow::Borrowed(bytes.as_slice()));
+                (
+                    id.vpath().as_rootless_path().to_path_buf(),
+                    bytes.len() as u64,
+                )
+            })
+            .collect();
+        // Fonts of the same collection share their data, which is only saved once.
+        let mut font_files: Vec<u64> = vec![];
+        let mut saved_data: Vec<(*const u8, usize)> = vec![];
+        let fonts = self
+            .loaded_fonts
+            .iter()
+            .map(|font| (font.data(), font.index()))
+            .chain(
+                self.lazy_fonts
+                    .iter()
+                    .map(|font| (font.data(), font.index())),
+            )
+            .map(|(data, index)| {
+                #[cfg(feature = "font-subsetting")]
+                let subset = subset_chars.as_ref().and_then(|chars| {
+                    let subset = crate::font_subset::subset(data.as_slice(), index, chars);
+                    if subset.is_none() {
+                        typst_log!(
+                            Setup,
+                            Warn,
+                            "[TYPST PROCESSING] A font of {:?} couldn't be subset, keeping it whole",
+                            self.path_given
+                        );
+                    }
+                    subset
+                });
+                #[cfg(not(feature = "font-subsetting"))]
+                let subset: Option<Vec<u8>> = None;
+                // Subset fonts are always the only font of their file.
+                if let Some(subset) = subset {
+                    font_files.push(subset.len() as u64);
+                    blobs.push(Cow::Owned(subset));
+                    return (font_files.len() - 1, 0);
+                }
+                let key = (data.as_slice().as_ptr(), data.len());
+                if let Some(file) = saved_data.iter().position(|saved| *saved == key) {
+                    return (file, index);
+                }
+                saved_data.push(key);
+                font_files.push(data.len() as u64);
+                blobs.push(Cow::Borrowed(data.as_slice()));
+                (font_files.len() - 1, index)
+            })
+            .collect();
+        let header = ProcessedHeader {
+            toml: self.loaded_toml.clone(),
+            main: self.loaded_main.clone(),
+            path_given: self.path_given.clone(),
+            main_path: self.main_path.clone(),
+            sources: self
+                .source_resolver
+                .iter()
+                .filter(|source| source.id().package().is_none())
+                .map(|source| {
+                    (
+                        source.id().vpath().as_rootless_path().to_path_buf(),
+                        source.text().to_owned(),
+                    )
+                })
+                .collect(),
+            files,
+            font_files,
+            fonts,
+        };
+        let header = serde_json::to_vec(&header).expect("template headers are always serializable");
+        let mut out = Vec::with_capacity(
+            16 + header.len() + blobs.iter().map(|blob| blob.len()).sum::<usize>(),
+        );
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        out.extend_from_slice(&header);
+        for blob in blobs {
+            out.extend_from_slice(&blob);
+        }
+        out
+    }
+
+    /// The characters to keep the glyphs of when subsetting fonts, or `None` if they're kept whole.
+    #[cfg(feature = "font-subsetting")]
+    fn subset_chars(
+        &self,
+        settings: &TypstProcessedSettings,
+    ) -> Option<std::collections::BTreeSet<char>> {
+        if !settings.subset_fonts {
+            return None;
+        }
+        let texts = std::iter::once(self.loaded_main.as_str())
+            .chain(self.source_resolver.iter().map(Source::text))
+            .chain(std::iter::once(settings.keep_characters.as_str()));
+        Some((' '..='~').chain(texts.flat_map(str::chars)).collect())
+    }
+
+    /// Load a template saved with [`StructuredInMemoryTemplate::save_processed`].
+    pub fn from_processed(bytes: &[u8]) -> Result<Self, TypstAssetError> {
+        let malformed = |reason: &str| TypstAssetError::Processed(reason.to_owned());
+        let rest = bytes
+            .strip_prefix(MAGIC.as_slice())
+            .ok_or_else(|| malformed("not a processed template"))?;
+        let (version, rest) = split_u32(rest).ok_or_else(|| malformed("truncated"))?;
+        if version != VERSION {
+            return Err(TypstAssetError::Processed(format!(
+                "saved with format version {version}, expected {VERSION}"
+            )));
+        }
+        let (header_len, rest) = split_u32(rest).ok_or_else(|| malformed("truncated"))?;
+        if rest.len() < header_len as usize {
+            return Err(malformed("truncated"));
+        }
+        let (header, mut blobs) = rest.split_at(header_len as usize);
+        let header: ProcessedHeader = serde_json::from_slice(header)
+            .map_err(|error| TypstAssetError::Processed(error.to_string()))?;
+        let mut next_blob = |len: u64| -> Result<Vec<u8>, TypstAssetError> {
+            let len = usize::try_from(len).map_err(|_| malformed("file too large"))?;
+            if blobs.len() < len {
+                return Err(malformed("truncated"));
+            }
+            let (blob, rest) = blobs.split_at(len);
+            blobs = rest;
+            Ok(blob.to_vec())
+        };
+        let mut file_resolver = Vec::with_capacity(header.files.len());
+        for (path, len) in header.files {
+            let id = FileId::new(None, VirtualPath::new(&path));
+            file_resolver.push((id, share_bytes(next_blob(len)?)));
+        }
+        let mut font_files = Vec::with_capacity(header.font_files.len());
+        for len in header.font_files {
+            let fonts =
+                share_fonts(next_blob(len)?).map_err(|_| malformed("a font couldn't be parsed"))?;
+            font_files.push(fonts);
+        }
+        let mut loaded_fonts = Vec::with_capacity(header.fonts.len());
+        for (file, index) in header.fonts {
+            let font = font_files
+                .get(file)
+                .and_then(|fonts| fonts.iter().find(|font| font.index() == index))
+                .ok_or_else(|| malformed("a font couldn't be parsed"))?;
+            loaded_fonts.push(font.clone());
+        }
+        Ok(StructuredInMemoryTemplate {
+            loaded_toml: header.toml,
+            loaded_fonts,
+            loaded_main: header.main,
+            path_given: header.path_given,
+            main_path: header.main_path,
+            file_resolver,
+            source_resolver: header
+                .sources
+                .into_iter()
+                .map(|(path, text)| Source::new(FileId::new(None, VirtualPath::new(&path)), text))
+                .collect(),
+            ..Default::default()
+        })
+    }
+}
+
+fn split_u32(bytes: &[u8]) -> Option<(u32, &[u8])> {
+    let (int, rest) = bytes.split_first_chunk::<4>()?;
+    Some((u32::from_le_bytes(*int), rest))
+}
+
+/// Embed a typst project packed by `embed::embed_project` in the build script, as a [`StructuredInMemoryTemplate`]
+/// registered under the name it was packed with, for tools and examples that shouldn't depend on an assets folder.
+/// Packing needs the `embedded-projects` feature on the build dependency, this doesn't.
+#[macro_export]
+macro_rules! include_typst_project {
+    ($name:literal) => {
+        $crate::file_resolver::StructuredInMemoryTemplate::from_processed(include_bytes!(concat!(
+            env!("OUT_DIR"),
+            "/",
+            $name,
+            ".typbin"
+        )))
+        .expect("embedded typst projects are checked when packed")
+    };
+}
@@ -0,0 +1,99 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use bevy_asset::{AssetPath, AsyncReadExt};
+use bevy_ecs::resource::Resource;
+
+use crate::asset_loading::TypstAssetError;
+
+/// How many bytes of an archive are read at a time, between updates to its [`TypstTemplateLoadProgress`].
+const READ_CHUNK_BYTES: usize = 1024 * 1024;
+
+/// The progress of templates that are loading, by asset path, for loading screens to show how far along large
+/// archives are. Updated from the template loaders as they read and extract archives, with each template
+/// removed once it has loaded or failed to. Archives aren't streamed: each is read into memory in full before
+/// any entry is extracted, as zips are indexed from their end, so reading is reported in bytes only.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct TypstLoadProgress(Arc<RwLock<HashMap<AssetPath<'static>, TypstTemplateLoadProgress>>>);
+
+/// How far along loading a template is, see [`TypstLoadProgress`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TypstTemplateLoadProgress {
+    pub stage: TypstLoadStage,
+    /// Bytes of the archive read from its asset source so far.
+    pub bytes_read: u64,
+    /// Entries of the archive extracted so far, out of `entry_count`.
+    pub entries_read: usize,
+    /// Entries in the archive, known once it has been read.
+    pub entry_count: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TypstLoadStage {
+    /// The archive is being read from its asset source. Sources don't report sizes, so only `bytes_read` is known.
+    #[default]
+    Reading,
+    /// The archive's entries are being decompressed and sorted into the template.
+    Extracting,
+}
+
+impl TypstTemplateLoadProgress {
+    /// The fraction of the archive's entries extracted, from 0 to 1, or `None` while it's still being read.
+    pub fn fraction(&self) -> Option<f32> {
+        match self.stage {
+            TypstLoadStage::Reading => None,
+            TypstLoadStage::Extracting if self.entry_count == 0 => Some(1.0),
+            TypstLoadStage::Extracting => Some(self.entries_read as f32 / self.entry_count as f32),
+        }
+    }
+}
+
+impl TypstLoadProgress {
+    /// The progress of a template that's loading, or `None` if it isn't.
+    pub fn get<'a>(&self, path: impl Into<AssetPath<'a>>) -> Option<TypstTemplateLoadProgress> {
+        let path: AssetPath = path.into();
+        self.0.read().unwrap().get(&path).copied()
+    }
+
+    /// The progress of every template that's loading.
+    pub fn all(&self) -> Vec<(AssetPath<'static>, TypstTemplateLoadProgress)> {
+        self.0
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(path, progress)| (path.clone(), *progress))
+            .collect()
+    }
+
+    pub(crate) fn update(
+        &self,
+        path: &AssetPath<'static>,
+        update: impl FnOnce(&mut TypstTemplateLoadProgress),
+    ) {
+        update(self.0.write().unwrap().entry(path.clone()).or_default());
+    }
+
+    pub(crate) fn finish(&self, path: &AssetPath<'static>) {
+        self.0.write().unwrap().remove(path);
+    }
+
+    /// Read the rest of `reader` into memory a chunk at a time, updating the progress of the template at `path`.
+    pub(crate) async fn read_to_end(
+        &self,
+        reader: &mut dyn bevy_asset::io::Reader,
+        path: &AssetPath<'static>,
+    ) -> Result<Vec<u8>, TypstAssetError> {
+        let mut buffer = Vec::new();
+        let mut chunk = vec![0; READ_CHUNK_BYTES];
+        loop {
+            let read = reader.read(&mut chunk).await.map_err(TypstAssetError::Io)?;
+            if read == 0 {
+                return Ok(buffer);
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+            self.update(path, |progress| progress.bytes_read = buffer.len() as u64);
+        }
+    }
+}
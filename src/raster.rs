@@ -0,0 +1,382 @@
+use std::sync::Arc;
+
+use bevy_asset::RenderAssetUsages;
+use bevy_image::Image;
+use half::f16;
+use tiny_skia::Pixmap;
+use wgpu_types::{Extent3d, TextureDimension, TextureFormat};
+
+#[cfg(feature = "basis-compression")]
+use crate::logging::typst_log;
+
+/// Clockwise rotation applied to rasterized output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TypstRotation {
+    #[default]
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+/// Orientation of the rasterized output. Flips are applied after rotation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TypstOrientation {
+    pub rotation: TypstRotation,
+    pub flip_x: bool,
+    pub flip_y: bool,
+}
+
+impl TypstOrientation {
+    pub fn rotated(rotation: TypstRotation) -> Self {
+        Self {
+            rotation,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The output dimensions for an input of the given dimensions.
+    pub fn output_size(&self, width: u32, height: u32) -> (u32, u32) {
+        match self.rotation {
+            TypstRotation::None | TypstRotation::Cw180 => (width, height),
+            TypstRotation::Cw90 | TypstRotation::Cw270 => (height, width),
+        }
+    }
+
+    /// Reorient tightly packed pixel data of `bytes_per_pixel` sized pixels.
+    pub(crate) fn apply(
+        &self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        bytes_per_pixel: usize,
+    ) -> Vec<u8> {
+        let (width, height) = (width as usize, height as usize);
+        let (out_width, out_height) = {
+            let (w, h) = self.output_size(width as u32, height as u32);
+            (w as usize, h as usize)
+        };
+        let mut out = vec![0u8; data.len()];
+        for out_y in 0..out_height {
+            for out_x in 0..out_width {
+                let x = if self.flip_x {
+                    out_width - 1 - out_x
+                } else {
+                    out_x
+                };
+                let y = if self.flip_y {
+                    out_height - 1 - out_y
+                } else {
+                    out_y
+                };
+                let (source_x, source_y) = match self.rotation {
+                    TypstRotation::None => (x, y),
+                    TypstRotation::Cw90 => (y, height - 1 - x),
+                    TypstRotation::Cw180 => (width - 1 - x, height - 1 - y),
+                    TypstRotation::Cw270 => (width - 1 - y, x),
+                };
+                let source = (source_y * width + source_x) * bytes_per_pixel;
+                let target = (out_y * out_width + out_x) * bytes_per_pixel;
+                out[target..target + bytes_per_pixel]
+                    .copy_from_slice(&data[source..source + bytes_per_pixel]);
+            }
+        }
+        out
+    }
+}
+
+/// Pixel format of the output [`bevy_image::Image`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TypstOutputFormat {
+    /// Full color output as `Rgba8UnormSrgb`.
+    #[default]
+    Rgba8,
+    /// Single channel `R8Unorm` output containing only the alpha coverage of the document.
+    AlphaMask,
+    /// Single channel `R8Unorm` output containing the luminance of the document, weighted by coverage.
+    Luminance,
+    /// Linear `Rgba16Float` output for HDR cameras, with color multiplied by
+    /// [`crate::TypstJobOptions::hdr_intensity`].
+    Rgba16Float,
+}
+
+impl TypstOutputFormat {
+    pub fn texture_format(&self) -> TextureFormat {
+        match self {
+            TypstOutputFormat::Rgba8 => TextureFormat::Rgba8UnormSrgb,
+            TypstOutputFormat::AlphaMask | TypstOutputFormat::Luminance => TextureFormat::R8Unorm,
+            TypstOutputFormat::Rgba16Float => TextureFormat::Rgba16Float,
+        }
+    }
+
+    /// Convert premultiplied RGBA8 pixel data into this format, with `intensity` as per
+    /// [`crate::TypstJobOptions::hdr_intensity`].
+    pub(crate) fn convert(&self, rgba: Vec<u8>, intensity: f32) -> Vec<u8> {
+        match self {
+            TypstOutputFormat::Rgba8 => rgba,
+            TypstOutputFormat::AlphaMask => rgba.chunks_exact(4).map(|pixel| pixel[3]).collect(),
+            TypstOutputFormat::Luminance => rgba
+                .chunks_exact(4)
+                .map(|pixel| {
+                    (0.2126 * pixel[0] as f32 + 0.7152 * pixel[1] as f32 + 0.0722 * pixel[2] as f32)
+                        .round()
+                        .min(255.) as u8
+                })
+                .collect(),
+            TypstOutputFormat::Rgba16Float => rgba
+                .chunks_exact(4)
+                .flat_map(|pixel| {
+                    let alpha = pixel[3] as f32 / 255.;
+                    let color = |channel: u8| {
+                        let unpremultiplied = if alpha > 0. {
+                            (channel as f32 / 255. / alpha).min(1.)
+                        } else {
+                            0.
+                        };
+                        srgb_to_linear(unpremultiplied) * intensity
+                    };
+                    [color(pixel[0]), color(pixel[1]), color(pixel[2]), alpha]
+                })
+                .flat_map(|channel| f16::from_f32(channel).to_le_bytes())
+                .collect(),
+        }
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// A post-processing step applied to the output before it's sent to the asset server.
+#[derive(Clone)]
+pub enum TypstPostProcess {
+    /// Invert the color of every pixel, leaving alpha unchanged.
+    Invert,
+    /// Snap every pixel to the nearest color in the palette.
+    Quantize { palette: Vec<[u8; 3]> },
+    /// Snap every pixel to the nearest color in the palette after applying an ordered (4x4 Bayer)
+    /// dither, where `strength` is the largest per-channel offset in 0-255 units.
+    Dither {
+        palette: Vec<[u8; 3]>,
+        strength: f32,
+    },
+    /// Arbitrary processing of the final image. This runs after [`crate::TypstJobOptions::output_format`]
+    /// conversion and compression, so the image data may not be RGBA.
+    Custom(Arc<dyn Fn(&mut Image) + Send + Sync>),
+}
+
+impl std::fmt::Debug for TypstPostProcess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invert => write!(f, "Invert"),
+            Self::Quantize { palette } => f
+                .debug_struct("Quantize")
+                .field("palette", palette)
+                .finish(),
+            Self::Dither { palette, strength } => f
+                .debug_struct("Dither")
+                .field("palette", palette)
+                .field("strength", strength)
+                .finish(),
+            Self::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+const BAYER_4X4: [[f32; 4]; 4] = [
+    [0., 8., 2., 10.],
+    [12., 4., 14., 6.],
+    [3., 11., 1., 9.],
+    [15., 7., 13., 5.],
+];
+
+impl TypstPostProcess {
+    /// Apply a built-in post-process to premultiplied RGBA8 data. Custom post-processing is applied later to the image.
+    fn apply_rgba(&self, data: &mut [u8], width: u32) {
+        for (index, pixel) in data.chunks_exact_mut(4).enumerate() {
+            let alpha = pixel[3] as f32 / 255.;
+            if alpha == 0. {
+                continue;
+            }
+            let color = [0, 1, 2].map(|channel| pixel[channel] as f32 / alpha);
+            let output = match self {
+                TypstPostProcess::Invert => color.map(|channel| 255. - channel),
+                TypstPostProcess::Quantize { palette } => nearest_in_palette(color, palette),
+                TypstPostProcess::Dither { palette, strength } => {
+                    let (x, y) = (index % width as usize, index / width as usize);
+                    let offset = (BAYER_4X4[y % 4][x % 4] / 16. - 0.5) * 2. * strength;
+                    nearest_in_palette(color.map(|channel| channel + offset), palette)
+                }
+                TypstPostProcess::Custom(_) => return,
+            };
+            for (channel, value) in pixel.iter_mut().zip(output) {
+                *channel = (value.clamp(0., 255.) * alpha).round() as u8;
+            }
+        }
+    }
+}
+
+fn nearest_in_palette(color: [f32; 3], palette: &[[u8; 3]]) -> [f32; 3] {
+    palette
+        .iter()
+        .map(|entry| entry.map(|channel| channel as f32))
+        .min_by(|a, b| {
+            let distance = |entry: &[f32; 3]| {
+                (0..3)
+                    .map(|channel| (entry[channel] - color[channel]).powi(2))
+                    .sum::<f32>()
+            };
+            distance(a).total_cmp(&distance(b))
+        })
+        .unwrap_or(color)
+}
+
+/// CPU-side pixels of a render, kept when [`crate::TypstJobOptions::retain_pixels`] is set.
+/// This is premultiplied RGBA8 data after orientation and built-in post-processing, but before
+/// any output format conversion or compression.
+#[derive(Debug, Clone)]
+pub struct TypstRgbaBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl TypstRgbaBuffer {
+    /// The RGBA value of the pixel at `x`, `y`, if it's in bounds.
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let index = (y * self.width + x) as usize * 4;
+        self.data
+            .get(index..index + 4)
+            .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+    }
+}
+
+/// The subset of [`crate::TypstJobOptions`] needed to turn a rasterized page into an [`Image`], so it can be moved off-thread.
+#[derive(Debug, Clone)]
+pub(crate) struct RasterSettings {
+    pub orientation: TypstOrientation,
+    pub output_format: TypstOutputFormat,
+    pub hdr_intensity: f32,
+    pub asset_usage: RenderAssetUsages,
+    pub post_process: Option<TypstPostProcess>,
+    pub retain_pixels: bool,
+    #[cfg(feature = "basis-compression")]
+    pub compression: Option<crate::compression::TypstTextureCompression>,
+}
+
+impl RasterSettings {
+    pub fn from_options(options: &crate::TypstJobOptions) -> Self {
+        Self {
+            orientation: options.orientation,
+            output_format: options.output_format,
+            hdr_intensity: options.hdr_intensity,
+            asset_usage: options.asset_usage,
+            post_process: options.post_process.clone(),
+            retain_pixels: options.retain_pixels,
+            #[cfg(feature = "basis-compression")]
+            compression: options.compression,
+        }
+    }
+
+    /// If the output is compressed, and so padded to whole blocks.
+    #[cfg(feature = "basis-compression")]
+    fn is_compressed(&self) -> bool {
+        self.compression.is_some() && self.output_format == TypstOutputFormat::Rgba8
+    }
+
+    /// The dimensions of the output image for a rasterized page of the given size, after orientation and any
+    /// padding for compression.
+    pub fn output_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let (width, height) = self.orientation.output_size(width, height);
+        #[cfg(feature = "basis-compression")]
+        if self.is_compressed() {
+            return crate::compression::padded_size(width, height);
+        }
+        (width, height)
+    }
+
+    /// Build the output image, along with a copy of its pixels if `retain_pixels` is set.
+    pub fn build_image(&self, rendered: Pixmap) -> (Image, Option<TypstRgbaBuffer>) {
+        let (width, height) = self
+            .orientation
+            .output_size(rendered.width(), rendered.height());
+        let mut data = if self.orientation.is_identity() {
+            rendered.take()
+        } else {
+            self.orientation
+                .apply(rendered.data(), rendered.width(), rendered.height(), 4)
+        };
+        if let Some(post_process) = &self.post_process {
+            post_process.apply_rgba(&mut data, width);
+        }
+        let retained = self.retain_pixels.then(|| TypstRgbaBuffer {
+            width,
+            height,
+            data: data.clone(),
+        });
+        #[cfg(feature = "basis-compression")]
+        let (data, (width, height)) = match self.is_compressed() {
+            // Padded before compressing, so that the image is the same size should compression fail.
+            true => (
+                crate::compression::pad(data, width, height),
+                crate::compression::padded_size(width, height),
+            ),
+            false => (data, (width, height)),
+        };
+        let mut image = self.create_image(data, width, height);
+        if let Some(TypstPostProcess::Custom(post_process)) = &self.post_process {
+            post_process(&mut image);
+        }
+        (image, retained)
+    }
+
+    fn create_image(&self, data: Vec<u8>, width: u32, height: u32) -> Image {
+        #[cfg(feature = "basis-compression")]
+        if let Some(compression) = self.compression {
+            if self.output_format != TypstOutputFormat::Rgba8 {
+                typst_log!(
+                    Render,
+                    Warn,
+                    "[TYPST COMPRESSION WARNING] Compression is only supported for Rgba8 output, skipping"
+                );
+            } else {
+                match crate::compression::compress(
+                    &data,
+                    width,
+                    height,
+                    compression,
+                    self.asset_usage,
+                ) {
+                    Ok(image) => return image,
+                    Err(error) => typst_log!(
+                        Render,
+                        Error,
+                        "[TYPST COMPRESSION ERROR] {error}, falling back to uncompressed output"
+                    ),
+                }
+            }
+        }
+        Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            self.output_format.convert(data, self.hdr_intensity),
+            self.output_format.texture_format(),
+            self.asset_usage,
+        )
+    }
+}
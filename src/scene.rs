@@ -0,0 +1,287 @@
+use std::path::Path;
+
+use bevy_asset::Assets;
+use typst::{
+    foundations::Dict,
+    layout::{Frame, FrameItem, PagedDocument, Point, Transform},
+    text::{Font, FontVariant},
+    visualize::{CurveItem, FixedStroke, Geometry, Paint},
+};
+use typst_as_lib::TypstAsLibError;
+
+use crate::{
+    TypstTextureServer, asset_loading::TypstTemplate, file_resolver::StructuredInMemoryTemplate,
+    font_library::TypstEngineFonts,
+};
+
+/// A compiled document flattened into positioned primitives, for building custom renderers on top of this crate's
+/// template loading and compilation. All lengths are in points, and all transforms are absolute within the page.
+#[derive(Debug, Clone, Default)]
+pub struct TypstScene {
+    pub pages: Vec<TypstScenePage>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TypstScenePage {
+    pub size_pt: (f64, f64),
+    pub items: Vec<TypstSceneItem>,
+}
+
+/// A primitive and its transform from item space to page space, as an affine matrix `[sx, ky, kx, sy, tx, ty]`.
+#[derive(Debug, Clone)]
+pub struct TypstSceneItem {
+    pub transform: [f64; 6],
+    pub kind: TypstSceneItemKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum TypstSceneItemKind {
+    Text(TypstTextRun),
+    Shape(TypstSceneShape),
+    /// A raster or vector image filling a box of this size.
+    Image {
+        size_pt: (f64, f64),
+    },
+}
+
+/// A run of glyphs in a single font, with the origin at the start of its baseline.
+#[derive(Debug, Clone)]
+pub struct TypstTextRun {
+    /// The font the glyph ids index into. Its bytes and index within a collection are available through
+    /// [`Font::data`] and [`Font::index`], i.e. to load it into another text renderer.
+    pub font: Font,
+    pub font_family: String,
+    /// The style, weight, and stretch of `font`.
+    pub font_variant: FontVariant,
+    pub size_pt: f64,
+    pub text: String,
+    pub glyphs: Vec<TypstSceneGlyph>,
+    pub fill: Option<[u8; 4]>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TypstSceneGlyph {
+    /// The glyph's id within its font.
+    pub id: u16,
+    pub x_advance_pt: f64,
+    pub x_offset_pt: f64,
+    /// Vertical advance, for vertical text. Like `y_offset_pt` this is y up, as typst gives it.
+    pub y_advance_pt: f64,
+    /// Vertical offset from the baseline, i.e. for marks placed above or below their base glyph.
+    pub y_offset_pt: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypstSceneShape {
+    pub path: Vec<TypstPathCommand>,
+    /// Solid sRGBA fill, if any. Gradients and tilings aren't exported.
+    pub fill: Option<[u8; 4]>,
+    pub stroke: Option<TypstSceneStroke>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TypstSceneStroke {
+    pub color: Option<[u8; 4]>,
+    pub thickness_pt: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TypstPathCommand {
+    MoveTo(f64, f64),
+    LineTo(f64, f64),
+    CubicTo {
+        control_a: (f64, f64),
+        control_b: (f64, f64),
+        to: (f64, f64),
+    },
+    Close,
+}
+
+#[derive(Debug)]
+pub enum TypstSceneError {
+    /// The template isn't known to the server, or hasn't finished loading.
+    TemplateNotLoaded,
+    Compile(TypstAsLibError),
+}
+
+impl std::fmt::Display for TypstSceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypstSceneError::TemplateNotLoaded => write!(f, "TypstSceneError::TemplateNotLoaded"),
+            TypstSceneError::Compile(error) => write!(f, "TypstSceneError::Compile: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for TypstSceneError {}
+
+impl TypstScene {
+    pub fn from_document(document: &PagedDocument) -> Self {
+        Self {
+            pages: document
+                .pages
+                .iter()
+                .map(|page| {
+                    let size = page.frame.size();
+                    let mut items = vec![];
+                    flatten(&page.frame, Transform::identity(), &mut items);
+                    TypstScenePage {
+                        size_pt: (size.x.to_pt(), size.y.to_pt()),
+                        items,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+fn flatten(frame: &Frame, transform: Transform, items: &mut Vec<TypstSceneItem>) {
+    for (position, item) in frame.items() {
+        let transform = transform.pre_concat(Transform::translate(position.x, position.y));
+        let kind = match item {
+            FrameItem::Group(group) => {
+                flatten(&group.frame, transform.pre_concat(group.transform), items);
+                continue;
+            }
+            FrameItem::Text(text) => TypstSceneItemKind::Text(TypstTextRun {
+                font: text.font.clone(),
+                font_family: text.font.info().family.clone(),
+                font_variant: text.font.info().variant,
+                size_pt: text.size.to_pt(),
+                text: text.text.to_string(),
+                glyphs: text
+                    .glyphs
+                    .iter()
+                    .map(|glyph| TypstSceneGlyph {
+                        id: glyph.id,
+                        x_advance_pt: glyph.x_advance.at(text.size).to_pt(),
+                        x_offset_pt: glyph.x_offset.at(text.size).to_pt(),
+                        y_advance_pt: glyph.y_advance.at(text.size).to_pt(),
+                        y_offset_pt: glyph.y_offset.at(text.size).to_pt(),
+                    })
+                    .collect(),
+                fill: solid(&text.fill),
+            }),
+            FrameItem::Shape(shape, _) => TypstSceneItemKind::Shape(TypstSceneShape {
+                path: path_of(&shape.geometry),
+                fill: shape.fill.as_ref().and_then(solid),
+                stroke: shape.stroke.as_ref().map(stroke_of),
+            }),
+            FrameItem::Image(_, size, _) => TypstSceneItemKind::Image {
+                size_pt: (size.x.to_pt(), size.y.to_pt()),
+            },
+            _ => continue,
+        };
+        items.push(TypstSceneItem {
+            transform: matrix(transform),
+            kind,
+        });
+    }
+}
+
+fn matrix(transform: Transform) -> [f64; 6] {
+    [
+        transform.sx.get(),
+        transform.ky.get(),
+        transform.kx.get(),
+        transform.sy.get(),
+        transform.tx.to_pt(),
+        transform.ty.to_pt(),
+    ]
+}
+
+fn solid(paint: &Paint) -> Option<[u8; 4]> {
+    match paint {
+        Paint::Solid(color) => Some(color.to_vec4_u8()),
+        _ => None,
+    }
+}
+
+fn stroke_of(stroke: &FixedStroke) -> TypstSceneStroke {
+    TypstSceneStroke {
+        color: solid(&stroke.paint),
+        thickness_pt: stroke.thickness.to_pt(),
+    }
+}
+
+fn point(point: Point) -> (f64, f64) {
+    (point.x.to_pt(), point.y.to_pt())
+}
+
+fn path_of(geometry: &Geometry) -> Vec<TypstPathCommand> {
+    match geometry {
+        Geometry::Line(to) => vec![
+            TypstPathCommand::MoveTo(0.0, 0.0),
+            TypstPathCommand::LineTo(to.x.to_pt(), to.y.to_pt()),
+        ],
+        Geometry::Rect(size) => {
+            let (width, height) = (size.x.to_pt(), size.y.to_pt());
+            vec![
+                TypstPathCommand::MoveTo(0.0, 0.0),
+                TypstPathCommand::LineTo(width, 0.0),
+                TypstPathCommand::LineTo(width, height),
+                TypstPathCommand::LineTo(0.0, height),
+                TypstPathCommand::Close,
+            ]
+        }
+        Geometry::Curve(curve) => curve
+            .0
+            .iter()
+            .map(|item| match *item {
+                CurveItem::Move(to) => {
+                    let (x, y) = point(to);
+                    TypstPathCommand::MoveTo(x, y)
+                }
+                CurveItem::Line(to) => {
+                    let (x, y) = point(to);
+                    TypstPathCommand::LineTo(x, y)
+                }
+                CurveItem::Cubic(control_a, control_b, to) => TypstPathCommand::CubicTo {
+                    control_a: point(control_a),
+                    control_b: point(control_b),
+                    to: point(to),
+                },
+                CurveItem::Close => TypstPathCommand::Close,
+            })
+            .collect(),
+    }
+}
+
+impl StructuredInMemoryTemplate {
+    /// Compile this template synchronously and flatten the result into a [`TypstScene`].
+    pub fn compile_scene(&self, input: Dict) -> Result<TypstScene, TypstAsLibError> {
+        self.compile_scene_with_fonts(input, &TypstEngineFonts::default())
+    }
+
+    /// Compile this template into a [`TypstScene`] as per [`StructuredInMemoryTemplate::compile_scene`], with the
+    /// font config and library fonts of `fonts`.
+    pub fn compile_scene_with_fonts(
+        &self,
+        input: Dict,
+        fonts: &TypstEngineFonts,
+    ) -> Result<TypstScene, TypstAsLibError> {
+        let (engine, _) = fonts.to_engine(self.clone());
+        let document = engine
+            .compile_with_input::<_, PagedDocument>(input)
+            .output?;
+        Ok(TypstScene::from_document(&document))
+    }
+}
+
+impl TypstTextureServer {
+    /// Compile a loaded template known to this server into a [`TypstScene`], as per [`StructuredInMemoryTemplate::compile_scene`].
+    pub fn compile_scene(
+        &self,
+        templates: &Assets<TypstTemplate>,
+        template_path: impl AsRef<Path>,
+        input: Dict,
+    ) -> Result<TypstScene, TypstSceneError> {
+        self.templates
+            .get(template_path.as_ref())
+            .and_then(|handle| templates.get(handle))
+            .ok_or(TypstSceneError::TemplateNotLoaded)?
+            .0
+            .compile_scene_with_fonts(input, &self.engine_fonts)
+            .map_err(TypstSceneError::Compile)
+    }
+}
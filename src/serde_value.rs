@@ -0,0 +1,632 @@
+use serde::{
+    Serialize,
+    ser::{self, Impossible},
+};
+use typst::foundations::{Array, Bytes, Dict, IntoValue, Str, Value};
+
+/// Serialize a value directly into a typst [`Value`].
+///
+/// Structs and maps become dictionaries, sequences and tuples become arrays, byte arrays become
+/// typst `bytes`, and enum variants with data become single-key dictionaries keyed by the variant name.
+/// Integers that don't fit in typst's 64 bit signed integers are an error rather than losing precision.
+/// Serde serializes `Vec<u8>` and `[u8; N]` as sequences, so they become arrays of integers unless they're
+/// serialized as bytes, i.e. with `serde_bytes`.
+pub fn to_value(value: &impl Serialize) -> Result<Value, ValueSerializeError> {
+    value.serialize(ValueSerializer)
+}
+
+/// Serialize a value directly into a typst [`Dict`], failing if it doesn't serialize to a dictionary.
+pub fn to_dict(value: &impl Serialize) -> Result<Dict, ValueSerializeError> {
+    match to_value(value)? {
+        Value::Dict(dict) => Ok(dict),
+        other => Err(ValueSerializeError::NotADict(other.ty().to_string())),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ValueSerializeError {
+    /// The top level value given to [`to_dict`] wasn't a map or struct.
+    NotADict(String),
+    /// A map key didn't serialize to a string or integer.
+    InvalidKey(String),
+    /// An integer didn't fit in typst's 64 bit signed integers.
+    IntegerOutOfRange(String),
+    Custom(String),
+}
+
+impl std::fmt::Display for ValueSerializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueSerializeError::NotADict(ty) => {
+                write!(f, "ValueSerializeError::NotADict: got {ty}")
+            }
+            ValueSerializeError::InvalidKey(ty) => {
+                write!(
+                    f,
+                    "ValueSerializeError::InvalidKey: keys must be strings, got {ty}"
+                )
+            }
+            ValueSerializeError::IntegerOutOfRange(int) => {
+                write!(
+                    f,
+                    "ValueSerializeError::IntegerOutOfRange: {int} doesn't fit in a typst integer"
+                )
+            }
+            ValueSerializeError::Custom(message) => {
+                write!(f, "ValueSerializeError::Custom: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValueSerializeError {}
+
+impl ser::Error for ValueSerializeError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        ValueSerializeError::Custom(msg.to_string())
+    }
+}
+
+/// A [`serde::Serializer`] producing typst [`Value`]s. See [`to_value`].
+pub struct ValueSerializer;
+
+fn variant_dict(variant: &str, value: Value) -> Value {
+    let mut dict = Dict::new();
+    dict.insert(variant.into(), value);
+    Value::Dict(dict)
+}
+
+fn int_value(int: impl TryInto<i64> + ToString + Copy) -> Result<Value, ValueSerializeError> {
+    int.try_into()
+        .map(Value::Int)
+        .map_err(|_| ValueSerializeError::IntegerOutOfRange(int.to_string()))
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = ValueSerializeError;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeDict;
+    type SerializeStruct = SerializeDict;
+    type SerializeStructVariant = SerializeDict;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Self::Error> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, Self::Error> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, Self::Error> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, Self::Error> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, Self::Error> {
+        Ok(Value::Int(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value, Self::Error> {
+        int_value(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, Self::Error> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, Self::Error> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, Self::Error> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, Self::Error> {
+        int_value(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value, Self::Error> {
+        int_value(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, Self::Error> {
+        Ok(Value::Float(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, Self::Error> {
+        Ok(Value::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, Self::Error> {
+        Ok(Value::Str(Str::from(v)))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, Self::Error> {
+        Ok(Value::Str(v.into()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Self::Error> {
+        Ok(Value::Bytes(Bytes::new(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Value, Self::Error> {
+        Ok(Value::None)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Value, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, Self::Error> {
+        Ok(Value::None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Self::Error> {
+        Ok(Value::None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Self::Error> {
+        Ok(Value::Str(variant.into()))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Self::Error> {
+        Ok(variant_dict(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SerializeArray {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(SerializeArray {
+            items: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(SerializeDict {
+            dict: Dict::new(),
+            next_key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(SerializeDict {
+            dict: Dict::new(),
+            next_key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+pub struct SerializeArray {
+    items: Vec<Value>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeArray {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ValueSerializeError> {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ValueSerializeError> {
+        let array: Array = self.items.into_iter().collect();
+        Ok(match self.variant {
+            Some(variant) => variant_dict(variant, array.into_value()),
+            None => Value::Array(array),
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Value;
+    type Error = ValueSerializeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Value;
+    type Error = ValueSerializeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Value;
+    type Error = ValueSerializeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = Value;
+    type Error = ValueSerializeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Self::Error> {
+        self.finish()
+    }
+}
+
+pub struct SerializeDict {
+    dict: Dict,
+    next_key: Option<Str>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeDict {
+    fn finish(self) -> Result<Value, ValueSerializeError> {
+        Ok(match self.variant {
+            Some(variant) => variant_dict(variant, Value::Dict(self.dict)),
+            None => Value::Dict(self.dict),
+        })
+    }
+}
+
+impl ser::SerializeMap for SerializeDict {
+    type Ok = Value;
+    type Error = ValueSerializeError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.next_key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self
+            .next_key
+            .take()
+            .ok_or_else(|| ValueSerializeError::Custom("value without a key".to_owned()))?;
+        self.dict.insert(key, value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeDict {
+    type Ok = Value;
+    type Error = ValueSerializeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.dict
+            .insert(key.into(), value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeDict {
+    type Ok = Value;
+    type Error = ValueSerializeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.dict
+            .insert(key.into(), value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Self::Error> {
+        self.finish()
+    }
+}
+
+/// Serializes map keys into strings, accepting strings, chars, integers, bools, and unit variants.
+struct KeySerializer;
+
+impl KeySerializer {
+    fn invalid(ty: &str) -> ValueSerializeError {
+        ValueSerializeError::InvalidKey(ty.to_owned())
+    }
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = Str;
+    type Error = ValueSerializeError;
+    type SerializeSeq = Impossible<Str, ValueSerializeError>;
+    type SerializeTuple = Impossible<Str, ValueSerializeError>;
+    type SerializeTupleStruct = Impossible<Str, ValueSerializeError>;
+    type SerializeTupleVariant = Impossible<Str, ValueSerializeError>;
+    type SerializeMap = Impossible<Str, ValueSerializeError>;
+    type SerializeStruct = Impossible<Str, ValueSerializeError>;
+    type SerializeStructVariant = Impossible<Str, ValueSerializeError>;
+
+    fn serialize_bool(self, v: bool) -> Result<Str, Self::Error> {
+        Ok(v.to_string().into())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Str, Self::Error> {
+        Ok(v.to_string().into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Str, Self::Error> {
+        Ok(v.to_string().into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Str, Self::Error> {
+        Ok(v.to_string().into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Str, Self::Error> {
+        Ok(v.to_string().into())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Str, Self::Error> {
+        Ok(v.to_string().into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Str, Self::Error> {
+        Ok(v.to_string().into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Str, Self::Error> {
+        Ok(v.to_string().into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Str, Self::Error> {
+        Ok(v.to_string().into())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Str, Self::Error> {
+        Err(Self::invalid("float"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Str, Self::Error> {
+        Err(Self::invalid("float"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Str, Self::Error> {
+        Ok(Str::from(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Str, Self::Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Str, Self::Error> {
+        Err(Self::invalid("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<Str, Self::Error> {
+        Err(Self::invalid("none"))
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Str, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Str, Self::Error> {
+        Err(Self::invalid("none"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Str, Self::Error> {
+        Err(Self::invalid("none"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Str, Self::Error> {
+        Ok(variant.into())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Str, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Str, Self::Error> {
+        Err(Self::invalid("dictionary"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(Self::invalid("array"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(Self::invalid("array"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(Self::invalid("array"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(Self::invalid("dictionary"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(Self::invalid("dictionary"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(Self::invalid("dictionary"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(Self::invalid("dictionary"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Serialize, Serializer};
+    use typst::foundations::{Bytes, Value};
+
+    use super::{ValueSerializeError, to_value};
+
+    struct AsBytes(&'static [u8]);
+
+    impl Serialize for AsBytes {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    #[test]
+    fn integers_in_range_are_ints() {
+        assert_eq!(to_value(&(i64::MAX as u64)).unwrap(), Value::Int(i64::MAX));
+        assert_eq!(to_value(&(i64::MIN as i128)).unwrap(), Value::Int(i64::MIN));
+        assert_eq!(to_value(&7u128).unwrap(), Value::Int(7));
+    }
+
+    #[test]
+    fn integers_out_of_range_are_errors() {
+        assert!(matches!(
+            to_value(&u64::MAX),
+            Err(ValueSerializeError::IntegerOutOfRange(int)) if int == u64::MAX.to_string()
+        ));
+        assert!(matches!(
+            to_value(&(i64::MIN as i128 - 1)),
+            Err(ValueSerializeError::IntegerOutOfRange(_))
+        ));
+        assert!(matches!(
+            to_value(&vec![1u64, u64::MAX]),
+            Err(ValueSerializeError::IntegerOutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn bytes_are_typst_bytes() {
+        assert_eq!(
+            to_value(&AsBytes(b"typst")).unwrap(),
+            Value::Bytes(Bytes::new(b"typst".to_vec()))
+        );
+    }
+
+    #[test]
+    fn byte_vectors_are_arrays() {
+        assert!(matches!(to_value(&vec![1u8, 2]).unwrap(), Value::Array(_)));
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use bevy_asset::{AssetEvent, Assets};
+use bevy_ecs::{
+    message::MessageReader,
+    system::{Local, Res},
+};
+use typst::{foundations::Bytes, text::Font};
+
+use crate::{asset_loading::TypstTemplate, font_library::TypstFontLibrary};
+
+/// Fonts and binary files of loaded templates by their contents, so that a font or image embedded in many
+/// templates is only kept in memory once and shared between them.
+static SHARED_FILES: LazyLock<Mutex<SharedFiles>> = LazyLock::new(Mutex::default);
+
+/// Template loads under way, whose files are in the store but not yet in any template asset.
+static LOADS_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+/// Bumped as each load starts and finishes, so pruning also waits for the templates of finished loads to be
+/// added to the assets.
+static LOAD_EVENTS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Default)]
+struct SharedFiles {
+    files: HashMap<u128, Bytes>,
+    /// Every font of each font file, by the hash of the file.
+    fonts: HashMap<u128, Vec<Font>>,
+}
+
+/// How much is held in the store of files shared between templates, see [`shared_file_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TypstSharedFileStats {
+    /// Distinct files held, fonts included.
+    pub files: usize,
+    /// Distinct fonts held, counting each font of a collection.
+    pub fonts: usize,
+    /// The size of the distinct files held.
+    pub bytes: usize,
+}
+
+/// How much is held in the store of files shared between templates. Files that no loaded template has are
+/// dropped from the store as templates are unloaded.
+pub fn shared_file_stats() -> TypstSharedFileStats {
+    SHARED_FILES
+        .lock()
+        .ok()
+        .map(|shared| TypstSharedFileStats {
+            files: shared.files.len(),
+            fonts: shared.fonts.values().map(Vec::len).sum(),
+            bytes: shared.files.values().map(|bytes| bytes.len()).sum(),
+        })
+        .unwrap_or_default()
+}
+
+/// The given file's contents, shared with any other template that has a file with the same contents.
+pub(crate) fn share_bytes(buf: Vec<u8>) -> Bytes {
+    let hash = typst::utils::hash128(buf.as_slice());
+    match SHARED_FILES.lock() {
+        Ok(mut shared) => shared.share(hash, buf),
+        Err(_) => Bytes::new(buf),
+    }
+}
+
+/// Every font of the given file, which may be a collection such as a `.ttc`, shared with any other template
+/// that has the same file. Fails with the file's contents if it has no fonts typst can parse.
+pub(crate) fn share_fonts(buf: Vec<u8>) -> Result<Vec<Font>, Bytes> {
+    let hash = typst::utils::hash128(buf.as_slice());
+    let Ok(mut shared) = SHARED_FILES.lock() else {
+        let bytes = Bytes::new(buf);
+        let fonts: Vec<Font> = Font::iter(bytes.clone()).collect();
+        return if fonts.is_empty() {
+            Err(bytes)
+        } else {
+            Ok(fonts)
+        };
+    };
+    let bytes = shared.share(hash, buf);
+    if let Some(fonts) = shared.fonts.get(&hash)
+        && fonts
+            .first()
+            .is_some_and(|font| font.data().as_ptr() == bytes.as_ptr())
+    {
+        return Ok(fonts.clone());
+    }
+    let fonts: Vec<Font> = Font::iter(bytes.clone()).collect();
+    if fonts.is_empty() {
+        return Err(bytes);
+    }
+    shared.fonts.insert(hash, fonts.clone());
+    Ok(fonts)
+}
+
+impl SharedFiles {
+    fn share(&mut self, hash: u128, buf: Vec<u8>) -> Bytes {
+        match self.files.get(&hash) {
+            // Compared in full so that a hash collision can't swap one file for another.
+            Some(bytes) if bytes.as_slice() == buf.as_slice() => bytes.clone(),
+            Some(_) => Bytes::new(buf),
+            None => {
+                let bytes = Bytes::new(buf);
+                self.files.insert(hash, bytes.clone());
+                bytes
+            }
+        }
+    }
+}
+
+/// Holds off pruning the shared store while a template is loading, as its files aren't in any template asset
+/// until it has loaded.
+pub(crate) struct SharedFilesLoad(());
+
+impl SharedFilesLoad {
+    pub(crate) fn start() -> Self {
+        LOADS_IN_FLIGHT.fetch_add(1, Ordering::AcqRel);
+        LOAD_EVENTS.fetch_add(1, Ordering::AcqRel);
+        Self(())
+    }
+}
+
+impl Drop for SharedFilesLoad {
+    fn drop(&mut self) {
+        LOAD_EVENTS.fetch_add(1, Ordering::AcqRel);
+        LOADS_IN_FLIGHT.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Runs in `Last`. Drops files from the shared store once no loaded template, template provider or the
+/// [`TypstFontLibrary`] has them. Pruning waits until no templates are loading, and no load has finished since
+/// the last frame, so the files of templates that haven't been added to the assets yet are kept.
+pub fn system_prune_shared_files(
+    mut asset_events: MessageReader<AssetEvent<TypstTemplate>>,
+    templates: Res<Assets<TypstTemplate>>,
+    font_library: Option<Res<TypstFontLibrary>>,
+    mut pending: Local<bool>,
+    mut last_load_events: Local<u64>,
+) {
+    *pending |= asset_events.read().any(|event| {
+        matches!(
+            event,
+            AssetEvent::Removed { .. } | AssetEvent::Modified { .. } | AssetEvent::Unused { .. }
+        )
+    });
+    let load_events = LOAD_EVENTS.load(Ordering::Acquire);
+    let loads_settled = std::mem::replace(&mut *last_load_events, load_events) == load_events
+        && LOADS_IN_FLIGHT.load(Ordering::Acquire) == 0;
+    if !*pending || !loads_settled {
+        return;
+    }
+    *pending = false;
+    let library_fonts = font_library
+        .as_ref()
+        .map_or(&[][..], |library| library.fonts());
+    let live: HashSet<*const u8> = templates
+        .iter()
+        .flat_map(|(_, template)| {
+            let template = &template.0;
+            let fonts = template
+                .loaded_fonts
+                .iter()
+                .map(|font| font.data().as_ptr())
+                .chain(template.lazy_fonts.iter().map(|font| font.data().as_ptr()));
+            let files = template
+                .file_resolver
+                .iter()
+                .map(|(_, bytes)| bytes.as_ptr())
+                .chain(
+                    template
+                        .file_providers
+                        .shared_files()
+                        .into_iter()
+                        .map(|bytes| bytes.as_ptr()),
+                );
+            fonts.chain(files).collect::<Vec<_>>()
+        })
+        .chain(library_fonts.iter().map(|font| font.data().as_ptr()))
+        .collect();
+    let Ok(mut shared) = SHARED_FILES.lock() else {
+        return;
+    };
+    shared
+        .files
+        .retain(|_, bytes| live.contains(&bytes.as_ptr()));
+    shared
+        .fonts
+        .retain(|_, fonts| live.contains(&fonts[0].data().as_ptr()));
+}
@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+
+use bevy_asset::Assets;
+use bevy_camera::Projection;
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    query::{Changed, With},
+    system::{Query, Res, ResMut},
+};
+use bevy_image::Image;
+use bevy_math::{Rect, Vec2};
+use bevy_sprite::Sprite;
+use bevy_window::{PrimaryWindow, Window};
+use typst::foundations::Dict;
+
+use crate::{TypstJobOptions, TypstRenderInfo, TypstRenderTarget, TypstTextureServer};
+
+/// A sprite rendered from a typst template. A job is queued whenever this component is added or changed, and the
+/// entity's [`Sprite`] is switched over to the new render once it's loaded, so the previous render stays visible
+/// in the meantime.
+#[derive(Debug, Clone, Component)]
+#[require(Sprite, TypstSpriteRender)]
+pub struct TypstSprite {
+    pub template: PathBuf,
+    pub input: Dict,
+    pub options: TypstJobOptions,
+    /// The sprite's width in world units, with the height following the rendered page's aspect ratio.
+    /// When `None`, the sprite is sized to the page's size in points.
+    pub width: Option<f32>,
+}
+
+impl TypstSprite {
+    pub fn new(template: impl Into<PathBuf>, input: impl Into<Dict>) -> Self {
+        Self {
+            template: template.into(),
+            input: input.into(),
+            options: TypstJobOptions::default(),
+            width: None,
+        }
+    }
+
+    pub fn with_options(mut self, options: TypstJobOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_width(mut self, width: f32) -> Self {
+        self.width = Some(width);
+        self
+    }
+}
+
+/// The renders of a [`TypstSprite`], managed by this crate.
+#[derive(Debug, Clone, Default, Component)]
+pub struct TypstSpriteRender {
+    /// The render currently shown by the sprite.
+    pub current: Option<TypstRenderTarget>,
+    /// A newer render that hasn't finished loading yet.
+    pub pending: Option<TypstRenderTarget>,
+}
+
+pub(crate) fn system_queue_typst_sprites(
+    mut template_server: ResMut<TypstTextureServer>,
+    mut sprites: Query<(&TypstSprite, &mut TypstSpriteRender), Changed<TypstSprite>>,
+) {
+    for (sprite, mut render) in &mut sprites {
+        if let Some(pending) = render.pending.take() {
+            template_server.cancel(pending.job);
+        }
+        render.pending = Some(template_server.submit_with_dict_input(
+            sprite.template.clone(),
+            sprite.input.clone(),
+            sprite.options.clone(),
+        ));
+    }
+}
+
+pub(crate) fn system_update_typst_sprites(
+    images: Res<Assets<Image>>,
+    infos: Res<Assets<TypstRenderInfo>>,
+    mut sprites: Query<(&TypstSprite, &mut TypstSpriteRender, &mut Sprite)>,
+) {
+    for (typst_sprite, mut render, mut sprite) in &mut sprites {
+        let Some(pending) = &render.pending else {
+            continue;
+        };
+        if !images.contains(&pending.image) {
+            continue;
+        }
+        if let Some(info) = infos.get(&pending.info) {
+            // Sized from the output pixels rather than the page, so that rotated renders keep their aspect, and
+            // cropped to the page so that any padding for compression isn't shown.
+            let (width_px, height_px) = info.content_size_px;
+            let size = Vec2::new(width_px as f32, height_px as f32) / info.pixels_per_pt;
+            sprite.custom_size = Some(match typst_sprite.width {
+                Some(width) => Vec2::new(width, width * size.y / size.x),
+                None => size,
+            });
+            sprite.rect = (info.content_size_px != info.size_px)
+                .then(|| Rect::new(0.0, 0.0, width_px as f32, height_px as f32));
+        }
+        sprite.image = pending.image.clone();
+        render.current = render.pending.take();
+    }
+}
+
+/// How far the display scale of a [`TypstDpiAware`] sprite can drift from its render, as a fraction, before it's
+/// re-rendered.
+const RESCALE_TOLERANCE: f32 = 0.01;
+
+/// Add to an entity with a [`TypstSprite`] to re-render it as the primary window's scale factor changes, such as
+/// when the window is moved to a monitor with a different DPI, and optionally as a camera zooms, so its text stays
+/// crisp. The sprite's `options.pixels_per_pt` is set to `base_pixels_per_pt` times the scale factor, divided by
+/// the camera's orthographic scale when a camera is set. The sprite's size in the world doesn't change.
+///
+/// [`crate::ui::TypstUiNode`]s don't need this, as they already follow their on-screen size.
+#[derive(Debug, Clone, Component)]
+pub struct TypstDpiAware {
+    /// The pixels per point at a scale factor of 1 and no zoom.
+    pub base_pixels_per_pt: f32,
+    /// A camera whose orthographic projection's scale is also followed.
+    pub camera: Option<Entity>,
+}
+
+impl Default for TypstDpiAware {
+    fn default() -> Self {
+        Self {
+            base_pixels_per_pt: 1.0,
+            camera: None,
+        }
+    }
+}
+
+impl TypstDpiAware {
+    pub fn new(base_pixels_per_pt: f32) -> Self {
+        Self {
+            base_pixels_per_pt,
+            camera: None,
+        }
+    }
+
+    pub fn with_camera(mut self, camera: Entity) -> Self {
+        self.camera = Some(camera);
+        self
+    }
+}
+
+pub(crate) fn system_rescale_dpi_aware_sprites(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    projections: Query<&Projection>,
+    mut sprites: Query<(&TypstDpiAware, &mut TypstSprite)>,
+) {
+    let scale_factor = windows.single().map_or(1.0, |window| window.scale_factor());
+    for (dpi_aware, mut sprite) in &mut sprites {
+        let zoom = match dpi_aware
+            .camera
+            .and_then(|camera| projections.get(camera).ok())
+        {
+            Some(Projection::Orthographic(orthographic)) if orthographic.scale > 0.0 => {
+                1.0 / orthographic.scale
+            }
+            _ => 1.0,
+        };
+        let pixels_per_pt = dpi_aware.base_pixels_per_pt * scale_factor * zoom;
+        if (pixels_per_pt / sprite.options.pixels_per_pt - 1.0).abs() > RESCALE_TOLERANCE {
+            sprite.options.pixels_per_pt = pixels_per_pt;
+        }
+    }
+}
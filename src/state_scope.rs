@@ -0,0 +1,135 @@
+use std::{
+    any::TypeId,
+    collections::VecDeque,
+    hash::{DefaultHasher, Hash, Hasher},
+    marker::PhantomData,
+};
+
+use bevy_app::{Last, Plugin};
+use bevy_ecs::{message::MessageReader, schedule::IntoScheduleConfigs, system::ResMut};
+use bevy_state::state::{StateTransitionEvent, States};
+
+use crate::TypstTextureServer;
+
+/// What happens to a queued job when the state it's scoped to is exited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TypstStateScopeExit {
+    /// Drop the job, as per [`crate::TypstTextureServer::cancel`]. Its image handle will never load.
+    #[default]
+    Cancel,
+    /// Hold the job until the state is entered again, then queue it as normal.
+    Park,
+}
+
+/// Ties a job to a value of a bevy [`States`] type, set through [`crate::TypstJobOptions::state_scope`].
+/// Requires a [`TypstStateScopePlugin`] for that states type.
+///
+/// Only queued jobs are affected, jobs already being compiled or rasterized on the task pool will still complete.
+/// Jobs submitted while their state is inactive are queued as normal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypstStateScope {
+    state: u64,
+    pub on_exit: TypstStateScopeExit,
+}
+
+impl TypstStateScope {
+    pub fn new<S: States>(state: S, on_exit: TypstStateScopeExit) -> Self {
+        Self {
+            state: state_key(&state),
+            on_exit,
+        }
+    }
+
+    /// Cancel the job when `state` is exited.
+    pub fn cancel<S: States>(state: S) -> Self {
+        Self::new(state, TypstStateScopeExit::Cancel)
+    }
+
+    /// Park the job when `state` is exited, resuming it when `state` is entered again.
+    pub fn park<S: States>(state: S) -> Self {
+        Self::new(state, TypstStateScopeExit::Park)
+    }
+
+    /// If this scope refers to `state`.
+    pub fn is_for<S: States>(&self, state: &S) -> bool {
+        self.state == state_key(state)
+    }
+}
+
+fn state_key<S: States>(state: &S) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    TypeId::of::<S>().hash(&mut hasher);
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Cancels and parks jobs scoped to states of type `S` as those states are exited, and resumes parked jobs
+/// when they're entered again. Add one for each states type used in a [`TypstStateScope`], alongside [`crate::TypstTexturesPlugin`].
+pub struct TypstStateScopePlugin<S: States>(PhantomData<S>);
+
+impl<S: States> Default for TypstStateScopePlugin<S> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<S: States> Plugin for TypstStateScopePlugin<S> {
+    fn build(&self, app: &mut bevy_app::App) {
+        app.add_systems(
+            Last,
+            system_scope_jobs::<S>.before(TypstTextureServer::system_do_jobs),
+        );
+    }
+
+    fn is_unique(&self) -> bool {
+        true
+    }
+}
+
+fn system_scope_jobs<S: States>(
+    mut transitions: MessageReader<StateTransitionEvent<S>>,
+    template_server: Option<ResMut<TypstTextureServer>>,
+) {
+    let Some(mut template_server) = template_server else {
+        transitions.clear();
+        return;
+    };
+    for transition in transitions.read() {
+        if let Some(exited) = &transition.exited {
+            template_server.exit_state_scope(exited);
+        }
+        if let Some(entered) = &transition.entered {
+            template_server.enter_state_scope(entered);
+        }
+    }
+}
+
+impl TypstTextureServer {
+    fn exit_state_scope<S: States>(&mut self, state: &S) {
+        let mut remaining = VecDeque::with_capacity(self.jobs.len());
+        for job in std::mem::take(&mut self.jobs) {
+            match job.job_options.state_scope {
+                Some(scope) if scope.is_for(state) => match scope.on_exit {
+                    TypstStateScopeExit::Cancel => self.job_cancelled(job),
+                    TypstStateScopeExit::Park => self.parked_jobs.push(job),
+                },
+                _ => remaining.push_back(job),
+            }
+        }
+        self.jobs = remaining;
+    }
+
+    fn enter_state_scope<S: States>(&mut self, state: &S) {
+        let (resumed, parked): (Vec<_>, Vec<_>) = std::mem::take(&mut self.parked_jobs)
+            .into_iter()
+            .partition(|job| {
+                job.job_options
+                    .state_scope
+                    .is_some_and(|scope| scope.is_for(state))
+            });
+        self.parked_jobs = parked;
+        for job in resumed {
+            self.enqueue(job);
+        }
+    }
+}
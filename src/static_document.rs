@@ -0,0 +1,138 @@
+use std::path::PathBuf;
+
+use bevy_asset::{AssetLoader, Handle};
+use bevy_image::Image;
+use bevy_reflect::TypePath;
+use serde::{Deserialize, Serialize};
+use typst::{foundations::Dict, layout::PagedDocument};
+
+use crate::{
+    TypstJobOptions, TypstTextureServer,
+    asset_loading::{
+        TypstArchiveLimits, TypstArchivePasswords, TypstAssetError, TypstTemplateSettings,
+        read_template,
+    },
+    font_library::TypstEngineFonts,
+    metadata,
+    progress::TypstLoadProgress,
+    raster::RasterSettings,
+};
+
+/// How a static document is rendered by [`TypstTextureServer::load_static`]. These are the loader settings of
+/// [`TypstStaticLoader`], so they can also be set in a `.meta` file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TypstStaticOptions {
+    /// How many pixels correspond to a typst `pt`. Defaults to `1.`
+    pub pixels_per_pt: f32,
+    /// Which page to render, clamped by the total number of pages in the document. Defaults to the first page.
+    pub specific_page: Option<usize>,
+    /// Render the page containing the element with this label instead of `specific_page`, if any element has it.
+    pub page_label: Option<String>,
+    /// How the template is found within its archive.
+    #[serde(default)]
+    pub template: TypstTemplateSettings,
+}
+
+impl Default for TypstStaticOptions {
+    fn default() -> Self {
+        Self {
+            pixels_per_pt: 1.0,
+            specific_page: None,
+            page_label: None,
+            template: TypstTemplateSettings::default(),
+        }
+    }
+}
+
+/// Loads a `.static.zip` template or `.static.typ` file directly as an [`Image`], compiling and rasterizing it
+/// with no inputs while the asset loads, for documents such as manuals and credits that never change. These
+/// never go through the job queue of the [`TypstTextureServer`]. The `static.` extensions keep plain `.zip` and
+/// `.typ` files loading as templates, and untyped loads of them from resolving to images.
+#[derive(Debug, Default, TypePath)]
+pub struct TypstStaticLoader {
+    pub archive_limits: TypstArchiveLimits,
+    pub passwords: TypstArchivePasswords,
+    pub progress: TypstLoadProgress,
+    /// Fonts for compiling the document with, shared with the [`TypstTextureServer`].
+    pub engine_fonts: TypstEngineFonts,
+}
+
+#[cfg(not(feature = "tar-archives"))]
+const STATIC_EXTENSIONS: &[&str] = &["static.zip", "static.typ"];
+#[cfg(feature = "tar-archives")]
+const STATIC_EXTENSIONS: &[&str] = &[
+    "static.zip",
+    "static.typ",
+    "static.tar",
+    "static.tar.gz",
+    "static.tgz",
+];
+
+impl AssetLoader for TypstStaticLoader {
+    type Asset = Image;
+
+    type Settings = TypstStaticOptions;
+
+    type Error = TypstAssetError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn bevy_asset::io::Reader,
+        settings: &Self::Settings,
+        load_context: &mut bevy_asset::LoadContext<'_>,
+    ) -> std::result::Result<Self::Asset, Self::Error> {
+        let template = read_template(
+            reader,
+            load_context,
+            &settings.template,
+            &self.archive_limits,
+            &self.passwords,
+            &self.progress,
+        )
+        .await?;
+        let (engine, _) = self.engine_fonts.to_engine(template);
+        let document = engine
+            .compile_with_input::<_, PagedDocument>(Dict::new())
+            .output
+            .map_err(TypstAssetError::Compile)?;
+        let page_index = settings
+            .page_label
+            .as_ref()
+            .and_then(|label| metadata::page_of_label(&document, label))
+            .or(settings.specific_page)
+            .unwrap_or(0)
+            .min(document.pages.len().saturating_sub(1));
+        let page = document
+            .pages
+            .get(page_index)
+            .ok_or(TypstAssetError::EmptyDocument)?;
+        let options = TypstJobOptions {
+            pixels_per_pt: settings.pixels_per_pt,
+            ..Default::default()
+        };
+        let rendered = typst_render::render(page, settings.pixels_per_pt);
+        Ok(RasterSettings::from_options(&options)
+            .build_image(rendered)
+            .0)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        STATIC_EXTENSIONS
+    }
+}
+
+impl TypstTextureServer {
+    /// Load a template with no inputs as a static image, rendered while it loads rather than by a job. The
+    /// path needs one of the extensions of the [`TypstStaticLoader`], i.e. `manual.static.zip`.
+    pub fn load_static(
+        &self,
+        path: impl Into<PathBuf>,
+        options: TypstStaticOptions,
+    ) -> Handle<Image> {
+        self.asset_server
+            .load_with_settings::<Image, TypstStaticOptions>(
+                path.into(),
+                move |settings: &mut TypstStaticOptions| *settings = options.clone(),
+            )
+    }
+}
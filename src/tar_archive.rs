@@ -0,0 +1,96 @@
+use std::{io::Read, path::Path};
+
+use flate2::read::GzDecoder;
+
+use crate::{
+    asset_loading::{TypstArchiveLimits, TypstAssetError, TypstTemplateSettings},
+    file_resolver::{
+        DEFAULT_ENTRY_POINTS, StructuredInMemoryTemplate, TemplateFileSet, TemplateLayout,
+        check_entry_count, read_limited,
+    },
+};
+
+/// How a tar archive is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TarCompression {
+    /// A plain `.tar`.
+    None,
+    /// A `.tar.gz` or `.tgz`.
+    Gzip,
+}
+
+impl TarCompression {
+    /// The compression of a tar archive with the given full extension, or `None` if it isn't a tar archive.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "tar" => Some(TarCompression::None),
+            "tar.gz" | "tgz" => Some(TarCompression::Gzip),
+            _ => None,
+        }
+    }
+}
+
+impl StructuredInMemoryTemplate {
+    /// Load a template from a tar archive, using [`DEFAULT_ENTRY_POINTS`] to find the main file.
+    pub fn from_tar(
+        archive: impl Read,
+        compression: TarCompression,
+    ) -> Result<Self, TypstAssetError> {
+        Self::from_tar_with_settings(
+            archive,
+            compression,
+            &TypstTemplateSettings::default(),
+            &TypstArchiveLimits::default(),
+        )
+    }
+
+    /// Load a template from a tar archive laid out like a zipped template, as per
+    /// [`StructuredInMemoryTemplate::from_zip_with_settings`]. A leading `./` on entries is ignored.
+    pub fn from_tar_with_settings(
+        archive: impl Read,
+        compression: TarCompression,
+        settings: &TypstTemplateSettings,
+        limits: &TypstArchiveLimits,
+    ) -> Result<Self, TypstAssetError> {
+        match compression {
+            TarCompression::None => read_tar(archive, settings, limits),
+            TarCompression::Gzip => read_tar(GzDecoder::new(archive), settings, limits),
+        }
+    }
+}
+
+fn read_tar(
+    archive: impl Read,
+    settings: &TypstTemplateSettings,
+    limits: &TypstArchiveLimits,
+) -> Result<StructuredInMemoryTemplate, TypstAssetError> {
+    let mut archive = tar::Archive::new(archive);
+    let mut total_bytes = 0;
+    let mut count = 0;
+    let mut files = vec![];
+    for entry in archive.entries().map_err(TypstAssetError::Io)? {
+        let mut entry = entry.map_err(TypstAssetError::Io)?;
+        count += 1;
+        check_entry_count(count, limits)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry
+            .path()
+            .map_err(TypstAssetError::Io)?
+            .to_string_lossy()
+            .into_owned();
+        let buf = read_limited(&mut entry, Path::new(&name), limits, &mut total_bytes)?;
+        files.push((name, buf));
+    }
+    // Tar archives can't be listed without reading them, so the root is only known once every file is read.
+    let names: Vec<String> = files.iter().map(|(name, _)| name.clone()).collect();
+    let layout = TemplateLayout::new(settings, Some(&names))?;
+    let mut set = TemplateFileSet::new(settings);
+    for (name, buf) in files {
+        if let Some(path) = set.locate(&layout, &name) {
+            set.add(&path, buf)?;
+        }
+    }
+    set.finish(settings.main.as_deref(), DEFAULT_ENTRY_POINTS)
+}
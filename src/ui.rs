@@ -0,0 +1,141 @@
+use std::path::PathBuf;
+
+use bevy_asset::Assets;
+use bevy_ecs::{
+    component::Component,
+    query::Changed,
+    system::{Query, Res, ResMut},
+};
+use bevy_image::Image;
+use bevy_math::Rect;
+use bevy_ui::{ComputedNode, widget::ImageNode};
+use typst::foundations::Dict;
+
+use crate::{TypstJobOptions, TypstRenderInfo, TypstRenderTarget, TypstTextureServer};
+
+/// How far the on-screen size of a node can drift from its render, as a fraction, before it's re-rendered.
+const RESIZE_TOLERANCE: f32 = 0.01;
+
+/// A UI image rendered from a typst template at the node's on-screen resolution. The template is re-rendered
+/// when this component changes, and when the node's computed size changes, such as from a window resize or a
+/// change of UI scale, so text stays sharp. The previous render stays visible until the new one has loaded.
+///
+/// The node should be sized independently of its image, i.e. with an explicit `width` and `height` or `aspect_ratio`
+/// on its `Node`, otherwise each render would resize the node it's rendered for.
+#[derive(Debug, Clone, Component)]
+#[require(ImageNode, TypstUiNodeRender)]
+pub struct TypstUiNode {
+    pub template: PathBuf,
+    pub input: Dict,
+    /// Options for the node's jobs. `pixels_per_pt` is only used for the first render, before the size of
+    /// the template's page is known.
+    pub options: TypstJobOptions,
+}
+
+impl TypstUiNode {
+    pub fn new(template: impl Into<PathBuf>, input: impl Into<Dict>) -> Self {
+        Self {
+            template: template.into(),
+            input: input.into(),
+            options: TypstJobOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: TypstJobOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+/// The renders of a [`TypstUiNode`], managed by this crate.
+#[derive(Debug, Clone, Default, Component)]
+pub struct TypstUiNodeRender {
+    /// The render currently shown by the node.
+    pub current: Option<TypstRenderTarget>,
+    /// A newer render that hasn't finished loading yet.
+    pub pending: Option<TypstRenderTarget>,
+    /// The pixels per point of the latest render.
+    pub pixels_per_pt: Option<f32>,
+}
+
+impl TypstUiNodeRender {
+    fn submit(
+        &mut self,
+        template_server: &mut TypstTextureServer,
+        node: &TypstUiNode,
+        pixels_per_pt: f32,
+    ) {
+        if let Some(pending) = self.pending.take() {
+            template_server.cancel(pending.job);
+        }
+        let mut options = node.options.clone();
+        options.pixels_per_pt = pixels_per_pt;
+        self.pending = Some(template_server.submit_with_dict_input(
+            node.template.clone(),
+            node.input.clone(),
+            options,
+        ));
+        self.pixels_per_pt = Some(pixels_per_pt);
+    }
+}
+
+pub(crate) fn system_queue_typst_ui_nodes(
+    mut template_server: ResMut<TypstTextureServer>,
+    mut nodes: Query<(&TypstUiNode, &mut TypstUiNodeRender), Changed<TypstUiNode>>,
+) {
+    for (node, mut render) in &mut nodes {
+        let pixels_per_pt = render.pixels_per_pt.unwrap_or(node.options.pixels_per_pt);
+        render.submit(&mut template_server, node, pixels_per_pt);
+    }
+}
+
+pub(crate) fn system_resize_typst_ui_nodes(
+    mut template_server: ResMut<TypstTextureServer>,
+    infos: Res<Assets<TypstRenderInfo>>,
+    mut nodes: Query<(&TypstUiNode, &ComputedNode, &mut TypstUiNodeRender)>,
+) {
+    for (node, computed, mut render) in &mut nodes {
+        let target_width = computed.size().x;
+        let Some(info) = render
+            .current
+            .as_ref()
+            .or(render.pending.as_ref())
+            .and_then(|target| infos.get(&target.info))
+        else {
+            continue;
+        };
+        // The width of the output in points, after any rotation and without padding for compression.
+        let width_pt = info.content_size_px.0 as f32 / info.pixels_per_pt;
+        if target_width <= 0.0 || width_pt <= 0.0 {
+            continue;
+        }
+        let pixels_per_pt = target_width / width_pt;
+        let rendered = render.pixels_per_pt.unwrap_or(info.pixels_per_pt);
+        if (pixels_per_pt / rendered - 1.0).abs() > RESIZE_TOLERANCE {
+            render.submit(&mut template_server, node, pixels_per_pt);
+        }
+    }
+}
+
+pub(crate) fn system_update_typst_ui_nodes(
+    images: Res<Assets<Image>>,
+    infos: Res<Assets<TypstRenderInfo>>,
+    mut nodes: Query<(&mut TypstUiNodeRender, &mut ImageNode)>,
+) {
+    for (mut render, mut image_node) in &mut nodes {
+        let Some(pending) = &render.pending else {
+            continue;
+        };
+        if !images.contains(&pending.image) {
+            continue;
+        }
+        if let Some(info) = infos.get(&pending.info) {
+            // Cropped to the page so that any padding for compression isn't shown.
+            let (width_px, height_px) = info.content_size_px;
+            image_node.rect = (info.content_size_px != info.size_px)
+                .then(|| Rect::new(0.0, 0.0, width_px as f32, height_px as f32));
+        }
+        image_node.image = pending.image.clone();
+        render.current = render.pending.take();
+    }
+}
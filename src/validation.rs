@@ -0,0 +1,105 @@
+use bevy_asset::{AssetEvent, AssetServer, Assets, LoadState};
+use bevy_ecs::{message::MessageReader, system::Res};
+use typst::{diag::SourceDiagnostic, foundations::Dict, layout::PagedDocument, syntax::Source};
+
+use crate::{
+    TypstTextureServer, ambient::TypstAmbientInputs, asset_loading::TypstTemplate,
+    file_resolver::StructuredInMemoryTemplate, font_library::TypstEngineFonts,
+    job_error::TypstJobError,
+};
+
+impl StructuredInMemoryTemplate {
+    /// Check that this template compiles, with no input besides `ambient`. Templates whose input schema has
+    /// required keys would fail without them, so for those only the syntax of their sources is checked.
+    pub fn validate(&self, ambient: &TypstAmbientInputs) -> Result<(), TypstJobError> {
+        self.validate_with_fonts(ambient, &TypstEngineFonts::default())
+    }
+
+    /// Check that this template compiles as per [`StructuredInMemoryTemplate::validate`], with the font config and
+    /// library fonts of `fonts`.
+    pub fn validate_with_fonts(
+        &self,
+        ambient: &TypstAmbientInputs,
+        fonts: &TypstEngineFonts,
+    ) -> Result<(), TypstJobError> {
+        if self
+            .loaded_toml
+            .inputs
+            .keys
+            .values()
+            .any(|spec| !spec.optional)
+        {
+            return self.validate_syntax();
+        }
+        let (engine, _) = fonts.to_engine(self.clone());
+        let mut input = Dict::new();
+        ambient.insert_into(&mut input);
+        engine
+            .compile_with_input::<_, PagedDocument>(input)
+            .output
+            .map(|_| ())
+            .map_err(TypstJobError::compile)
+    }
+
+    fn validate_syntax(&self) -> Result<(), TypstJobError> {
+        let main = Source::detached(self.loaded_main.clone());
+        let errors: Vec<SourceDiagnostic> = std::iter::once(&main)
+            .chain(&self.source_resolver)
+            .flat_map(|source| source.root().errors())
+            .map(|error| SourceDiagnostic::error(error.span, error.message).with_hints(error.hints))
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(TypstJobError::Compile(errors))
+        }
+    }
+}
+
+/// Panics when a template known to the server fails to load or compile, if [`TypstTextureServer::fail_fast`]
+/// is set.
+pub(crate) fn system_fail_fast_on_broken_templates(
+    template_server: Res<TypstTextureServer>,
+    mut template_events: MessageReader<AssetEvent<TypstTemplate>>,
+    templates: Res<Assets<TypstTemplate>>,
+    asset_server: Res<AssetServer>,
+    ambient_inputs: Option<Res<TypstAmbientInputs>>,
+) {
+    if !template_server.fail_fast {
+        template_events.clear();
+        return;
+    }
+    for handle in template_server.templates.values() {
+        if let Some(LoadState::Failed(error)) = asset_server.get_load_state(handle) {
+            panic!(
+                "[TYPST FAIL FAST] Template {:?} failed to load: {}",
+                handle.path(),
+                error
+            );
+        }
+    }
+    let ambient = ambient_inputs.as_deref().cloned().unwrap_or_default();
+    for event in template_events.read() {
+        let (AssetEvent::Added { id } | AssetEvent::Modified { id }) = event else {
+            continue;
+        };
+        let Some(template) = templates.get(*id) else {
+            continue;
+        };
+        if let Err(error) = template
+            .0
+            .validate_with_fonts(&ambient, &template_server.engine_fonts)
+        {
+            let diagnostics: Vec<String> = error
+                .diagnostics(Some(&template.0))
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            panic!(
+                "[TYPST FAIL FAST] Template {:?} failed to compile:\n{}",
+                asset_server.get_path(*id),
+                diagnostics.join("\n")
+            );
+        }
+    }
+}